@@ -0,0 +1,64 @@
+use serde::{Deserialize, Serialize};
+
+use crate::messages::Answer;
+use crate::types::Applicant;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum ApiError {
+    DuplicateUser,
+    IncorrectSolution {
+        given_solution: Answer,
+    },
+    // How many answers in a batch were right - not which ones
+    IncorrectBatch {
+        correct: u32,
+        total: u32,
+    },
+    DeserializeError,
+    ApplicantsNotFound {
+        applicants_found: Vec<Applicant>,
+        applicants_not_found: Vec<String>,
+    },
+    NoUserFound,
+    ValidationFailed {
+        errors: Vec<FieldError>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct FieldError {
+    pub field: String,
+    pub msg: String,
+}
+
+// The `code` on every error response. Clients match on these instead of the
+// message, so once one has shipped it must never change
+pub mod codes {
+    pub const DUPLICATE_USER: &str = "duplicate_user";
+    pub const INCORRECT_SOLUTION: &str = "incorrect_solution";
+    pub const APPLICANTS_NOT_FOUND: &str = "applicants_not_found";
+    pub const NO_USER_FOUND: &str = "no_user_found";
+    pub const VALIDATION_FAILED: &str = "validation_failed";
+    pub const BAD_REQUEST: &str = "bad_request";
+    pub const NOT_FOUND: &str = "not_found";
+    pub const INTERNAL_ERROR: &str = "internal_error";
+    pub const UNAUTHORIZED: &str = "unauthorized";
+    pub const JOB_NOT_FOUND: &str = "job_not_found";
+    pub const IDEMPOTENCY_KEY_REUSED: &str = "idempotency_key_reused";
+    pub const TOKEN_REVOKED: &str = "token_revoked";
+    pub const TOKEN_EXPIRED: &str = "token_expired";
+    pub const EMAIL_DISABLED: &str = "email_disabled";
+    pub const CYCLE_NOT_FOUND: &str = "cycle_not_found";
+    pub const DUPLICATE_CYCLE: &str = "duplicate_cycle";
+    pub const PROGRAM_REQUIRED: &str = "program_required";
+    pub const ANSWER_REQUIRED: &str = "answer_required";
+    pub const BATCH_REQUIRED: &str = "batch_required";
+    pub const BATCH_NOT_FOUND: &str = "batch_not_found";
+    pub const BATCH_EXPIRED: &str = "batch_expired";
+    pub const BATCH_ANSWERED: &str = "batch_answered";
+    pub const UNSUPPORTED_MEDIA_TYPE: &str = "unsupported_media_type";
+    pub const SANDBOX_UNAVAILABLE: &str = "sandbox_unavailable";
+    pub const RUN_IN_PROGRESS: &str = "run_in_progress";
+}
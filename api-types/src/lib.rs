@@ -0,0 +1,11 @@
+pub mod errors;
+pub mod messages;
+pub mod types;
+
+pub use errors::{codes, ApiError, FieldError};
+pub use messages::{
+    Answer, BatchResponse, ErrorResponse, GetChallengeString, HandleForgotTokenResponse,
+    HintResponse, Language, ProgramSubmission, RegisterRequest, RegisterResponse,
+    RotateTokenResponse, RunResponse,
+};
+pub use types::{Applicant, Leaderboard, LeaderboardEntry, StageTiming, TestOutcome, TestRun};
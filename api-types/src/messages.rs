@@ -0,0 +1,145 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::ApiError;
+use crate::types::TestRun;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RegisterResponse {
+    pub token: String,
+    pub challenge_string: String,
+}
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RegisterRequest {
+    pub name: String,
+    pub nuid: String,
+    #[serde(default)]
+    pub email: Option<String>,
+    // Shown on the public leaderboard once you've solved it - leave it out to
+    // stay off the leaderboard
+    #[serde(default)]
+    pub display_name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct HandleForgotTokenResponse {
+    // Tokens are only ever emailed - this just says to go and look
+    pub msg: String,
+}
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RotateTokenResponse {
+    // The old token stops working as soon as this is sent
+    pub token: String,
+}
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GetChallengeString {
+    pub challenge_string: String,
+    // What to work out from the challenge string, and the shape of the answer
+    pub task: String,
+    // The stage this is for, counting from 1, out of however many the cycle has
+    pub stage: u32,
+    pub stages: u32,
+    // They've solved the last stage, so this is the one they already solved
+    pub complete: bool,
+}
+
+// A solution to whichever challenge the stage is. The shape says which kind of
+// answer it is - k-mer counts are an object, a sequence is a string, and GC
+// content is a list of fractions
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(untagged)]
+pub enum Answer {
+    Counts(HashMap<String, u64>),
+    Sequence(String),
+    Fractions(Vec<f64>),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    Python,
+    C,
+    Cpp,
+}
+
+impl Language {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Language::Python => "python",
+            Language::C => "c",
+            Language::Cpp => "cpp",
+        }
+    }
+}
+
+// A program for a stage that takes one instead of an answer. It reads a
+// challenge string on stdin and prints its answer to stdout, as JSON
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ProgramSubmission {
+    pub language: Language,
+    pub source: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RunResponse {
+    // Every test passed
+    pub ok: bool,
+    pub msg: String,
+    // What the compiler had to say, if it didn't compile
+    #[serde(default)]
+    pub compiler_output: Option<String>,
+    // In the order they ran - empty if it didn't compile
+    pub tests: Vec<TestRun>,
+}
+
+// Fresh challenge strings for a stage that's answered in batches. Answer them
+// all, in order, before the time's up
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BatchResponse {
+    pub batch_id: String,
+    pub challenge_strings: Vec<String>,
+    pub task: String,
+    // How long there is to answer, from when this was sent
+    pub seconds: u32,
+}
+
+// Every hint unlocked so far for the stage they're on, oldest first. Each
+// request unlocks the next one, until there are none left
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct HintResponse {
+    pub stage: u32,
+    pub hints: Vec<String>,
+    pub remaining: u32,
+    // Points off their score for each hint - reviewers see how many they used
+    pub penalty: u32,
+}
+
+// The server builds these out of string literals, clients read them off the
+// wire - hence the Cows
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ErrorResponse<'a> {
+    // These first few fields make this a problem details object (RFC 7807)
+    #[serde(rename = "type")]
+    pub problem_type: Cow<'a, str>,
+    pub title: Cow<'a, str>,
+    pub status: u16,
+    // Stable and machine readable, unlike `msg` - see errors::codes
+    pub code: Cow<'a, str>,
+    pub msg: Cow<'a, str>,
+    #[serde(flatten)]
+    pub error: Option<ApiError>,
+}
@@ -0,0 +1,97 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Applicant {
+    // serde writes std Durations out as {"secs": .., "nanos": ..}
+    #[cfg_attr(
+        feature = "openapi",
+        schema(value_type = Object, example = json!({"secs": 1042, "nanos": 0}))
+    )]
+    pub time_to_completion: Duration,
+    pub ok: bool,
+    pub name: String,
+    pub nuid: String,
+    // Every stage they've unlocked so far, in order
+    pub stages: Vec<StageTiming>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct StageTiming {
+    pub stage: u32,
+    // From registering to this stage unlocking
+    #[cfg_attr(
+        feature = "openapi",
+        schema(value_type = Object, example = json!({"secs": 310, "nanos": 0}))
+    )]
+    pub unlocked_after: Duration,
+    // From unlocking it to solving it - missing until they have
+    #[cfg_attr(
+        feature = "openapi",
+        schema(value_type = Option<Object>, example = json!({"secs": 422, "nanos": 0}))
+    )]
+    pub time_to_solve: Option<Duration>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Leaderboard {
+    pub cycle: String,
+    // Fastest first
+    pub entries: Vec<LeaderboardEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct LeaderboardEntry {
+    // Applicants with the same time share a rank
+    pub rank: u32,
+    pub display_name: String,
+    // From registering to solving the last stage
+    #[cfg_attr(
+        feature = "openapi",
+        schema(value_type = Object, example = json!({"secs": 1042, "nanos": 0}))
+    )]
+    pub time_to_completion: Duration,
+}
+
+// One hidden test a program was run against
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TestRun {
+    pub test: u32,
+    pub outcome: TestOutcome,
+    #[cfg_attr(
+        feature = "openapi",
+        schema(value_type = Object, example = json!({"secs": 0, "nanos": 41000000}))
+    )]
+    pub runtime: Duration,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum TestOutcome {
+    Passed,
+    // It printed something, but not the right answer
+    WrongAnswer,
+    // Exited with an error, or was killed for going over its memory
+    Crashed,
+    TimedOut,
+    OutputTooLong,
+}
+
+impl TestOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TestOutcome::Passed => "passed",
+            TestOutcome::WrongAnswer => "wrong_answer",
+            TestOutcome::Crashed => "crashed",
+            TestOutcome::TimedOut => "timed_out",
+            TestOutcome::OutputTooLong => "output_too_long",
+        }
+    }
+}
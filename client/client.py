@@ -1,3 +1,6 @@
+import os
+import sys
+
 import click
 from dotenv import load_dotenv
 import requests
@@ -8,12 +11,13 @@ local = "http://localhost:8080"
 app = "https://generate-tech-app.xyz"
 
 
-def get_token(path, nuid):
-    return requests.get(f"{path}/forgot_token/{nuid}").json()["token"]
+# Tokens only ever come by email - this asks for yours to be sent again
+def forgot_token(path, nuid):
+    return requests.get(f"{path}/v1/forgot_token/{nuid}").json()["msg"]
 
 
 def get_challenge(path, token):
-    return requests.get(f"{path}/challenge/{token}").json()["challenge_string"]
+    return requests.get(f"{path}/v1/challenge/{token}").json()["challenge_string"]
 
 
 def find_kmers(k, challenge):
@@ -24,20 +28,23 @@ def find_kmers(k, challenge):
     return soln
 
 
-def submit_soln(path, challenge):
+def submit_soln(path, token, challenge):
     soln = find_kmers(3, challenge)
-    return requests.post(f"{path}/submit/{token}", json=soln)
+    return requests.post(f"{path}/v1/submit/{token}", json=soln)
 
 
 if __name__ == "__main__":
     load_dotenv()
 
     path = app
-    token = get_token(path, "001453760")
+    token = os.getenv("GENERATE_TOKEN")
+    if token is None:
+        print(forgot_token(path, "001453760"))
+        sys.exit("Set GENERATE_TOKEN to the token from the email")
 
     challenge = get_challenge(path, token)
 
-    r = submit_soln(path, challenge)
+    r = submit_soln(path, token, challenge)
 
     if r.status_code == 200:
         pprint(r.json())
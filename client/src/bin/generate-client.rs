@@ -0,0 +1,204 @@
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand};
+use generate_client::{Answer, Client, ClientError, Language, ProgramSubmission, DEFAULT_SERVER};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tokio::io::AsyncReadExt;
+
+/// Register for the Generate technical application, fetch your challenge, and
+/// submit your solution
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    /// The server to talk to
+    #[arg(long, env = "GENERATE_SERVER", default_value = DEFAULT_SERVER)]
+    server: String,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Register and get your token and challenge string
+    Register {
+        #[arg(long)]
+        name: String,
+        #[arg(long)]
+        nuid: String,
+        #[arg(long)]
+        email: Option<String>,
+        /// Put yourself on the public leaderboard under this name once you've solved it
+        #[arg(long)]
+        display_name: Option<String>,
+    },
+    /// See who's solved it fastest
+    Leaderboard {
+        /// The active cycle if left out
+        #[arg(long)]
+        cycle: Option<String>,
+    },
+    /// Have the token you registered with emailed to you again
+    ForgotToken { nuid: String },
+    /// Fetch your challenge string, and what to work out from it
+    Challenge {
+        #[arg(env = "GENERATE_TOKEN")]
+        token: String,
+    },
+    /// Unlock the next hint for the stage you're on - reviewers see how many
+    /// you used
+    Hint {
+        #[arg(env = "GENERATE_TOKEN")]
+        token: String,
+    },
+    /// Swap your token for a new one, if it's leaked - the old one stops working
+    RotateToken {
+        #[arg(env = "GENERATE_TOKEN")]
+        token: String,
+    },
+    /// Delete your registration and submissions for good - your token stops working
+    DeleteMyData {
+        #[arg(env = "GENERATE_TOKEN")]
+        token: String,
+    },
+    /// Submit a solution, as JSON shaped the way your challenge's task says
+    Submit {
+        #[arg(env = "GENERATE_TOKEN")]
+        token: String,
+        /// File to read the solution from, or stdin if left out
+        solution: Option<PathBuf>,
+    },
+    /// Upload a program for a stage that takes one, and see how it does on the
+    /// hidden tests
+    Run {
+        #[arg(env = "GENERATE_TOKEN")]
+        token: String,
+        /// A .py, .c or .cpp file - the extension says which language it is
+        program: PathBuf,
+    },
+    /// Get a batch of challenge strings, for a stage that's answered in batches
+    Batch {
+        #[arg(env = "GENERATE_TOKEN")]
+        token: String,
+    },
+    /// Answer a batch, as a JSON list with an answer for each string in order
+    AnswerBatch {
+        #[arg(env = "GENERATE_TOKEN")]
+        token: String,
+        batch_id: String,
+        /// File to read the answers from, or stdin if left out
+        answers: Option<PathBuf>,
+    },
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let client = Client::new(&cli.server);
+
+    let result = match cli.command {
+        Command::Register {
+            name,
+            nuid,
+            email,
+            display_name,
+        } => client
+            .register(&name, &nuid, email.as_deref(), display_name.as_deref())
+            .await
+            .map(|res| print(&res)),
+        Command::Leaderboard { cycle } => client
+            .leaderboard(cycle.as_deref())
+            .await
+            .map(|res| print(&res)),
+        Command::ForgotToken { nuid } => client.forgot_token(&nuid).await.map(|res| print(&res)),
+        Command::Challenge { token } => client.challenge(&token).await.map(|res| print(&res)),
+        Command::Hint { token } => client.hint(&token).await.map(|res| print(&res)),
+        Command::RotateToken { token } => client.rotate_token(&token).await.map(|res| print(&res)),
+        Command::DeleteMyData { token } => {
+            client.delete_my_data(&token).await.map(|res| print(&res))
+        }
+        Command::Submit { token, solution } => match read_json::<Answer>(solution).await {
+            Ok(solution) => client
+                .submit(&token, &solution)
+                .await
+                .map(|res| print(&res)),
+            Err(e) => {
+                eprintln!("Couldn't read your solution: {}", e);
+                return ExitCode::FAILURE;
+            }
+        },
+        Command::Batch { token } => client.start_batch(&token).await.map(|res| print(&res)),
+        Command::AnswerBatch {
+            token,
+            batch_id,
+            answers,
+        } => match read_json::<Vec<Answer>>(answers).await {
+            Ok(answers) => client
+                .answer_batch(&token, &batch_id, &answers)
+                .await
+                .map(|res| print(&res)),
+            Err(e) => {
+                eprintln!("Couldn't read your answers: {}", e);
+                return ExitCode::FAILURE;
+            }
+        },
+        Command::Run { token, program } => match read_program(program).await {
+            Ok(program) => client.run(&token, &program).await.map(|res| print(&res)),
+            Err(e) => {
+                eprintln!("Couldn't read your program: {}", e);
+                return ExitCode::FAILURE;
+            }
+        },
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(ClientError::Api(e)) => {
+            eprintln!("{}", e.msg);
+            eprintln!("{}", serde_json::to_string_pretty(&e).unwrap_or_default());
+            ExitCode::FAILURE
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn read_json<T: DeserializeOwned>(path: Option<PathBuf>) -> Result<T, String> {
+    let raw = match path {
+        Some(path) => tokio::fs::read_to_string(path)
+            .await
+            .map_err(|e| e.to_string())?,
+        None => {
+            let mut raw = String::new();
+            tokio::io::stdin()
+                .read_to_string(&mut raw)
+                .await
+                .map_err(|e| e.to_string())?;
+            raw
+        }
+    };
+
+    serde_json::from_str(&raw).map_err(|e| e.to_string())
+}
+
+async fn read_program(path: PathBuf) -> Result<ProgramSubmission, String> {
+    let language = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("py") => Language::Python,
+        Some("c") => Language::C,
+        Some("cpp" | "cc" | "cxx") => Language::Cpp,
+        _ => return Err("it should be a .py, .c or .cpp file".to_string()),
+    };
+    let source = tokio::fs::read_to_string(path)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(ProgramSubmission { language, source })
+}
+
+fn print<T: Serialize>(res: &T) {
+    println!("{}", serde_json::to_string_pretty(res).unwrap_or_default());
+}
@@ -0,0 +1,303 @@
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+
+pub use api_types::{
+    codes, Answer, ApiError, BatchResponse, ErrorResponse, GetChallengeString,
+    HandleForgotTokenResponse, HintResponse, Language, Leaderboard, LeaderboardEntry,
+    ProgramSubmission, RegisterRequest, RegisterResponse, RotateTokenResponse, RunResponse,
+    TestOutcome, TestRun,
+};
+
+pub const DEFAULT_SERVER: &str = "https://generate-tech-app.xyz";
+
+#[derive(thiserror::Error, Debug)]
+pub enum ClientError {
+    // The server understood the request and said no - `code` says why
+    #[error("{} ({}): {}", .0.status, .0.code, .0.msg)]
+    Api(ErrorResponse<'static>),
+    #[error("the server sent back a {status} we couldn't make sense of: {body}")]
+    UnexpectedResponse { status: StatusCode, body: String },
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+}
+
+impl ClientError {
+    // The stable error code from the server, if the server got far enough to send one
+    pub fn code(&self) -> Option<&str> {
+        match self {
+            ClientError::Api(e) => Some(&e.code),
+            _ => None,
+        }
+    }
+}
+
+// A typed client for the v1 API
+#[derive(Clone, Debug)]
+pub struct Client {
+    base_url: String,
+    http: reqwest::Client,
+}
+
+impl Client {
+    // `server` is the host, e.g. DEFAULT_SERVER - the API version is added here
+    pub fn new(server: &str) -> Client {
+        Client {
+            base_url: format!("{}/v1", server.trim_end_matches('/')),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    pub async fn register(
+        &self,
+        name: &str,
+        nuid: &str,
+        email: Option<&str>,
+        display_name: Option<&str>,
+    ) -> Result<RegisterResponse, ClientError> {
+        let request = RegisterRequest {
+            name: name.to_string(),
+            nuid: nuid.to_string(),
+            email: email.map(String::from),
+            display_name: display_name.map(String::from),
+        };
+        let res = self
+            .http
+            .post(self.url("register"))
+            .json(&request)
+            .send()
+            .await?;
+
+        parse(res).await
+    }
+
+    pub async fn forgot_token(&self, nuid: &str) -> Result<HandleForgotTokenResponse, ClientError> {
+        let res = self
+            .http
+            .get(self.url(&format!("forgot_token/{}", nuid)))
+            .send()
+            .await?;
+
+        parse(res).await
+    }
+
+    pub async fn challenge(&self, token: &str) -> Result<GetChallengeString, ClientError> {
+        let res = self
+            .http
+            .get(self.url(&format!("challenge/{}", token)))
+            .send()
+            .await?;
+
+        parse(res).await
+    }
+
+    // Swaps the token for a new one - use this if yours has leaked
+    pub async fn rotate_token(&self, token: &str) -> Result<RotateTokenResponse, ClientError> {
+        let res = self
+            .http
+            .post(self.url(&format!("rotate_token/{}", token)))
+            .send()
+            .await?;
+
+        parse(res).await
+    }
+
+    // Deletes your registration and every submission - there's no undoing this
+    pub async fn delete_my_data(&self, token: &str) -> Result<String, ClientError> {
+        let res = self
+            .http
+            .delete(self.url(&format!("my_data/{}", token)))
+            .send()
+            .await?;
+
+        parse(res).await
+    }
+
+    // The active cycle's leaderboard, unless you ask for another
+    pub async fn leaderboard(&self, cycle: Option<&str>) -> Result<Leaderboard, ClientError> {
+        let mut req = self.http.get(self.url("leaderboard"));
+        if let Some(cycle) = cycle {
+            req = req.query(&[("cycle", cycle)]);
+        }
+        let res = req.send().await?;
+
+        parse(res).await
+    }
+
+    // Ok means the solution was correct - an incorrect one comes back as a
+    // ClientError::Api with the `incorrect_solution` code
+    pub async fn submit(&self, token: &str, solution: &Answer) -> Result<String, ClientError> {
+        let res = self
+            .http
+            .post(self.url(&format!("submit/{}", token)))
+            .json(solution)
+            .send()
+            .await?;
+
+        parse(res).await
+    }
+
+    // For stages that take a program. Ok doesn't mean it passed - check `ok`
+    pub async fn run(
+        &self,
+        token: &str,
+        program: &ProgramSubmission,
+    ) -> Result<RunResponse, ClientError> {
+        let res = self
+            .http
+            .post(self.url(&format!("run/{}", token)))
+            .json(program)
+            .send()
+            .await?;
+
+        parse(res).await
+    }
+
+    // Unlocks the next hint for the stage you're on, if there's one left. Each
+    // costs points off your score, if the cycle says so
+    pub async fn hint(&self, token: &str) -> Result<HintResponse, ClientError> {
+        let res = self
+            .http
+            .get(self.url(&format!("hint/{}", token)))
+            .send()
+            .await?;
+
+        parse(res).await
+    }
+
+    // For stages answered in batches - the clock starts as soon as this returns
+    pub async fn start_batch(&self, token: &str) -> Result<BatchResponse, ClientError> {
+        let res = self
+            .http
+            .post(self.url(&format!("batch/{}", token)))
+            .send()
+            .await?;
+
+        parse(res).await
+    }
+
+    // Ok means every answer was correct - otherwise it's a ClientError::Api with
+    // the `incorrect_solution` code, and how many were right
+    pub async fn answer_batch(
+        &self,
+        token: &str,
+        batch_id: &str,
+        answers: &[Answer],
+    ) -> Result<String, ClientError> {
+        let res = self
+            .http
+            .post(self.url(&format!("batch/{}/{}", token, batch_id)))
+            .json(answers)
+            .send()
+            .await?;
+
+        parse(res).await
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.base_url, path)
+    }
+}
+
+async fn parse<T: DeserializeOwned>(res: reqwest::Response) -> Result<T, ClientError> {
+    let status = res.status();
+    let body = res.bytes().await?;
+
+    if status.is_success() {
+        if let Ok(parsed) = serde_json::from_slice(&body) {
+            return Ok(parsed);
+        }
+    } else if let Ok(err) = serde_json::from_slice::<ErrorResponse>(&body) {
+        return Err(ClientError::Api(err));
+    }
+
+    Err(ClientError::UnexpectedResponse {
+        status,
+        body: String::from_utf8_lossy(&body).into_owned(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::net::SocketAddr;
+
+    use serde_json::json;
+    use warp::Filter;
+
+    use super::{codes, Answer, Client, ClientError};
+
+    // Stands in for the real server, which needs a database
+    async fn stand_in() -> SocketAddr {
+        let register = warp::path!("v1" / "register")
+            .and(warp::post())
+            .and(warp::body::json())
+            .map(|request: super::RegisterRequest| {
+                warp::reply::json(&json!({
+                    "token": format!("token-for-{}", request.nuid),
+                    "challenge_string": "ACTG",
+                }))
+            });
+        let submit = warp::path!("v1" / "submit" / String)
+            .and(warp::post())
+            .and(warp::body::json())
+            .map(|_token: String, solution: Answer| {
+                warp::reply::with_status(
+                    warp::reply::json(&json!({
+                        "type": "about:blank",
+                        "title": "Bad Request",
+                        "status": 400,
+                        "code": "incorrect_solution",
+                        "msg": "Incorrect solution",
+                        "IncorrectSolution": { "given_solution": solution },
+                    })),
+                    warp::http::StatusCode::BAD_REQUEST,
+                )
+            });
+
+        let (addr, server) = warp::serve(register.or(submit)).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_register() {
+        let addr = stand_in().await;
+        let client = Client::new(&format!("http://{}", addr));
+
+        let res = client
+            .register("Ada", "001453760", None, None)
+            .await
+            .unwrap();
+
+        assert_eq!(res.token, "token-for-001453760");
+        assert_eq!(res.challenge_string, "ACTG");
+    }
+
+    #[tokio::test]
+    async fn test_api_errors_are_typed() {
+        let addr = stand_in().await;
+        let client = Client::new(&format!("http://{}/", addr));
+
+        let solution = Answer::Counts(HashMap::from([(String::from("ACT"), 1)]));
+        let err = client.submit("token", &solution).await.unwrap_err();
+
+        assert_eq!(err.code(), Some(codes::INCORRECT_SOLUTION));
+        match err {
+            ClientError::Api(e) => assert!(matches!(
+                e.error,
+                Some(super::ApiError::IncorrectSolution { given_solution }) if given_solution == solution
+            )),
+            other => panic!("expected an api error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unexpected_responses() {
+        let addr = stand_in().await;
+        let client = Client::new(&format!("http://{}", addr));
+
+        let err = client.challenge("token").await.unwrap_err();
+
+        assert!(matches!(err, ClientError::UnexpectedResponse { status, .. } if status == 404));
+    }
+}
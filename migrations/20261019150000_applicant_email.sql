@@ -0,0 +1,2 @@
+-- Applicants can optionally leave an email address when they register
+ALTER TABLE applicants ADD COLUMN IF NOT EXISTS email varchar;
@@ -0,0 +1,20 @@
+-- Outbox for webhook notifications. Rows are written in the same transaction as
+-- the change they describe, and each one is sent by a job on the jobs queue -
+-- so an event is never lost to a restart or a flaky receiver
+CREATE TABLE IF NOT EXISTS webhook_deliveries (
+    delivery_id bigserial PRIMARY KEY,
+    url varchar NOT NULL,
+    event varchar NOT NULL,
+    payload jsonb NOT NULL,
+    -- pending, delivered or failed (gave up retrying)
+    status varchar NOT NULL DEFAULT 'pending',
+    attempts integer NOT NULL DEFAULT 0,
+    created_at timestamp with time zone NOT NULL,
+    next_attempt_at timestamp with time zone NOT NULL,
+    last_attempt_at timestamp with time zone,
+    last_response_status integer,
+    last_error varchar
+);
+
+CREATE INDEX IF NOT EXISTS webhook_deliveries_due ON webhook_deliveries (next_attempt_at)
+    WHERE status = 'pending';
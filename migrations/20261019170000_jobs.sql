@@ -0,0 +1,33 @@
+-- Background jobs. Handlers write these in the same transaction as the change
+-- that caused them, and the runner picks them up with FOR UPDATE SKIP LOCKED
+CREATE TABLE IF NOT EXISTS jobs (
+    job_id bigserial PRIMARY KEY,
+    kind varchar NOT NULL,
+    payload jsonb NOT NULL,
+    -- pending, done or dead (ran out of attempts)
+    status varchar NOT NULL DEFAULT 'pending',
+    attempts integer NOT NULL DEFAULT 0,
+    max_attempts integer NOT NULL,
+    run_at timestamp with time zone NOT NULL,
+    created_at timestamp with time zone NOT NULL,
+    finished_at timestamp with time zone,
+    last_error varchar
+);
+
+CREATE INDEX IF NOT EXISTS jobs_due ON jobs (run_at) WHERE status = 'pending';
+
+-- Webhook retries belong to the job runner now, so webhook_deliveries is only
+-- the delivery log. Anything still waiting to go out gets a job of its own
+INSERT INTO jobs (kind, payload, max_attempts, run_at, created_at)
+SELECT
+    'deliver_webhook',
+    jsonb_build_object(
+        'kind', 'deliver_webhook',
+        'args', jsonb_build_object('delivery_id', delivery_id)
+    ),
+    12,
+    next_attempt_at,
+    created_at
+FROM webhook_deliveries WHERE status = 'pending';
+
+ALTER TABLE webhook_deliveries DROP COLUMN IF EXISTS next_attempt_at;
@@ -0,0 +1,17 @@
+-- Applicants apply in recruiting cycles. New registrations join whichever
+-- cycle is active, and there's only ever one of those
+CREATE TABLE IF NOT EXISTS cycles (
+    name varchar PRIMARY KEY,
+    active boolean NOT NULL DEFAULT false,
+    created_at timestamp with time zone NOT NULL
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS cycles_one_active ON cycles (active) WHERE active;
+
+INSERT INTO cycles (name, active, created_at) VALUES ('default', true, now())
+    ON CONFLICT DO NOTHING;
+
+-- Everyone who registered before cycles existed belongs to the default one
+ALTER TABLE applicants ADD COLUMN IF NOT EXISTS cycle varchar NOT NULL DEFAULT 'default'
+    REFERENCES cycles (name);
+ALTER TABLE applicants ALTER COLUMN cycle DROP DEFAULT;
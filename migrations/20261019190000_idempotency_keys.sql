@@ -0,0 +1,12 @@
+-- Idempotency-Key headers sent with /submit. A retry with the same key gets
+-- the first request's result back instead of counting as another attempt
+CREATE TABLE IF NOT EXISTS idempotency_keys (
+    nuid varchar NOT NULL REFERENCES applicants (nuid),
+    idempotency_key varchar NOT NULL,
+    -- sha256 of the solution, to catch a key being reused for a different one
+    request_hash varchar NOT NULL,
+    -- Filled in by the same transaction that claims the key
+    submission_id integer REFERENCES submissions (submission_id),
+    created_at timestamp with time zone NOT NULL,
+    PRIMARY KEY (nuid, idempotency_key)
+);
@@ -0,0 +1,15 @@
+-- Tokens can be given an expiry, and replaced if they leak
+ALTER TABLE applicants ADD COLUMN IF NOT EXISTS token_expires_at timestamp with time zone;
+
+-- Every token that's been replaced. Requests with one of these get told the
+-- token was revoked rather than that it doesn't exist, and the table doubles
+-- as the record of who rotated what
+CREATE TABLE IF NOT EXISTS token_rotations (
+    revoked_token uuid PRIMARY KEY,
+    nuid varchar NOT NULL REFERENCES applicants (nuid),
+    -- applicant or admin
+    rotated_by varchar NOT NULL,
+    rotated_at timestamp with time zone NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS token_rotations_nuid ON token_rotations (nuid);
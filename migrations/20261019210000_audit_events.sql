@@ -0,0 +1,31 @@
+-- Who changed what, and from where. Written by the model layer in the same
+-- transaction as the change itself. nuid isn't a foreign key so the trail
+-- outlives the applicant
+CREATE TABLE IF NOT EXISTS audit_events (
+    event_id bigserial PRIMARY KEY,
+    occurred_at timestamp with time zone NOT NULL,
+    -- e.g. applicant.registered or token.rotated
+    action varchar NOT NULL,
+    -- applicant or admin
+    actor varchar NOT NULL,
+    -- The applicant the action was about
+    nuid varchar,
+    ip varchar,
+    user_agent varchar,
+    request_id varchar,
+    details jsonb NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS audit_events_nuid ON audit_events (nuid);
+CREATE INDEX IF NOT EXISTS audit_events_occurred_at ON audit_events (occurred_at);
+
+-- Append only - nothing gets to rewrite history
+CREATE OR REPLACE FUNCTION audit_events_append_only() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'audit_events is append only';
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS audit_events_append_only ON audit_events;
+CREATE TRIGGER audit_events_append_only BEFORE UPDATE OR DELETE ON audit_events
+    FOR EACH ROW EXECUTE FUNCTION audit_events_append_only();
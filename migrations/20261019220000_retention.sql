@@ -0,0 +1,44 @@
+-- How long each cycle keeps applicants around, and what happens to them after.
+-- No retention_days means forever
+ALTER TABLE cycles ADD COLUMN IF NOT EXISTS retention_days integer;
+ALTER TABLE cycles ADD COLUMN IF NOT EXISTS retention_action varchar NOT NULL DEFAULT 'anonymize';
+
+ALTER TABLE applicants ADD COLUMN IF NOT EXISTS anonymized_at timestamp with time zone;
+
+-- Anonymizing an applicant changes their NUID and deleting one removes it, so
+-- everything hanging off the NUID has to follow along
+ALTER TABLE submissions
+    DROP CONSTRAINT IF EXISTS submissions_nuid_fkey,
+    ADD CONSTRAINT submissions_nuid_fkey FOREIGN KEY (nuid) REFERENCES applicants (nuid)
+        ON UPDATE CASCADE ON DELETE CASCADE;
+ALTER TABLE idempotency_keys
+    DROP CONSTRAINT IF EXISTS idempotency_keys_nuid_fkey,
+    ADD CONSTRAINT idempotency_keys_nuid_fkey FOREIGN KEY (nuid) REFERENCES applicants (nuid)
+        ON UPDATE CASCADE ON DELETE CASCADE,
+    DROP CONSTRAINT IF EXISTS idempotency_keys_submission_id_fkey,
+    ADD CONSTRAINT idempotency_keys_submission_id_fkey FOREIGN KEY (submission_id)
+        REFERENCES submissions (submission_id) ON DELETE CASCADE;
+ALTER TABLE token_rotations
+    DROP CONSTRAINT IF EXISTS token_rotations_nuid_fkey,
+    ADD CONSTRAINT token_rotations_nuid_fkey FOREIGN KEY (nuid) REFERENCES applicants (nuid)
+        ON UPDATE CASCADE ON DELETE CASCADE;
+
+-- The audit log stays append only, except that retention can scrub who an
+-- event was about. It has to ask for that explicitly, and can't touch
+-- anything else
+CREATE OR REPLACE FUNCTION audit_events_append_only() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'UPDATE' AND current_setting('generate.redacting', true) = 'on'
+        AND NEW.event_id = OLD.event_id
+        AND NEW.occurred_at = OLD.occurred_at
+        AND NEW.action = OLD.action
+        AND NEW.actor = OLD.actor
+        AND NEW.details = OLD.details
+        AND NEW.request_id IS NOT DISTINCT FROM OLD.request_id
+        AND NEW.ip IS NULL
+        AND NEW.user_agent IS NULL THEN
+        RETURN NEW;
+    END IF;
+    RAISE EXCEPTION 'audit_events is append only';
+END;
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,11 @@
+-- What was submitted and where from, for spotting copied answers. Submissions
+-- from before this have neither, except for what the audit log remembers
+ALTER TABLE submissions ADD COLUMN IF NOT EXISTS solution_hash varchar;
+ALTER TABLE submissions ADD COLUMN IF NOT EXISTS ip varchar;
+
+UPDATE submissions SET ip = audit_events.ip FROM audit_events
+    WHERE audit_events.action = 'applicant.submitted'
+    AND (audit_events.details->>'submission_id')::integer = submissions.submission_id;
+
+CREATE INDEX IF NOT EXISTS submissions_solution_hash ON submissions (solution_hash);
+CREATE INDEX IF NOT EXISTS submissions_ip ON submissions (ip, submission_time);
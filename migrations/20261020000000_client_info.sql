@@ -0,0 +1,16 @@
+-- Where each registration and submission came from, for rate limiting and
+-- spotting abuse. Filled in from the audit log for anything older
+ALTER TABLE applicants ADD COLUMN IF NOT EXISTS registration_ip varchar;
+ALTER TABLE applicants ADD COLUMN IF NOT EXISTS registration_user_agent varchar;
+ALTER TABLE submissions ADD COLUMN IF NOT EXISTS user_agent varchar;
+
+UPDATE applicants SET registration_ip = audit_events.ip,
+    registration_user_agent = audit_events.user_agent
+    FROM audit_events
+    WHERE audit_events.action = 'applicant.registered' AND audit_events.nuid = applicants.nuid;
+
+UPDATE submissions SET user_agent = audit_events.user_agent FROM audit_events
+    WHERE audit_events.action = 'applicant.submitted'
+    AND (audit_events.details->>'submission_id')::integer = submissions.submission_id;
+
+CREATE INDEX IF NOT EXISTS applicants_registration_ip ON applicants (registration_ip, registration_time);
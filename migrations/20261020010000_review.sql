@@ -0,0 +1,12 @@
+-- Where each applicant is in review, and what reviewers have said about them
+ALTER TABLE applicants ADD COLUMN IF NOT EXISTS status varchar NOT NULL DEFAULT 'new';
+
+CREATE TABLE IF NOT EXISTS applicant_notes (
+    note_id bigserial PRIMARY KEY,
+    nuid varchar NOT NULL REFERENCES applicants (nuid) ON UPDATE CASCADE ON DELETE CASCADE,
+    body varchar NOT NULL,
+    created_at timestamp with time zone NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS applicant_notes_nuid ON applicant_notes (nuid, created_at);
+CREATE INDEX IF NOT EXISTS applicants_cycle_status ON applicants (cycle, status);
@@ -0,0 +1,6 @@
+-- What an applicant wants to be called on the public leaderboard. Leaving it
+-- out keeps them off it
+ALTER TABLE applicants ADD COLUMN IF NOT EXISTS display_name varchar;
+
+CREATE INDEX IF NOT EXISTS applicants_leaderboard ON applicants (cycle)
+    WHERE display_name IS NOT NULL;
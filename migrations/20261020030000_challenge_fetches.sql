@@ -0,0 +1,3 @@
+-- When the applicant first came back for their challenge string, for the
+-- funnel in the admin stats. Nobody's been tracked before now
+ALTER TABLE applicants ADD COLUMN IF NOT EXISTS challenge_fetched_at timestamp with time zone;
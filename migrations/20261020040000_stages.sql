@@ -0,0 +1,37 @@
+-- A cycle's challenge can come in stages, each unlocked by solving the one
+-- before. A cycle with none here has the original single k-mer stage
+CREATE TABLE IF NOT EXISTS cycle_stages (
+    cycle varchar NOT NULL REFERENCES cycles (name) ON UPDATE CASCADE ON DELETE CASCADE,
+    stage integer NOT NULL CHECK (stage > 0),
+    challenge jsonb NOT NULL,
+    PRIMARY KEY (cycle, stage)
+);
+
+-- The stages an applicant has unlocked so far, and what each one asked of them
+CREATE TABLE IF NOT EXISTS applicant_stages (
+    nuid varchar NOT NULL REFERENCES applicants (nuid) ON UPDATE CASCADE ON DELETE CASCADE,
+    stage integer NOT NULL CHECK (stage > 0),
+    challenge_string varchar NOT NULL,
+    solution json NOT NULL,
+    unlocked_at timestamp with time zone NOT NULL,
+    solved_at timestamp with time zone,
+    PRIMARY KEY (nuid, stage)
+);
+
+-- Everyone so far had just the one stage
+INSERT INTO applicant_stages (nuid, stage, challenge_string, solution, unlocked_at, solved_at)
+SELECT a.nuid, 1, a.challenge_string, a.solution, a.registration_time,
+    (SELECT min(s.submission_time) FROM submissions s WHERE s.nuid = a.nuid AND s.ok)
+FROM applicants a
+ON CONFLICT DO NOTHING;
+
+-- When the last stage was solved
+ALTER TABLE applicants ADD COLUMN IF NOT EXISTS completed_at timestamp with time zone;
+UPDATE applicants a SET completed_at = st.solved_at
+FROM applicant_stages st
+WHERE st.nuid = a.nuid AND st.stage = 1 AND a.completed_at IS NULL;
+
+ALTER TABLE submissions ADD COLUMN IF NOT EXISTS stage integer NOT NULL DEFAULT 1;
+
+ALTER TABLE applicants DROP COLUMN IF EXISTS challenge_string;
+ALTER TABLE applicants DROP COLUMN IF EXISTS solution;
@@ -0,0 +1,12 @@
+-- Challenges are generated from a seed now, so the solution can be worked out
+-- again whenever it's needed instead of being stored. Each stage keeps the
+-- challenge it was generated from, in case the cycle's stages change later.
+-- Stages unlocked before this keep their stored solution
+ALTER TABLE applicant_stages ADD COLUMN IF NOT EXISTS seed bigint;
+ALTER TABLE applicant_stages ADD COLUMN IF NOT EXISTS challenge jsonb;
+ALTER TABLE applicant_stages ALTER COLUMN solution DROP NOT NULL;
+
+ALTER TABLE applicant_stages DROP CONSTRAINT IF EXISTS applicant_stages_reproducible;
+ALTER TABLE applicant_stages ADD CONSTRAINT applicant_stages_reproducible CHECK (
+    (seed IS NULL) = (challenge IS NULL) AND (seed IS NOT NULL OR solution IS NOT NULL)
+);
@@ -0,0 +1,26 @@
+-- Solutions aren't stored at all any more. Every stage keeps the challenge it
+-- was made from and the solution is worked out again when it's graded. Stages
+-- from before challenges were seeded were all k-mer counts of the stored
+-- string - k is however long the stored solution's k-mers are, or the cycle's
+-- stage says if the solution came out empty
+ALTER TABLE applicant_stages DROP CONSTRAINT IF EXISTS applicant_stages_reproducible;
+UPDATE applicant_stages st
+SET challenge = jsonb_build_object(
+    'kind', 'kmers',
+    'k', coalesce(
+        (SELECT length(kmer) FROM json_object_keys(st.solution) AS kmer LIMIT 1),
+        (SELECT (cs.challenge ->> 'k')::integer
+            FROM cycle_stages cs JOIN applicants a ON a.cycle = cs.cycle
+            WHERE a.nuid = st.nuid AND cs.stage = st.stage),
+        3
+    ),
+    'length', length(st.challenge_string)
+)
+WHERE st.challenge IS NULL;
+ALTER TABLE applicant_stages ALTER COLUMN challenge SET NOT NULL;
+ALTER TABLE applicant_stages DROP COLUMN IF EXISTS solution;
+
+-- Which shape each challenge was written in, so the shape can change without
+-- leaving older rows unreadable
+ALTER TABLE applicant_stages ADD COLUMN IF NOT EXISTS challenge_version integer NOT NULL DEFAULT 1;
+ALTER TABLE cycle_stages ADD COLUMN IF NOT EXISTS challenge_version integer NOT NULL DEFAULT 1;
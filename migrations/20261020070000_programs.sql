@@ -0,0 +1,16 @@
+-- Programs uploaded for program stages, and how they did on each hidden test.
+-- The inputs aren't kept, just the seed each one was generated from
+CREATE TABLE IF NOT EXISTS programs (
+    submission_id integer PRIMARY KEY REFERENCES submissions (submission_id) ON DELETE CASCADE,
+    language varchar NOT NULL,
+    source text NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS program_tests (
+    submission_id integer NOT NULL REFERENCES programs (submission_id) ON DELETE CASCADE,
+    test integer NOT NULL,
+    seed bigint NOT NULL,
+    outcome varchar NOT NULL,
+    runtime_us bigint NOT NULL,
+    PRIMARY KEY (submission_id, test)
+);
@@ -0,0 +1,13 @@
+-- Batches of challenge strings handed out for batch stages. Each is answered
+-- once, before its deadline. The strings aren't kept, just their seeds
+CREATE TABLE IF NOT EXISTS batches (
+    batch_id uuid PRIMARY KEY,
+    nuid varchar NOT NULL REFERENCES applicants (nuid) ON UPDATE CASCADE ON DELETE CASCADE,
+    stage integer NOT NULL,
+    seeds bigint[] NOT NULL,
+    issued_at timestamp with time zone NOT NULL,
+    deadline timestamp with time zone NOT NULL,
+    answered_at timestamp with time zone
+);
+
+CREATE INDEX IF NOT EXISTS batches_nuid ON batches (nuid);
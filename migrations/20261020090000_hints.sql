@@ -0,0 +1,9 @@
+-- Hints applicants have unlocked, and the penalty each one cost them at the time
+CREATE TABLE IF NOT EXISTS hints (
+    nuid varchar NOT NULL REFERENCES applicants (nuid) ON UPDATE CASCADE ON DELETE CASCADE,
+    stage integer NOT NULL,
+    hint integer NOT NULL CHECK (hint > 0),
+    penalty integer NOT NULL,
+    unlocked_at timestamp with time zone NOT NULL,
+    PRIMARY KEY (nuid, stage, hint)
+);
@@ -0,0 +1,5 @@
+-- Jobs are marked running while the runner works on them, instead of staying
+-- locked for the whole attempt. run_at doubles as the lease - a running job
+-- whose runner died gets picked up again once it passes
+DROP INDEX IF EXISTS jobs_due;
+CREATE INDEX IF NOT EXISTS jobs_due ON jobs (run_at) WHERE status IN ('pending', 'running');
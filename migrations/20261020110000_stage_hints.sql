@@ -0,0 +1,2 @@
+-- Hints are written by whoever sets up the cycle's stages, handed out in order
+ALTER TABLE cycle_stages ADD COLUMN IF NOT EXISTS hints text[] NOT NULL DEFAULT '{}';
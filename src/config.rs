@@ -1,7 +1,239 @@
+use std::net::IpAddr;
+
+use ipnet::IpNet;
+
 #[derive(serde::Deserialize, Clone, Debug)]
 pub struct Settings {
     pub database: DatabaseSettings,
     pub application: ApplicationSettings,
+    #[serde(default)]
+    pub admin: AdminSettings,
+    #[serde(default)]
+    pub webhooks: Vec<WebhookSettings>,
+    #[serde(default)]
+    pub email: EmailSettings,
+    #[serde(default)]
+    pub tokens: TokenSettings,
+    #[serde(default)]
+    pub analysis: AnalysisSettings,
+    #[serde(default)]
+    pub proxies: ProxySettings,
+    #[serde(default)]
+    pub sandbox: SandboxSettings,
+    #[serde(default)]
+    pub hints: HintSettings,
+}
+
+// The load balancers in front of us. Requests from these are trusted to say who
+// the client really is in Fly-Client-IP or X-Forwarded-For - anyone else could
+// put whatever they like in there
+#[derive(serde::Deserialize, Clone, Debug, Default)]
+pub struct ProxySettings {
+    // Addresses or CIDR ranges, e.g. "10.0.0.1" or "fdaa::/16"
+    #[serde(default, deserialize_with = "networks")]
+    pub trusted: Vec<IpNet>,
+}
+
+fn networks<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Vec<IpNet>, D::Error> {
+    let raw: Vec<String> = serde::Deserialize::deserialize(deserializer)?;
+    raw.iter()
+        .map(|network| {
+            network
+                .parse::<IpNet>()
+                .or_else(|_| network.parse::<IpAddr>().map(IpNet::from))
+                .map_err(|_| {
+                    serde::de::Error::custom(format!("{} isn't an IP address or range", network))
+                })
+        })
+        .collect()
+}
+
+// When a submission starts to look like cheating - see model::analysis
+#[derive(serde::Deserialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct AnalysisSettings {
+    // Solving it in fewer seconds than this after registering isn't believable
+    pub fastest: u64,
+    pub burst: BurstSettings,
+}
+
+// This many different applicants submitting from one IP within this many
+// seconds of each other
+#[derive(serde::Deserialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct BurstSettings {
+    pub applicants: i64,
+    pub seconds: u64,
+}
+
+impl Default for AnalysisSettings {
+    fn default() -> AnalysisSettings {
+        AnalysisSettings {
+            fastest: 60,
+            burst: BurstSettings::default(),
+        }
+    }
+}
+
+impl Default for BurstSettings {
+    fn default() -> BurstSettings {
+        BurstSettings {
+            applicants: 3,
+            seconds: 600,
+        }
+    }
+}
+
+// Limits on the programs applicants upload - see sandbox
+#[derive(serde::Deserialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct SandboxSettings {
+    // How long each test gets, in milliseconds
+    pub timeout_ms: u64,
+    pub memory_mb: u64,
+    // Output past this many kilobytes fails the test
+    pub output_kb: u64,
+    // How many programs compile or run at once - the rest wait their turn
+    pub concurrency: usize,
+}
+
+impl Default for SandboxSettings {
+    fn default() -> SandboxSettings {
+        SandboxSettings {
+            timeout_ms: 2000,
+            memory_mb: 256,
+            output_kb: 1024,
+            concurrency: 2,
+        }
+    }
+}
+
+// Every applicant's score starts at 100, and each hint they unlock takes this
+// many points off. Hints already unlocked keep the penalty they had then
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default)]
+#[serde(default)]
+pub struct HintSettings {
+    #[serde(deserialize_with = "penalty")]
+    pub penalty: i32,
+}
+
+fn penalty<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<i32, D::Error> {
+    let penalty: i64 = serde::Deserialize::deserialize(deserializer)?;
+    match i32::try_from(penalty) {
+        Ok(penalty) if (0..=100).contains(&penalty) => Ok(penalty),
+        _ => Err(serde::de::Error::custom(format!(
+            "a hint penalty of {} isn't between 0 and 100",
+            penalty
+        ))),
+    }
+}
+
+#[derive(serde::Deserialize, Clone, Debug, Default)]
+pub struct TokenSettings {
+    // Tokens stop working this many days after they're handed out, and have
+    // to be rotated by an admin - they never expire if this is unset
+    pub days: Option<i64>,
+}
+
+impl TokenSettings {
+    pub fn lifetime(&self) -> Option<chrono::Duration> {
+        self.days.map(chrono::Duration::days)
+    }
+}
+
+// Set ADMIN_TOKEN in the environment - the admin routes are locked if it's missing
+#[derive(serde::Deserialize, Clone, Default)]
+pub struct AdminSettings {
+    pub token: Option<String>,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct WebhookSettings {
+    pub url: String,
+    // Deliveries are signed with this - see webhooks::delivery::sign
+    pub secret: String,
+    // Names of the events to send, e.g. applicant.solved - all of them if empty
+    #[serde(default)]
+    pub events: Vec<String>,
+}
+
+#[derive(serde::Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct EmailSettings {
+    pub backend: MailBackend,
+    pub from: String,
+    // Where the API is publicly reachable, for the links in the emails
+    pub url: String,
+    // Templates live in <templates>/<name>.txt, and a cycle can override any of
+    // them with <templates>/<cycle>/<name>.txt
+    pub templates: String,
+    // Where the file backend drops its messages
+    pub dir: String,
+    pub smtp: SmtpSettings,
+}
+
+#[derive(serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MailBackend {
+    // Don't send any email
+    Off,
+    Smtp,
+    // Write each message to a file instead of sending it - for local testing
+    File,
+}
+
+// Set EMAIL_SMTP_PASSWORD in the environment rather than the config files
+#[derive(serde::Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct SmtpSettings {
+    pub host: String,
+    // Defaults to the submission port, 587, with STARTTLS
+    pub port: Option<u16>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+impl Default for EmailSettings {
+    fn default() -> EmailSettings {
+        EmailSettings {
+            backend: MailBackend::Off,
+            from: "Generate <noreply@generatenu.com>".into(),
+            url: "http://localhost:8080".into(),
+            templates: "templates/email".into(),
+            dir: "mail".into(),
+            smtp: SmtpSettings::default(),
+        }
+    }
+}
+
+// Settings get logged on startup, so keep the secrets out of Debug
+impl std::fmt::Debug for AdminSettings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AdminSettings")
+            .field("token", &self.token.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
+impl std::fmt::Debug for WebhookSettings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WebhookSettings")
+            .field("url", &self.url)
+            .field("secret", &"<redacted>")
+            .field("events", &self.events)
+            .finish()
+    }
+}
+
+impl std::fmt::Debug for SmtpSettings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SmtpSettings")
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("username", &self.username)
+            .field("password", &self.password.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
 }
 
 #[derive(serde::Deserialize, Clone, Debug)]
@@ -128,3 +360,36 @@ fn test_parse_int() {
         );
     })
 }
+
+#[test]
+fn test_proxies_default_to_untrusted() {
+    use config::{Config, File, FileFormat};
+
+    let config: ProxySettings = Config::builder()
+        .add_source(File::from_str("{}", FileFormat::Yaml))
+        .build()
+        .unwrap()
+        .try_deserialize()
+        .unwrap();
+
+    assert!(config.trusted.is_empty());
+}
+
+#[test]
+fn test_hint_penalty_is_out_of_100() {
+    use config::{Config, File, FileFormat};
+
+    let load = |yaml: &str| {
+        Config::builder()
+            .add_source(File::from_str(yaml, FileFormat::Yaml))
+            .build()
+            .unwrap()
+            .try_deserialize::<HintSettings>()
+    };
+
+    assert_eq!(load("{}").unwrap().penalty, 0);
+    assert_eq!(load("penalty: 10").unwrap().penalty, 10);
+    assert!(load("penalty: 101").is_err());
+    assert!(load("penalty: -1").is_err());
+    assert!(load("penalty: 4294967295").is_err());
+}
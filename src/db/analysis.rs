@@ -0,0 +1,124 @@
+use chrono::{DateTime, Utc};
+use sqlx::{query, PgPool};
+
+// Everything below takes an optional NUID and cycle to narrow it down to
+
+pub struct ReviewRecord {
+    pub nuid: String,
+    pub applicant_name: String,
+    pub email: Option<String>,
+    pub cycle: String,
+    pub status: String,
+    pub registration_time: DateTime<Utc>,
+    pub registration_ip: Option<String>,
+    pub registration_user_agent: Option<String>,
+    pub submissions: i64,
+    pub solved: bool,
+}
+
+pub async fn get_review_db(pool: &PgPool, nuid: &str) -> Result<Option<ReviewRecord>, sqlx::Error> {
+    sqlx::query_as!(
+        ReviewRecord,
+        r#"SELECT applicants.nuid, applicant_name, email, cycle, status, registration_time,
+        registration_ip, registration_user_agent,
+        count(submission_id) AS "submissions!", completed_at IS NOT NULL AS "solved!"
+        FROM applicants LEFT JOIN submissions ON submissions.nuid = applicants.nuid
+        WHERE applicants.nuid = $1 GROUP BY applicants.nuid;"#,
+        nuid
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+// Applicants who submitted exactly what someone with a different challenge
+// string for that stage did, and who that was
+pub async fn get_shared_answers_db(
+    pool: &PgPool,
+    nuid: Option<&str>,
+    cycle: Option<&str>,
+) -> Result<Vec<(String, Vec<String>)>, sqlx::Error> {
+    let records = query!(
+        r#"SELECT s.nuid, array_agg(DISTINCT o.nuid ORDER BY o.nuid) AS "shared_with!"
+        FROM submissions s JOIN applicants a ON a.nuid = s.nuid
+        JOIN applicant_stages sa ON sa.nuid = s.nuid AND sa.stage = s.stage
+        JOIN submissions o ON o.solution_hash = s.solution_hash AND o.nuid <> s.nuid
+        JOIN applicant_stages so ON so.nuid = o.nuid AND so.stage = o.stage
+        WHERE sa.challenge_string <> so.challenge_string
+        AND ($1::varchar IS NULL OR s.nuid = $1) AND ($2::varchar IS NULL OR a.cycle = $2)
+        GROUP BY s.nuid, s.solution_hash ORDER BY s.nuid;"#,
+        nuid,
+        cycle
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(records
+        .into_iter()
+        .map(|record| (record.nuid, record.shared_with))
+        .collect())
+}
+
+// Applicants who solved the last stage under `fastest` seconds after they
+// registered, and how long it actually took
+pub async fn get_fast_solves_db(
+    pool: &PgPool,
+    nuid: Option<&str>,
+    cycle: Option<&str>,
+    fastest: f64,
+) -> Result<Vec<(String, f64)>, sqlx::Error> {
+    let records = query!(
+        r#"SELECT nuid,
+        EXTRACT(EPOCH FROM completed_at - registration_time)::float8 AS "seconds!"
+        FROM applicants
+        WHERE ($1::varchar IS NULL OR nuid = $1) AND ($2::varchar IS NULL OR cycle = $2)
+        AND completed_at - registration_time < make_interval(secs => $3)
+        ORDER BY nuid;"#,
+        nuid,
+        cycle,
+        fastest
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(records
+        .into_iter()
+        .map(|record| (record.nuid, record.seconds))
+        .collect())
+}
+
+// Applicants who submitted from an IP that at least `applicants` different
+// applicants submitted from within `seconds` of them. Each applicant gets their
+// biggest burst per IP
+pub async fn get_ip_bursts_db(
+    pool: &PgPool,
+    nuid: Option<&str>,
+    cycle: Option<&str>,
+    seconds: f64,
+    applicants: i64,
+) -> Result<Vec<(String, String, Vec<String>)>, sqlx::Error> {
+    let records = query!(
+        r#"SELECT DISTINCT ON (nuid, ip) nuid AS "nuid!", ip AS "ip!", applicants AS "applicants!"
+        FROM (
+            SELECT s.nuid, s.ip, array_agg(DISTINCT o.nuid ORDER BY o.nuid) AS applicants
+            FROM submissions s JOIN applicants a ON a.nuid = s.nuid
+            JOIN submissions o ON o.ip = s.ip
+                AND o.submission_time BETWEEN s.submission_time - make_interval(secs => $3)
+                AND s.submission_time + make_interval(secs => $3)
+            WHERE ($1::varchar IS NULL OR s.nuid = $1) AND ($2::varchar IS NULL OR a.cycle = $2)
+            GROUP BY s.submission_id, s.nuid, s.ip
+            HAVING count(DISTINCT o.nuid) >= $4
+        ) bursts
+        ORDER BY nuid, ip, cardinality(applicants) DESC;"#,
+        nuid,
+        cycle,
+        seconds,
+        applicants
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(records
+        .into_iter()
+        .map(|record| (record.nuid, record.ip, record.applicants))
+        .collect())
+}
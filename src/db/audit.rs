@@ -0,0 +1,80 @@
+use chrono::{DateTime, Utc};
+use sqlx::{query, query_as, PgPool, Postgres, Transaction};
+
+use crate::model::audit::{AuditEvent, RequestContext};
+
+pub async fn record_event_db(
+    tx: &mut Transaction<'_, Postgres>,
+    ctx: &RequestContext,
+    action: &str,
+    actor: &str,
+    nuid: Option<&str>,
+    details: &serde_json::Value,
+) -> Result<(), sqlx::Error> {
+    query!(
+        r#"INSERT INTO audit_events
+        (occurred_at, action, actor, nuid, ip, user_agent, request_id, details)
+        VALUES (now(), $1, $2, $3, $4, $5, $6, $7);"#,
+        action,
+        actor,
+        nuid,
+        ctx.ip,
+        ctx.user_agent,
+        ctx.request_id,
+        details
+    )
+    .execute(tx)
+    .await?;
+
+    Ok(())
+}
+
+// Whether this happened to the applicant since `since`
+pub async fn has_event_since_db(
+    tx: &mut Transaction<'_, Postgres>,
+    nuid: &str,
+    action: &str,
+    since: DateTime<Utc>,
+) -> Result<bool, sqlx::Error> {
+    let record = query!(
+        r#"SELECT EXISTS (
+            SELECT 1 FROM audit_events WHERE nuid = $1 AND action = $2 AND occurred_at > $3
+        ) AS "found!""#,
+        nuid,
+        action,
+        since
+    )
+    .fetch_one(tx)
+    .await?;
+
+    Ok(record.found)
+}
+
+pub async fn get_events_db(
+    pool: &PgPool,
+    action: Option<String>,
+    actor: Option<String>,
+    nuid: Option<String>,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    limit: i64,
+) -> Result<Vec<AuditEvent>, sqlx::Error> {
+    query_as!(
+        AuditEvent,
+        r#"SELECT event_id, occurred_at, action, actor, nuid, ip, user_agent, request_id, details
+        FROM audit_events
+        WHERE ($1::varchar IS NULL OR action = $1) AND ($2::varchar IS NULL OR actor = $2)
+        AND ($3::varchar IS NULL OR nuid = $3)
+        AND ($4::timestamptz IS NULL OR occurred_at >= $4)
+        AND ($5::timestamptz IS NULL OR occurred_at < $5)
+        ORDER BY event_id DESC LIMIT $6;"#,
+        action,
+        actor,
+        nuid,
+        since,
+        until,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+}
@@ -0,0 +1,70 @@
+use chrono::{DateTime, Utc};
+use sqlx::{query, query_as, PgExecutor, Postgres, Transaction};
+use uuid::Uuid;
+
+pub struct NewBatch<'a> {
+    pub batch_id: Uuid,
+    pub nuid: &'a str,
+    pub stage: i32,
+    pub seeds: &'a [i64],
+    pub issued_at: DateTime<Utc>,
+    pub deadline: DateTime<Utc>,
+}
+
+pub struct BatchRecord {
+    pub stage: i32,
+    pub seeds: Vec<i64>,
+    pub deadline: DateTime<Utc>,
+    pub answered_at: Option<DateTime<Utc>>,
+}
+
+pub async fn write_batch_db(
+    executor: impl PgExecutor<'_>,
+    batch: &NewBatch<'_>,
+) -> Result<(), sqlx::Error> {
+    query!(
+        r#"INSERT INTO batches (batch_id, nuid, stage, seeds, issued_at, deadline)
+        VALUES ($1, $2, $3, $4, $5, $6);"#,
+        batch.batch_id,
+        batch.nuid,
+        batch.stage,
+        batch.seeds,
+        batch.issued_at,
+        batch.deadline
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+// Locked, so it can only be answered once
+pub async fn get_batch_db(
+    tx: &mut Transaction<'_, Postgres>,
+    batch_id: Uuid,
+    nuid: &str,
+) -> Result<Option<BatchRecord>, sqlx::Error> {
+    query_as!(
+        BatchRecord,
+        r#"SELECT stage, seeds, deadline, answered_at FROM batches
+        WHERE batch_id = $1 AND nuid = $2 FOR UPDATE;"#,
+        batch_id,
+        nuid
+    )
+    .fetch_optional(tx)
+    .await
+}
+
+pub async fn answer_batch_db(
+    tx: &mut Transaction<'_, Postgres>,
+    batch_id: Uuid,
+    answered_at: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    query!(
+        r#"UPDATE batches SET answered_at = $2 WHERE batch_id = $1;"#,
+        batch_id,
+        answered_at
+    )
+    .execute(tx)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,52 @@
+use chrono::{DateTime, Utc};
+use sqlx::{query, query_as, PgExecutor, PgPool, Postgres, Transaction};
+
+use crate::model::hints::HintUse;
+
+pub async fn count_hints_db(
+    executor: impl PgExecutor<'_>,
+    nuid: &str,
+    stage: i32,
+) -> Result<i64, sqlx::Error> {
+    let record = query!(
+        r#"SELECT count(*) AS "count!" FROM hints WHERE nuid = $1 AND stage = $2;"#,
+        nuid,
+        stage
+    )
+    .fetch_one(executor)
+    .await?;
+    Ok(record.count)
+}
+
+pub async fn unlock_hint_db(
+    tx: &mut Transaction<'_, Postgres>,
+    nuid: &str,
+    stage: i32,
+    hint: i32,
+    penalty: i32,
+    unlocked_at: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    query!(
+        r#"INSERT INTO hints (nuid, stage, hint, penalty, unlocked_at)
+        VALUES ($1, $2, $3, $4, $5);"#,
+        nuid,
+        stage,
+        hint,
+        penalty,
+        unlocked_at
+    )
+    .execute(tx)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_hint_uses_db(pool: &PgPool, nuid: &str) -> Result<Vec<HintUse>, sqlx::Error> {
+    query_as!(
+        HintUse,
+        r#"SELECT stage, hint, penalty, unlocked_at FROM hints
+        WHERE nuid = $1 ORDER BY stage, hint;"#,
+        nuid
+    )
+    .fetch_all(pool)
+    .await
+}
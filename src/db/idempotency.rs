@@ -0,0 +1,60 @@
+use sqlx::{query, Postgres, Transaction};
+
+// Claims a key for a submission. Returns false if it's already been used - a
+// concurrent request with the same key blocks here until the first one commits
+pub async fn claim_key_db(
+    tx: &mut Transaction<'_, Postgres>,
+    nuid: &str,
+    key: &str,
+    request_hash: &str,
+) -> Result<bool, sqlx::Error> {
+    let result = query!(
+        r#"INSERT INTO idempotency_keys (nuid, idempotency_key, request_hash, created_at)
+        VALUES ($1, $2, $3, now()) ON CONFLICT DO NOTHING;"#,
+        nuid,
+        key,
+        request_hash
+    )
+    .execute(tx)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+pub async fn record_key_db(
+    tx: &mut Transaction<'_, Postgres>,
+    nuid: &str,
+    key: &str,
+    submission_id: i32,
+) -> Result<(), sqlx::Error> {
+    query!(
+        r#"UPDATE idempotency_keys SET submission_id = $3
+        WHERE nuid = $1 AND idempotency_key = $2;"#,
+        nuid,
+        key,
+        submission_id
+    )
+    .execute(tx)
+    .await?;
+
+    Ok(())
+}
+
+// The hash of the request that first used this key, whether it was correct, and
+// the stage it was for
+pub async fn get_key_db(
+    tx: &mut Transaction<'_, Postgres>,
+    nuid: &str,
+    key: &str,
+) -> Result<(String, bool, i32), sqlx::Error> {
+    let record = query!(
+        r#"SELECT request_hash, ok, stage FROM idempotency_keys JOIN submissions USING (submission_id, nuid)
+        WHERE nuid = $1 AND idempotency_key = $2;"#,
+        nuid,
+        key
+    )
+    .fetch_one(tx)
+    .await?;
+
+    Ok((record.request_hash, record.ok, record.stage))
+}
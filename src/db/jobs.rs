@@ -0,0 +1,201 @@
+use chrono::{DateTime, Utc};
+use sqlx::{query, query_as, PgExecutor, PgPool, Postgres, Transaction};
+
+use crate::jobs::JobRecord;
+
+pub async fn enqueue_job_db(
+    tx: &mut Transaction<'_, Postgres>,
+    kind: &str,
+    payload: &serde_json::Value,
+    max_attempts: i32,
+) -> Result<i64, sqlx::Error> {
+    let now = Utc::now();
+
+    let record = query!(
+        r#"INSERT INTO jobs (kind, payload, max_attempts, run_at, created_at)
+        VALUES ($1, $2, $3, $4, $4) RETURNING job_id;"#,
+        kind,
+        payload,
+        max_attempts,
+        now,
+    )
+    .fetch_one(tx)
+    .await?;
+
+    Ok(record.job_id)
+}
+
+// Marks the next due job running until `lease_until`, when it's due again in
+// case whoever claimed it died. Every claim uses up an attempt, so a job that
+// keeps losing its runner still runs out of them: once it has none left it's
+// marked dead instead of being claimed again. SKIP LOCKED keeps two runners
+// from claiming the same job at once
+pub async fn claim_next_job_db(
+    executor: impl PgExecutor<'_>,
+    lease_until: DateTime<Utc>,
+) -> Result<Option<JobRecord>, sqlx::Error> {
+    query_as!(
+        JobRecord,
+        r#"WITH expired AS (
+            UPDATE jobs SET status = 'dead', finished_at = now(),
+            last_error = 'Ran past its lease on its last attempt'
+            WHERE status = 'running' AND run_at <= now() AND attempts >= max_attempts
+        )
+        UPDATE jobs SET status = 'running', attempts = attempts + 1, run_at = $1
+        WHERE job_id = (
+            SELECT job_id FROM jobs
+            WHERE (status = 'pending' OR (status = 'running' AND attempts < max_attempts))
+            AND run_at <= now()
+            ORDER BY run_at LIMIT 1 FOR UPDATE SKIP LOCKED
+        )
+        RETURNING job_id, kind, payload, status, attempts, max_attempts, run_at, created_at,
+        finished_at, last_error;"#,
+        lease_until
+    )
+    .fetch_optional(executor)
+    .await
+}
+
+// Only counts if nobody has claimed the job since `attempts` was handed out,
+// so a runner that overran its lease can't finish a job that's running again
+pub async fn finish_job_db(
+    executor: impl PgExecutor<'_>,
+    job_id: i64,
+    attempts: i32,
+) -> Result<(), sqlx::Error> {
+    query!(
+        r#"UPDATE jobs SET status = 'done', finished_at = now(), last_error = NULL
+        WHERE job_id = $1 AND status = 'running' AND attempts = $2;"#,
+        job_id,
+        attempts,
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn fail_job_db(
+    executor: impl PgExecutor<'_>,
+    job_id: i64,
+    attempts: i32,
+    dead: bool,
+    run_at: DateTime<Utc>,
+    error: &str,
+) -> Result<(), sqlx::Error> {
+    query!(
+        r#"UPDATE jobs SET last_error = $5, run_at = $4,
+        status = CASE WHEN $3 THEN 'dead' ELSE 'pending' END,
+        finished_at = CASE WHEN $3 THEN now() ELSE NULL END
+        WHERE job_id = $1 AND status = 'running' AND attempts = $2;"#,
+        job_id,
+        attempts,
+        dead,
+        run_at,
+        error,
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn get_jobs_db(
+    pool: &PgPool,
+    status: Option<String>,
+    kind: Option<String>,
+    limit: i64,
+) -> Result<Vec<JobRecord>, sqlx::Error> {
+    query_as!(
+        JobRecord,
+        r#"SELECT job_id, kind, payload, status, attempts, max_attempts, run_at, created_at,
+        finished_at, last_error FROM jobs
+        WHERE ($1::varchar IS NULL OR status = $1) AND ($2::varchar IS NULL OR kind = $2)
+        ORDER BY job_id DESC LIMIT $3;"#,
+        status,
+        kind,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// Gives a dead job a fresh set of attempts. Returns false if there's no dead
+// job with this id
+pub async fn retry_job_db(
+    tx: &mut Transaction<'_, Postgres>,
+    job_id: i64,
+) -> Result<bool, sqlx::Error> {
+    let result = query!(
+        r#"UPDATE jobs SET status = 'pending', attempts = 0, run_at = now(), finished_at = NULL
+        WHERE job_id = $1 AND status = 'dead';"#,
+        job_id
+    )
+    .execute(tx)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use sqlx::PgPool;
+
+    use super::{claim_next_job_db, finish_job_db};
+
+    // Needs a migrated database, so it only runs where DATABASE_URL points at
+    // one. Everything happens in a transaction that's rolled back at the end
+    #[tokio::test]
+    async fn test_reclaims_expired_jobs() {
+        let url = match std::env::var("DATABASE_URL") {
+            Ok(url) => url,
+            Err(_) => return,
+        };
+        let pool = PgPool::connect(&url).await.unwrap();
+        let mut tx = pool.begin().await.unwrap();
+
+        // Claimed once already, and its runner never came back. Due long
+        // before anything else in the table
+        let expired = Utc::now() - Duration::weeks(52 * 30);
+        let (job_id,): (i64,) = sqlx::query_as(
+            "INSERT INTO jobs (kind, payload, status, attempts, max_attempts, run_at, created_at)
+            VALUES ('test', '{}', 'running', 1, 2, $1, $1) RETURNING job_id",
+        )
+        .bind(expired)
+        .fetch_one(&mut tx)
+        .await
+        .unwrap();
+
+        let lease_until = Utc::now() + Duration::minutes(5);
+        let job = claim_next_job_db(&mut tx, lease_until)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(job.job_id, job_id);
+        assert_eq!(job.status, "running");
+        assert_eq!(job.attempts, 2);
+
+        // The first runner finishing late doesn't count for the second
+        finish_job_db(&mut tx, job_id, 1).await.unwrap();
+
+        // That was its last attempt, so running past the lease again kills it
+        sqlx::query("UPDATE jobs SET run_at = $2 WHERE job_id = $1")
+            .bind(job_id)
+            .bind(expired)
+            .execute(&mut tx)
+            .await
+            .unwrap();
+        let next = claim_next_job_db(&mut tx, lease_until).await.unwrap();
+        assert!(next.is_none_or(|next| next.job_id != job_id));
+
+        let (status, attempts): (String, i32) =
+            sqlx::query_as("SELECT status, attempts FROM jobs WHERE job_id = $1")
+                .bind(job_id)
+                .fetch_one(&mut tx)
+                .await
+                .unwrap();
+        assert_eq!(status, "dead");
+        assert_eq!(attempts, 2);
+    }
+}
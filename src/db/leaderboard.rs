@@ -0,0 +1,45 @@
+use chrono::{DateTime, Utc};
+use sqlx::{query, PgExecutor, PgPool};
+
+pub struct SolveRecord {
+    pub display_name: String,
+    pub registration_time: DateTime<Utc>,
+    pub solved_at: DateTime<Utc>,
+}
+
+// The cycle asked for if it exists, or the active one if none was
+pub async fn get_cycle_name_db(
+    executor: impl PgExecutor<'_>,
+    cycle: Option<&str>,
+) -> Result<Option<String>, sqlx::Error> {
+    let record = query!(
+        r#"SELECT name FROM cycles
+        WHERE ($1::varchar IS NULL AND active) OR name = $1;"#,
+        cycle
+    )
+    .fetch_optional(executor)
+    .await?;
+
+    Ok(record.map(|record| record.name))
+}
+
+// Everyone who opted in and has solved every stage, by how long it took them
+pub async fn get_solves_db(
+    pool: &PgPool,
+    cycle: &str,
+    limit: i64,
+) -> Result<Vec<SolveRecord>, sqlx::Error> {
+    sqlx::query_as!(
+        SolveRecord,
+        r#"SELECT display_name AS "display_name!", registration_time,
+        completed_at AS "solved_at!"
+        FROM applicants
+        WHERE cycle = $1 AND display_name IS NOT NULL AND completed_at IS NOT NULL
+        ORDER BY completed_at - registration_time, nuid
+        LIMIT $2;"#,
+        cycle,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+}
@@ -0,0 +1,14 @@
+use sqlx::{query, Postgres, Transaction};
+
+// Postgres holds on to this until the transaction commits, and drops it if it
+// rolls back
+pub async fn notify_db(
+    tx: &mut Transaction<'_, Postgres>,
+    channel: &str,
+    payload: &str,
+) -> Result<(), sqlx::Error> {
+    query!(r#"SELECT pg_notify($1, $2);"#, channel, payload)
+        .fetch_one(tx)
+        .await?;
+    Ok(())
+}
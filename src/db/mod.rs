@@ -1 +1,16 @@
+pub mod analysis;
+pub mod audit;
+pub mod batches;
+pub mod hints;
+pub mod idempotency;
+pub mod jobs;
+pub mod leaderboard;
+pub mod live;
+pub mod programs;
+pub mod retention;
+pub mod review;
+pub mod stages;
+pub mod stats;
+pub mod tokens;
 pub mod transactions;
+pub mod webhooks;
@@ -0,0 +1,41 @@
+use sqlx::{query, Postgres, Transaction};
+
+use crate::model::programs::TestRecord;
+
+pub async fn write_program_db(
+    tx: &mut Transaction<'_, Postgres>,
+    submission_id: i32,
+    language: &str,
+    source: &str,
+) -> Result<(), sqlx::Error> {
+    query!(
+        r#"INSERT INTO programs (submission_id, language, source) VALUES ($1, $2, $3);"#,
+        submission_id,
+        language,
+        source
+    )
+    .execute(tx)
+    .await?;
+    Ok(())
+}
+
+pub async fn write_tests_db(
+    tx: &mut Transaction<'_, Postgres>,
+    submission_id: i32,
+    tests: &[TestRecord],
+) -> Result<(), sqlx::Error> {
+    for test in tests {
+        query!(
+            r#"INSERT INTO program_tests (submission_id, test, seed, outcome, runtime_us)
+            VALUES ($1, $2, $3, $4, $5);"#,
+            submission_id,
+            test.run.test as i32,
+            test.seed,
+            test.run.outcome.as_str(),
+            test.run.runtime.as_micros() as i64
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+    Ok(())
+}
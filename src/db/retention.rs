@@ -0,0 +1,147 @@
+use sqlx::{query, query_as, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::model::cycles::Cycle;
+
+// Applicants whose cycle's retention period has run out, and what to do with each
+pub async fn get_expired_applicants_db(
+    pool: &PgPool,
+) -> Result<Vec<(String, String)>, sqlx::Error> {
+    let records = query!(
+        r#"SELECT nuid, retention_action FROM applicants JOIN cycles ON applicants.cycle = cycles.name
+        WHERE retention_days IS NOT NULL AND anonymized_at IS NULL
+        AND registration_time < now() - make_interval(days => retention_days)
+        ORDER BY registration_time;"#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(records
+        .into_iter()
+        .map(|record| (record.nuid, record.retention_action))
+        .collect())
+}
+
+// Swaps the NUID for a pseudonym everywhere it's been copied outside of the
+// applicant's own rows - the audit log, the webhook log and the job queue - and
+// drops the name and IP addresses from them
+pub async fn redact_applicant_db(
+    tx: &mut Transaction<'_, Postgres>,
+    nuid: &str,
+    pseudonym: &str,
+) -> Result<(), sqlx::Error> {
+    query!(r#"SELECT set_config('generate.redacting', 'on', true);"#)
+        .fetch_one(&mut *tx)
+        .await?;
+    query!(
+        r#"UPDATE audit_events SET nuid = $2, ip = NULL, user_agent = NULL WHERE nuid = $1;"#,
+        nuid,
+        pseudonym
+    )
+    .execute(&mut *tx)
+    .await?;
+    query!(r#"SELECT set_config('generate.redacting', 'off', true);"#)
+        .fetch_one(&mut *tx)
+        .await?;
+    query!(
+        r#"UPDATE submissions SET ip = NULL, user_agent = NULL WHERE nuid = $1;"#,
+        nuid
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    query!(
+        r#"UPDATE webhook_deliveries
+        SET payload = jsonb_set(payload #- '{data,name}', '{data,nuid}', to_jsonb($2::varchar))
+        WHERE payload->'data'->>'nuid' = $1;"#,
+        nuid,
+        pseudonym
+    )
+    .execute(&mut *tx)
+    .await?;
+    query!(
+        r#"UPDATE jobs
+        SET payload = jsonb_set(payload #- '{args,data,name}', '{args,data,nuid}', to_jsonb($2::varchar))
+        WHERE payload->'args'->'data'->>'nuid' = $1;"#,
+        nuid,
+        pseudonym
+    )
+    .execute(&mut *tx)
+    .await?;
+    query!(
+        r#"UPDATE jobs SET payload = jsonb_set(payload, '{args,nuid}', to_jsonb($2::varchar))
+        WHERE payload->'args'->>'nuid' = $1;"#,
+        nuid,
+        pseudonym
+    )
+    .execute(tx)
+    .await?;
+
+    Ok(())
+}
+
+// Keeps the row (and the submissions) for the stats, minus anything that says
+// who it was. The token is replaced so the old one stops working, and reviewer
+// notes go since there's no telling what's in them
+pub async fn anonymize_applicant_db(
+    tx: &mut Transaction<'_, Postgres>,
+    nuid: &str,
+    pseudonym: &str,
+) -> Result<bool, sqlx::Error> {
+    query!(r#"DELETE FROM applicant_notes WHERE nuid = $1;"#, nuid)
+        .execute(&mut *tx)
+        .await?;
+    let result = query!(
+        r#"UPDATE applicants SET nuid = $2, applicant_name = 'Anonymized', email = NULL,
+        display_name = NULL, token = $3, token_expires_at = NULL, registration_ip = NULL,
+        registration_user_agent = NULL, anonymized_at = now()
+        WHERE nuid = $1;"#,
+        nuid,
+        pseudonym,
+        Uuid::new_v4()
+    )
+    .execute(tx)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+// Submissions, idempotency keys and token rotations go with it
+pub async fn delete_applicant_db(
+    tx: &mut Transaction<'_, Postgres>,
+    nuid: &str,
+) -> Result<bool, sqlx::Error> {
+    let result = query!(r#"DELETE FROM applicants WHERE nuid = $1;"#, nuid)
+        .execute(tx)
+        .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+pub async fn get_cycles_db(pool: &PgPool) -> Result<Vec<Cycle>, sqlx::Error> {
+    query_as!(
+        Cycle,
+        r#"SELECT name, active, created_at, retention_days, retention_action FROM cycles
+        ORDER BY created_at DESC;"#
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn set_retention_db(
+    tx: &mut Transaction<'_, Postgres>,
+    cycle: &str,
+    days: Option<i32>,
+    action: &str,
+) -> Result<bool, sqlx::Error> {
+    let result = query!(
+        r#"UPDATE cycles SET retention_days = $2, retention_action = $3 WHERE name = $1;"#,
+        cycle,
+        days,
+        action
+    )
+    .execute(tx)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
@@ -0,0 +1,145 @@
+use chrono::{DateTime, Utc};
+use sqlx::{query, query_as, PgPool, Postgres, Transaction};
+
+use crate::model::applicants::{ApplicantRow, Note, SubmissionRecord};
+
+pub async fn list_applicants_db(
+    pool: &PgPool,
+    cycle: Option<&str>,
+    status: Option<&str>,
+    solved: Option<bool>,
+    search: Option<&str>,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<ApplicantRow>, sqlx::Error> {
+    query_as!(
+        ApplicantRow,
+        r#"SELECT a.nuid, a.applicant_name AS name, a.email, a.cycle, a.status,
+        a.registration_time AS registered_at,
+        count(s.submission_id) AS "submissions!", a.completed_at IS NOT NULL AS "solved!",
+        max(s.submission_time) AS last_submission
+        FROM applicants a LEFT JOIN submissions s ON s.nuid = a.nuid
+        WHERE ($1::varchar IS NULL OR a.cycle = $1) AND ($2::varchar IS NULL OR a.status = $2)
+        AND ($3::varchar IS NULL OR a.nuid ILIKE $3 OR a.applicant_name ILIKE $3)
+        GROUP BY a.nuid
+        HAVING ($4::bool IS NULL OR (a.completed_at IS NOT NULL) = $4)
+        ORDER BY a.registration_time DESC LIMIT $5 OFFSET $6;"#,
+        cycle,
+        status,
+        search,
+        solved,
+        limit,
+        offset
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn get_status_db(pool: &PgPool, nuid: &str) -> Result<Option<String>, sqlx::Error> {
+    let record = query!(r#"SELECT status FROM applicants WHERE nuid = $1;"#, nuid)
+        .fetch_optional(pool)
+        .await?;
+    Ok(record.map(|record| record.status))
+}
+
+// Returns the status it had before, if there's an applicant with this NUID
+pub async fn set_status_db(
+    tx: &mut Transaction<'_, Postgres>,
+    nuid: &str,
+    status: &str,
+) -> Result<Option<String>, sqlx::Error> {
+    let record = query!(
+        r#"UPDATE applicants SET status = $2 FROM (
+            SELECT nuid, status FROM applicants WHERE nuid = $1 FOR UPDATE
+        ) old WHERE applicants.nuid = old.nuid RETURNING old.status;"#,
+        nuid,
+        status
+    )
+    .fetch_optional(tx)
+    .await?;
+    Ok(record.map(|record| record.status))
+}
+
+pub async fn get_submissions_db(
+    pool: &PgPool,
+    nuid: &str,
+) -> Result<Vec<SubmissionRecord>, sqlx::Error> {
+    query_as!(
+        SubmissionRecord,
+        r#"SELECT submission_id, ok, submission_time AS submitted_at, ip, user_agent
+        FROM submissions WHERE nuid = $1 ORDER BY submission_time DESC;"#,
+        nuid
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn add_note_db(
+    tx: &mut Transaction<'_, Postgres>,
+    nuid: &str,
+    body: &str,
+) -> Result<i64, sqlx::Error> {
+    let record = query!(
+        r#"INSERT INTO applicant_notes (nuid, body) VALUES ($1, $2) RETURNING note_id;"#,
+        nuid,
+        body
+    )
+    .fetch_one(tx)
+    .await?;
+    Ok(record.note_id)
+}
+
+pub async fn get_notes_db(pool: &PgPool, nuid: &str) -> Result<Vec<Note>, sqlx::Error> {
+    query_as!(
+        Note,
+        r#"SELECT note_id, body, created_at FROM applicant_notes WHERE nuid = $1
+        ORDER BY created_at DESC;"#,
+        nuid
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn create_cycle_db(
+    tx: &mut Transaction<'_, Postgres>,
+    name: &str,
+    created_at: DateTime<Utc>,
+) -> Result<bool, sqlx::Error> {
+    let result = query!(
+        r#"INSERT INTO cycles (name, active, created_at) VALUES ($1, false, $2)
+        ON CONFLICT DO NOTHING;"#,
+        name,
+        created_at
+    )
+    .execute(tx)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+// New registrations go to this cycle from now on
+pub async fn activate_cycle_db(
+    tx: &mut Transaction<'_, Postgres>,
+    name: &str,
+) -> Result<bool, sqlx::Error> {
+    let exists = query!(
+        r#"SELECT name FROM cycles WHERE name = $1 FOR UPDATE;"#,
+        name
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    if exists.is_none() {
+        return Ok(false);
+    }
+
+    // Two statements, since there can't be two active cycles even for a moment
+    query!(
+        r#"UPDATE cycles SET active = false WHERE active AND name <> $1;"#,
+        name
+    )
+    .execute(&mut *tx)
+    .await?;
+    query!(r#"UPDATE cycles SET active = true WHERE name = $1;"#, name)
+        .execute(tx)
+        .await?;
+    Ok(true)
+}
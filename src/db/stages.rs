@@ -0,0 +1,201 @@
+use chrono::{DateTime, Utc};
+use sqlx::{query, query_as, PgExecutor, PgPool, Postgres, Transaction};
+
+// A model::stages::Challenge, as it was serialized at the time
+#[derive(Clone)]
+pub struct StoredChallenge {
+    pub version: i32,
+    pub challenge: serde_json::Value,
+}
+
+// One of a cycle's stages, with the hints written for it
+pub struct CycleStage {
+    pub challenge: StoredChallenge,
+    pub hints: Vec<String>,
+}
+
+// A stage an applicant has unlocked, and what it asks of them. Stages unlocked
+// before challenges were seeded don't have a seed
+#[derive(Clone)]
+pub struct CurrentStage {
+    pub stage: i32,
+    pub cycle: String,
+    pub challenge_string: String,
+    pub seed: Option<i64>,
+    pub challenge: StoredChallenge,
+    pub solved_at: Option<DateTime<Utc>>,
+}
+
+pub struct NewStage<'a> {
+    pub nuid: &'a str,
+    pub stage: i32,
+    pub challenge: &'a StoredChallenge,
+    pub seed: i64,
+    pub challenge_string: &'a str,
+    pub unlocked_at: DateTime<Utc>,
+}
+
+pub struct StageRecord {
+    pub nuid: String,
+    pub stage: i32,
+    pub registration_time: DateTime<Utc>,
+    pub unlocked_at: DateTime<Utc>,
+    pub solved_at: Option<DateTime<Utc>>,
+}
+
+// A cycle's stages in order
+pub async fn get_stages_db(
+    executor: impl PgExecutor<'_>,
+    cycle: &str,
+) -> Result<Vec<CycleStage>, sqlx::Error> {
+    let records = query!(
+        r#"SELECT challenge_version, challenge, hints FROM cycle_stages
+        WHERE cycle = $1 ORDER BY stage;"#,
+        cycle
+    )
+    .fetch_all(executor)
+    .await?;
+
+    Ok(records
+        .into_iter()
+        .map(|record| CycleStage {
+            challenge: StoredChallenge {
+                version: record.challenge_version,
+                challenge: record.challenge,
+            },
+            hints: record.hints,
+        })
+        .collect())
+}
+
+// Replaces every stage the cycle has
+pub async fn set_stages_db(
+    tx: &mut Transaction<'_, Postgres>,
+    cycle: &str,
+    stages: &[CycleStage],
+) -> Result<(), sqlx::Error> {
+    query!(r#"DELETE FROM cycle_stages WHERE cycle = $1;"#, cycle)
+        .execute(&mut *tx)
+        .await?;
+    for (i, stage) in stages.iter().enumerate() {
+        query!(
+            r#"INSERT INTO cycle_stages (cycle, stage, challenge_version, challenge, hints)
+            VALUES ($1, $2, $3, $4, $5);"#,
+            cycle,
+            i as i32 + 1,
+            stage.challenge.version,
+            stage.challenge.challenge,
+            &stage.hints
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+    Ok(())
+}
+
+// False if it was already unlocked
+pub async fn add_stage_db(
+    tx: &mut Transaction<'_, Postgres>,
+    stage: &NewStage<'_>,
+) -> Result<bool, sqlx::Error> {
+    let result = query!(
+        r#"INSERT INTO applicant_stages
+        (nuid, stage, challenge_version, challenge, seed, challenge_string, unlocked_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7) ON CONFLICT DO NOTHING;"#,
+        stage.nuid,
+        stage.stage,
+        stage.challenge.version,
+        stage.challenge.challenge,
+        stage.seed,
+        stage.challenge_string,
+        stage.unlocked_at
+    )
+    .execute(tx)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+// The first stage they haven't solved, or the last one if they've solved them
+// all. Locked until the transaction ends, so two submissions can't both unlock
+// the next stage
+pub async fn get_current_stage_db(
+    executor: impl PgExecutor<'_>,
+    nuid: &str,
+) -> Result<Option<CurrentStage>, sqlx::Error> {
+    let record = query!(
+        r#"SELECT st.stage, a.cycle, st.challenge_string, st.seed, st.challenge_version,
+        st.challenge, st.solved_at
+        FROM applicant_stages st JOIN applicants a ON a.nuid = st.nuid
+        WHERE st.nuid = $1
+        ORDER BY st.solved_at IS NOT NULL, st.stage DESC
+        LIMIT 1 FOR UPDATE OF st;"#,
+        nuid
+    )
+    .fetch_optional(executor)
+    .await?;
+
+    Ok(record.map(|record| CurrentStage {
+        stage: record.stage,
+        cycle: record.cycle,
+        challenge_string: record.challenge_string,
+        seed: record.seed,
+        challenge: StoredChallenge {
+            version: record.challenge_version,
+            challenge: record.challenge,
+        },
+        solved_at: record.solved_at,
+    }))
+}
+
+// False if it was already solved
+pub async fn solve_stage_db(
+    tx: &mut Transaction<'_, Postgres>,
+    nuid: &str,
+    stage: i32,
+    solved_at: DateTime<Utc>,
+) -> Result<bool, sqlx::Error> {
+    let result = query!(
+        r#"UPDATE applicant_stages SET solved_at = $3
+        WHERE nuid = $1 AND stage = $2 AND solved_at IS NULL;"#,
+        nuid,
+        stage,
+        solved_at
+    )
+    .execute(tx)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+// False if they'd already finished
+pub async fn complete_db(
+    tx: &mut Transaction<'_, Postgres>,
+    nuid: &str,
+    completed_at: DateTime<Utc>,
+) -> Result<bool, sqlx::Error> {
+    let result = query!(
+        r#"UPDATE applicants SET completed_at = $2 WHERE nuid = $1 AND completed_at IS NULL;"#,
+        nuid,
+        completed_at
+    )
+    .execute(tx)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn get_stage_records_db(
+    pool: &PgPool,
+    nuids: &[String],
+) -> Result<Vec<StageRecord>, sqlx::Error> {
+    query_as!(
+        StageRecord,
+        r#"SELECT st.nuid, st.stage, a.registration_time, st.unlocked_at, st.solved_at
+        FROM applicant_stages st JOIN applicants a ON a.nuid = st.nuid
+        WHERE st.nuid = ANY($1) ORDER BY st.nuid, st.stage;"#,
+        nuids
+    )
+    .fetch_all(pool)
+    .await
+}
@@ -0,0 +1,86 @@
+use chrono::NaiveDate;
+use sqlx::{query_as, PgPool};
+
+use crate::model::stats::{AttemptCount, DailyRegistrations, Funnel, Percentiles};
+
+// Days are UTC days
+pub async fn get_registrations_db(
+    pool: &PgPool,
+    cycle: &str,
+) -> Result<Vec<DailyRegistrations>, sqlx::Error> {
+    query_as!(
+        DailyRegistrations,
+        r#"SELECT (registration_time AT TIME ZONE 'UTC')::date AS "day!: NaiveDate",
+        count(*) AS "registrations!"
+        FROM applicants WHERE cycle = $1
+        GROUP BY 1 ORDER BY 1;"#,
+        cycle
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// Each step counts everyone who got at least that far. The challenge string
+// comes back at registration too, so submitting without fetching it counts
+pub async fn get_funnel_db(pool: &PgPool, cycle: &str) -> Result<Funnel, sqlx::Error> {
+    query_as!(
+        Funnel,
+        r#"WITH stages AS (
+            SELECT a.challenge_fetched_at IS NOT NULL AS fetched,
+            count(s.submission_id) > 0 AS submitted,
+            a.completed_at IS NOT NULL AS solved
+            FROM applicants a LEFT JOIN submissions s ON s.nuid = a.nuid
+            WHERE a.cycle = $1 GROUP BY a.nuid
+        )
+        SELECT count(*) AS "registered!",
+        count(*) FILTER (WHERE fetched OR submitted) AS "fetched_challenge!",
+        count(*) FILTER (WHERE submitted) AS "submitted!",
+        count(*) FILTER (WHERE solved) AS "solved!"
+        FROM stages;"#,
+        cycle
+    )
+    .fetch_one(pool)
+    .await
+}
+
+// Seconds from registering to solving the last stage, over everyone who has
+pub async fn get_completion_times_db(
+    pool: &PgPool,
+    cycle: &str,
+) -> Result<Percentiles, sqlx::Error> {
+    query_as!(
+        Percentiles,
+        r#"WITH solves AS (
+            SELECT extract(epoch FROM completed_at - registration_time)::float8 AS seconds
+            FROM applicants WHERE cycle = $1 AND completed_at IS NOT NULL
+        )
+        SELECT percentile_cont(0.5) WITHIN GROUP (ORDER BY seconds) AS median,
+        percentile_cont(0.75) WITHIN GROUP (ORDER BY seconds) AS p75,
+        percentile_cont(0.9) WITHIN GROUP (ORDER BY seconds) AS p90,
+        percentile_cont(0.95) WITHIN GROUP (ORDER BY seconds) AS p95
+        FROM solves;"#,
+        cycle
+    )
+    .fetch_one(pool)
+    .await
+}
+
+// How many submissions it took, over every stage - up to solving the last one
+// for those who have, and every one for those still trying
+pub async fn get_attempts_db(pool: &PgPool, cycle: &str) -> Result<Vec<AttemptCount>, sqlx::Error> {
+    query_as!(
+        AttemptCount,
+        r#"WITH attempts AS (
+            SELECT count(*) AS attempts, a.completed_at IS NOT NULL AS solved
+            FROM applicants a JOIN submissions s ON s.nuid = a.nuid
+            WHERE a.cycle = $1 AND (a.completed_at IS NULL OR s.submission_time <= a.completed_at)
+            GROUP BY a.nuid
+        )
+        SELECT attempts AS "attempts!", count(*) AS "applicants!",
+        count(*) FILTER (WHERE solved) AS "solved!"
+        FROM attempts GROUP BY attempts ORDER BY attempts;"#,
+        cycle
+    )
+    .fetch_all(pool)
+    .await
+}
@@ -0,0 +1,90 @@
+use chrono::{DateTime, Utc};
+use sqlx::{query, query_as, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::model::tokens::TokenRotation;
+
+// The applicant a token belongs to and when it expires, if it's live
+pub async fn get_token_db(
+    pool: &PgPool,
+    token: Uuid,
+) -> Result<Option<(String, Option<DateTime<Utc>>)>, sqlx::Error> {
+    let record = query!(
+        r#"SELECT nuid, token_expires_at FROM applicants WHERE token = $1"#,
+        token
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(record.map(|record| (record.nuid, record.token_expires_at)))
+}
+
+pub async fn is_revoked_db(pool: &PgPool, token: Uuid) -> Result<bool, sqlx::Error> {
+    let record = query!(
+        r#"SELECT EXISTS (SELECT 1 FROM token_rotations WHERE revoked_token = $1) AS "revoked!""#,
+        token
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(record.revoked)
+}
+
+// Swaps in a new token and revokes the old one. Only if `token` is still the
+// current one, when it's given - otherwise someone else rotated it first. None
+// if there's no such applicant, or their token has already changed
+pub async fn rotate_token_db(
+    tx: &mut Transaction<'_, Postgres>,
+    nuid: &str,
+    token: Option<Uuid>,
+    new_token: Uuid,
+    expires_at: Option<DateTime<Utc>>,
+    rotated_by: &str,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    let record = query!(
+        r#"WITH old AS (
+            SELECT nuid, token FROM applicants
+            WHERE nuid = $1 AND ($2::uuid IS NULL OR token = $2) FOR UPDATE
+        )
+        UPDATE applicants SET token = $3, token_expires_at = $4
+        FROM old WHERE applicants.nuid = old.nuid
+        RETURNING old.token;"#,
+        nuid,
+        token,
+        new_token,
+        expires_at
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let old_token = match record {
+        Some(record) => record.token,
+        None => return Ok(None),
+    };
+
+    query!(
+        r#"INSERT INTO token_rotations (revoked_token, nuid, rotated_by, rotated_at)
+        VALUES ($1, $2, $3, now());"#,
+        old_token,
+        nuid,
+        rotated_by
+    )
+    .execute(tx)
+    .await?;
+
+    Ok(Some(old_token))
+}
+
+pub async fn get_rotations_db(
+    pool: &PgPool,
+    nuid: &str,
+) -> Result<Vec<TokenRotation>, sqlx::Error> {
+    query_as!(
+        TokenRotation,
+        r#"SELECT revoked_token, rotated_by, rotated_at FROM token_rotations
+        WHERE nuid = $1 ORDER BY rotated_at DESC;"#,
+        nuid
+    )
+    .fetch_all(pool)
+    .await
+}
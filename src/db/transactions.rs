@@ -1,41 +1,59 @@
 use chrono::{DateTime, Utc};
-use core::panic;
-use serde_json;
-use std::collections::HashMap;
 use std::time::SystemTime;
 use uuid::Uuid;
 
-use sqlx::{query, PgPool};
+use sqlx::{query, query_as, PgExecutor, PgPool, Postgres, Transaction};
 
+use crate::email::Contact;
+
+// Everything that goes into a new row of the applicants table. Their challenge
+// goes in applicant_stages
+pub struct NewApplicant {
+    pub name: String,
+    pub nuid: String,
+    pub email: Option<String>,
+    pub display_name: Option<String>,
+    pub token: Uuid,
+    pub token_expires_at: Option<DateTime<Utc>>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+pub struct NewSubmission<'a> {
+    pub nuid: &'a str,
+    pub stage: i32,
+    pub ok: bool,
+    pub solution_hash: &'a str,
+    pub ip: Option<&'a str>,
+    pub user_agent: Option<&'a str>,
+}
+
+// Returns the cycle they joined, and when
 pub async fn register_user_db(
-    pool: &PgPool,
-    token: Uuid,
-    name: String,
-    nuid: String,
-    challenge_string: &String,
-    solution: HashMap<String, u64>,
-) -> Result<(), sqlx::Error> {
+    tx: &mut Transaction<'_, Postgres>,
+    applicant: &NewApplicant,
+) -> Result<(String, DateTime<Utc>), sqlx::Error> {
     // Insert the applicant
     let registration_time: DateTime<Utc> = SystemTime::now().into();
-    let ser_solution = match serde_json::to_value(&solution) {
-        Ok(val) => val,
-        Err(_) => todo!("Figure out how to handle the serde error properly"),
-    };
-
-    query!(
-        r#"INSERT INTO applicants (nuid, applicant_name, registration_time, token, challenge_string, solution)
-         VALUES ($1, $2, $3, $4, $5, $6);"#,
-        nuid,
-        name,
+
+    let record = query!(
+        r#"INSERT INTO applicants (nuid, applicant_name, email, display_name, registration_time, token, token_expires_at, registration_ip, registration_user_agent, cycle)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, (SELECT name FROM cycles WHERE active))
+         RETURNING cycle;"#,
+        applicant.nuid,
+        applicant.name,
+        applicant.email,
+        applicant.display_name,
         registration_time,
-        token,
-        challenge_string,
-        ser_solution
+        applicant.token,
+        applicant.token_expires_at,
+        applicant.ip,
+        applicant.user_agent
     )
-    .execute(pool)
+    .fetch_one(tx)
     .await?;
 
-    Ok(())
+    Ok((record.cycle, registration_time))
 }
 
 pub async fn get_applicants_db(
@@ -66,53 +84,61 @@ pub async fn get_applicants_db(
         .collect())
 }
 
-pub async fn retreive_token_db(pool: &PgPool, nuid: &String) -> Result<Uuid, sqlx::Error> {
-    let record = query!(r#"SELECT token FROM applicants WHERE nuid=$1"#, nuid)
-        .fetch_one(pool)
-        .await?;
-
-    Ok(record.token)
+pub async fn get_contact_db(
+    executor: impl PgExecutor<'_>,
+    nuid: &str,
+) -> Result<Option<Contact>, sqlx::Error> {
+    query_as!(
+        Contact,
+        r#"SELECT nuid, applicant_name, email, token, cycle, status FROM applicants
+        WHERE nuid=$1"#,
+        nuid
+    )
+    .fetch_optional(executor)
+    .await
 }
 
+// Notes the first time they came back for their challenge. Returns their NUID.
+// Only the first fetch writes anything - after that it's just a read
 pub async fn retreive_challenge_db(pool: &PgPool, token: Uuid) -> Result<String, sqlx::Error> {
-    let record = query!(
-        r#"select challenge_string from applicants where token=$1"#,
+    let first = query!(
+        r#"UPDATE applicants SET challenge_fetched_at = now()
+        WHERE token = $1 AND challenge_fetched_at IS NULL RETURNING nuid;"#,
         token
     )
-    .fetch_one(pool)
+    .fetch_optional(pool)
     .await?;
+    if let Some(record) = first {
+        return Ok(record.nuid);
+    }
 
-    Ok(record.challenge_string)
-}
-
-pub async fn retreive_soln(
-    pool: &PgPool,
-    token: Uuid,
-) -> Result<(HashMap<String, u64>, String), sqlx::Error> {
-    let record = query!(
-        r#"SELECT nuid, solution FROM applicants WHERE token=$1"#,
-        token
-    )
-    .fetch_one(pool)
-    .await?;
+    let record = query!(r#"SELECT nuid FROM applicants WHERE token = $1;"#, token)
+        .fetch_one(pool)
+        .await?;
 
-    match serde_json::from_value(record.solution) {
-        Ok(soln) => Ok((soln, record.nuid)),
-        Err(_e) => panic!("solution didn't deserialize properly - this should never happen"),
-    }
+    Ok(record.nuid)
 }
 
-pub async fn write_submission(pool: PgPool, nuid: String, ok: bool) -> Result<(), sqlx::Error> {
+// Returns its id, and when it was made
+pub async fn write_submission(
+    tx: &mut Transaction<'_, Postgres>,
+    submission: &NewSubmission<'_>,
+) -> Result<(i32, DateTime<Utc>), sqlx::Error> {
     let submission_time: DateTime<Utc> = SystemTime::now().into();
 
-    query!(
-        r#"INSERT INTO submissions (nuid, ok, submission_time) VALUES ($1, $2, $3);"#,
-        nuid,
-        ok,
+    let record = query!(
+        r#"INSERT INTO submissions (nuid, stage, ok, submission_time, solution_hash, ip, user_agent)
+        VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING submission_id;"#,
+        submission.nuid,
+        submission.stage,
+        submission.ok,
         submission_time,
+        submission.solution_hash,
+        submission.ip,
+        submission.user_agent,
     )
-    .execute(&pool)
+    .fetch_one(tx)
     .await?;
 
-    Ok(())
+    Ok((record.submission_id, submission_time))
 }
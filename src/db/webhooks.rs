@@ -0,0 +1,81 @@
+use chrono::Utc;
+use sqlx::{query, query_as, PgExecutor, PgPool, Postgres, Transaction};
+
+use crate::webhooks::Delivery;
+
+pub async fn create_delivery_db(
+    tx: &mut Transaction<'_, Postgres>,
+    url: &str,
+    event: &str,
+    payload: &serde_json::Value,
+) -> Result<i64, sqlx::Error> {
+    let now = Utc::now();
+
+    let record = query!(
+        r#"INSERT INTO webhook_deliveries (url, event, payload, created_at)
+        VALUES ($1, $2, $3, $4) RETURNING delivery_id;"#,
+        url,
+        event,
+        payload,
+        now,
+    )
+    .fetch_one(tx)
+    .await?;
+
+    Ok(record.delivery_id)
+}
+
+pub async fn get_delivery_db(
+    executor: impl PgExecutor<'_>,
+    delivery_id: i64,
+) -> Result<Delivery, sqlx::Error> {
+    query_as!(
+        Delivery,
+        r#"SELECT delivery_id, url, event, payload, status, attempts, created_at,
+        last_attempt_at, last_response_status, last_error
+        FROM webhook_deliveries WHERE delivery_id = $1;"#,
+        delivery_id
+    )
+    .fetch_one(executor)
+    .await
+}
+
+pub async fn record_attempt_db(
+    executor: impl PgExecutor<'_>,
+    delivery_id: i64,
+    status: &str,
+    response_status: Option<i32>,
+    error: Option<String>,
+) -> Result<(), sqlx::Error> {
+    query!(
+        r#"UPDATE webhook_deliveries SET status = $2, attempts = attempts + 1,
+        last_attempt_at = now(), last_response_status = $3, last_error = $4
+        WHERE delivery_id = $1;"#,
+        delivery_id,
+        status,
+        response_status,
+        error,
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn get_deliveries_db(
+    pool: &PgPool,
+    status: Option<String>,
+    limit: i64,
+) -> Result<Vec<Delivery>, sqlx::Error> {
+    query_as!(
+        Delivery,
+        r#"SELECT delivery_id, url, event, payload, status, attempts, created_at,
+        last_attempt_at, last_response_status, last_error
+        FROM webhook_deliveries WHERE ($1::varchar IS NULL OR status = $1)
+        ORDER BY delivery_id DESC LIMIT $2;"#,
+        status,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+}
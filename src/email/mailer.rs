@@ -0,0 +1,100 @@
+use async_trait::async_trait;
+use lettre::transport::{file, smtp};
+use lettre::{AsyncFileTransport, AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+use crate::config::SmtpSettings;
+
+// Something that can get a message to an inbox. The job runner only ever sees
+// this, so the backend is down to the config
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, message: Message) -> Result<(), MailError>;
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum MailError {
+    #[error("SMTP error: {0}")]
+    Smtp(#[from] smtp::Error),
+    #[error("Couldn't write the message: {0}")]
+    File(#[from] file::Error),
+}
+
+impl MailError {
+    // The server turned the message down for good (bad mailbox and the like)
+    pub fn is_permanent(&self) -> bool {
+        match self {
+            MailError::Smtp(e) => e.is_permanent(),
+            MailError::File(_) => false,
+        }
+    }
+}
+
+#[async_trait]
+impl Mailer for AsyncSmtpTransport<Tokio1Executor> {
+    async fn send(&self, message: Message) -> Result<(), MailError> {
+        AsyncTransport::send(self, message).await?;
+        Ok(())
+    }
+}
+
+// Drops every message into a directory as a .eml file, so you can see what
+// would have gone out without a mail server
+#[async_trait]
+impl Mailer for AsyncFileTransport<Tokio1Executor> {
+    async fn send(&self, message: Message) -> Result<(), MailError> {
+        let id = AsyncTransport::send(self, message).await?;
+        info!("Wrote email {}.eml", id);
+        Ok(())
+    }
+}
+
+pub fn smtp(settings: &SmtpSettings) -> Result<AsyncSmtpTransport<Tokio1Executor>, smtp::Error> {
+    let mut builder = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&settings.host)?;
+    if let Some(port) = settings.port {
+        builder = builder.port(port);
+    }
+    if let (Some(username), Some(password)) = (&settings.username, &settings.password) {
+        builder = builder.credentials(smtp::authentication::Credentials::new(
+            username.clone(),
+            password.clone(),
+        ));
+    }
+    Ok(builder.build())
+}
+
+pub fn file(dir: &str) -> std::io::Result<AsyncFileTransport<Tokio1Executor>> {
+    std::fs::create_dir_all(dir)?;
+    Ok(AsyncFileTransport::new(dir))
+}
+
+#[cfg(test)]
+mod tests {
+    use lettre::message::header::ContentType;
+    use lettre::Message;
+
+    use super::{file, Mailer};
+
+    #[tokio::test]
+    async fn test_file_mailer() {
+        let dir = std::env::temp_dir().join(format!("mail-{}", uuid::Uuid::new_v4()));
+        let mailer = file(dir.to_str().unwrap()).unwrap();
+
+        let message = Message::builder()
+            .from("Generate <noreply@generatenu.com>".parse().unwrap())
+            .to("ada@example.com".parse().unwrap())
+            .subject("Hello")
+            .header(ContentType::TEXT_PLAIN)
+            .body(String::from("Your token is in here"))
+            .unwrap();
+        mailer.send(message).await.unwrap();
+
+        let dropped: Vec<_> = std::fs::read_dir(&dir).unwrap().collect();
+        assert_eq!(dropped.len(), 1);
+        let eml = std::fs::read_to_string(dropped[0].as_ref().unwrap().path()).unwrap();
+        assert!(eml.contains("To: ada@example.com"));
+        assert!(eml.contains("Subject: Hello"));
+        assert!(eml.contains("Your token is in here"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
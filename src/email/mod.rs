@@ -0,0 +1,147 @@
+use std::sync::Arc;
+
+use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
+use lettre::Message;
+use serde_json::json;
+use sqlx::{PgPool, Postgres, Transaction};
+
+use crate::config::{EmailSettings, MailBackend};
+use crate::db;
+use crate::jobs::{self, Job, JobError};
+use crate::model::applicants::Status;
+use crate::model::events::Event;
+
+pub mod mailer;
+pub mod templates;
+
+pub use mailer::Mailer;
+pub use templates::Templates;
+
+// Who an email is going to
+pub struct Contact {
+    pub nuid: String,
+    pub applicant_name: String,
+    pub email: Option<String>,
+    pub token: uuid::Uuid,
+    pub cycle: String,
+    // Where they are in review
+    pub status: String,
+}
+
+// Everything the job runner needs to send applicants email - cheap to clone
+#[derive(Clone)]
+pub struct Mail {
+    mailer: Arc<dyn Mailer>,
+    templates: Templates,
+    from: Mailbox,
+    url: String,
+}
+
+impl Mail {
+    // None when email is switched off
+    pub fn from_settings(settings: &EmailSettings) -> Result<Option<Mail>, String> {
+        let mailer: Arc<dyn Mailer> = match settings.backend {
+            MailBackend::Off => return Ok(None),
+            MailBackend::Smtp => Arc::new(mailer::smtp(&settings.smtp).map_err(|e| e.to_string())?),
+            MailBackend::File => Arc::new(mailer::file(&settings.dir).map_err(|e| e.to_string())?),
+        };
+
+        Ok(Some(Mail {
+            mailer,
+            templates: Templates::new(&settings.templates),
+            from: settings
+                .from
+                .parse()
+                .map_err(|e| format!("Bad from address: {}", e))?,
+            url: settings.url.trim_end_matches('/').into(),
+        }))
+    }
+
+    // Queues an email for the applicant the event is about, if there's a
+    // template for it
+    pub async fn fan_out(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        event: Event,
+        data: &serde_json::Value,
+    ) -> Result<(), JobError> {
+        if template_for(event).is_none() {
+            return Ok(());
+        }
+        if let Some(nuid) = data["nuid"].as_str() {
+            let job = Job::SendEmail {
+                nuid: nuid.into(),
+                event,
+            };
+            jobs::enqueue(tx, &job).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn send(&self, pool: &PgPool, nuid: &str, event: Event) -> Result<(), JobError> {
+        let template = match template_for(event) {
+            Some(template) => template,
+            None => return Ok(()),
+        };
+        let applicant = match db::transactions::get_contact_db(pool, nuid).await? {
+            Some(applicant) => applicant,
+            // They've been deleted since this was queued
+            None => return Ok(()),
+        };
+        let to = match &applicant.email {
+            Some(email) => email
+                .parse::<Mailbox>()
+                .map_err(|e| JobError::permanent(format!("Bad address {}: {}", email, e)))?,
+            None => return Ok(()),
+        };
+        // The decision was taken back before this went out
+        let decided = Status::ALL
+            .iter()
+            .any(|status| status.is_decision() && status.as_str() == applicant.status);
+        if event == Event::Decided && !decided {
+            return Ok(());
+        }
+
+        let ctx = json!({
+            "name": applicant.applicant_name,
+            "nuid": applicant.nuid,
+            "token": applicant.token.to_string(),
+            "cycle": applicant.cycle,
+            "status": applicant.status,
+            "url": self.url,
+        });
+        let rendered = self
+            .templates
+            .render(template, &applicant.cycle, ctx)
+            .await
+            .map_err(|e| JobError::permanent(e.to_string()))?;
+
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(rendered.subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(rendered.body)
+            .map_err(|e| JobError::permanent(e.to_string()))?;
+
+        match self.mailer.send(message).await {
+            Ok(()) => {
+                info!("Sent the {} email to {}", template, nuid);
+                Ok(())
+            }
+            Err(e) if e.is_permanent() => Err(JobError::permanent(e.to_string())),
+            Err(e) => Err(JobError::retry(e.to_string())),
+        }
+    }
+}
+
+// Which template goes out for an event, if any
+fn template_for(event: Event) -> Option<&'static str> {
+    match event {
+        Event::Registered => Some("registered"),
+        Event::Solved => Some("solved"),
+        Event::Decided => Some("decided"),
+        Event::TokenRequested => Some("forgot_token"),
+    }
+}
@@ -0,0 +1,181 @@
+use std::path::{Path, PathBuf};
+
+use minijinja::{Environment, UndefinedBehavior};
+use serde::Serialize;
+
+// The email templates on disk. They're read on every send, so they can be
+// edited without a restart
+#[derive(Clone, Debug)]
+pub struct Templates {
+    dir: PathBuf,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Rendered {
+    pub subject: String,
+    pub body: String,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum TemplateError {
+    #[error("Couldn't read template {0}: {1}")]
+    Missing(String, std::io::Error),
+    #[error("Couldn't render template {0}: {1}")]
+    Render(String, minijinja::Error),
+    #[error("Template {0} has to start with a Subject: line and a blank line")]
+    NoSubject(String),
+}
+
+impl Templates {
+    pub fn new(dir: impl AsRef<Path>) -> Templates {
+        Templates {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+
+    // Renders <name>.txt, preferring the cycle's own copy if it has one. A
+    // template is a Subject: line, a blank line, then the body
+    pub async fn render<S: Serialize>(
+        &self,
+        name: &str,
+        cycle: &str,
+        ctx: S,
+    ) -> Result<Rendered, TemplateError> {
+        let path = self.path_for(name, cycle).await;
+        let source = tokio::fs::read_to_string(&path)
+            .await
+            .map_err(|e| TemplateError::Missing(path.display().to_string(), e))?;
+
+        let mut env = Environment::new();
+        // A typo'd variable should fail loudly, not email someone a blank
+        env.set_undefined_behavior(UndefinedBehavior::Strict);
+        let rendered = env
+            .render_named_str(name, &source, ctx)
+            .map_err(|e| TemplateError::Render(name.into(), e))?;
+
+        let (subject, body) = rendered
+            .split_once("\n\n")
+            .and_then(|(header, body)| Some((header.strip_prefix("Subject:")?, body)))
+            .ok_or_else(|| TemplateError::NoSubject(name.into()))?;
+
+        Ok(Rendered {
+            subject: subject.trim().into(),
+            body: body.into(),
+        })
+    }
+
+    async fn path_for(&self, name: &str, cycle: &str) -> PathBuf {
+        let file = format!("{}.txt", name);
+        // Cycle names come out of the database, but they're still going into a path
+        let safe = !cycle.is_empty() && cycle.chars().all(|c| c.is_alphanumeric() || c == '-');
+        if safe {
+            let path = self.dir.join(cycle).join(&file);
+            if tokio::fs::metadata(&path).await.is_ok() {
+                return path;
+            }
+        }
+        self.dir.join(file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{Rendered, TemplateError, Templates};
+
+    fn scratch_dir() -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("templates-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("fall-2026")).unwrap();
+        std::fs::write(
+            dir.join("registered.txt"),
+            "Subject: Welcome {{ name }}\n\nYour token is {{ token }}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("fall-2026/registered.txt"),
+            "Subject: Welcome to fall, {{ name }}\n\nToken: {{ token }}\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("broken.txt"), "No subject here {{ name }}").unwrap();
+        std::fs::write(dir.join("typo.txt"), "Subject: Hi\n\n{{ nmae }}").unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn test_render() {
+        let dir = scratch_dir();
+        let templates = Templates::new(&dir);
+        let ctx = json!({"name": "Ada", "token": "abc"});
+
+        assert_eq!(
+            templates
+                .render("registered", "default", &ctx)
+                .await
+                .unwrap(),
+            Rendered {
+                subject: "Welcome Ada".into(),
+                body: "Your token is abc".into(),
+            }
+        );
+        assert_eq!(
+            templates
+                .render("registered", "fall-2026", &ctx)
+                .await
+                .unwrap()
+                .subject,
+            "Welcome to fall, Ada"
+        );
+        // Cycles can't walk out of the templates directory
+        assert_eq!(
+            templates
+                .render("registered", "../fall-2026", &ctx)
+                .await
+                .unwrap()
+                .subject,
+            "Welcome Ada"
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_render_errors() {
+        let dir = scratch_dir();
+        let templates = Templates::new(&dir);
+        let ctx = json!({"name": "Ada"});
+
+        assert!(matches!(
+            templates.render("nope", "default", &ctx).await,
+            Err(TemplateError::Missing(..))
+        ));
+        assert!(matches!(
+            templates.render("broken", "default", &ctx).await,
+            Err(TemplateError::NoSubject(..))
+        ));
+        assert!(matches!(
+            templates.render("typo", "default", &ctx).await,
+            Err(TemplateError::Render(..))
+        ));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_shipped_templates() {
+        let templates = Templates::new("templates/email");
+        let ctx = json!({
+            "name": "Ada",
+            "nuid": "001453760",
+            "token": "00000000-0000-0000-0000-000000000000",
+            "cycle": "default",
+            "status": "accepted",
+            "url": "http://localhost:8080",
+        });
+
+        for name in ["registered", "solved", "decided", "forgot_token"] {
+            let rendered = templates.render(name, "default", &ctx).await.unwrap();
+            assert!(!rendered.subject.is_empty());
+        }
+    }
+}
@@ -1,30 +1,15 @@
-use std::collections::HashMap;
-
 use serde::{Deserialize, Serialize};
 use warp::reject;
 
-use crate::model::types::Applicant;
+pub use api_types::errors::{codes, ApiError, FieldError};
 
-#[derive(Debug, Serialize, Deserialize)]
-pub enum ApiError {
-    DuplicateUser,
-    IncorrectSolution {
-        given_solution: HashMap<String, u64>,
-    },
-    DeserializeError,
-    ApplicantsNotFound {
-        applicants_found: Vec<Applicant>,
-        applicants_not_found: Vec<String>,
-    },
-    NoUserFound,
-}
+use super::messages::Answer;
+use crate::model::types::Applicant;
 
 #[derive(thiserror::Error, Debug, Serialize, Deserialize)]
 pub enum ModelError {
     #[error("Incorrect solution")]
-    IncorrectSolution {
-        given_solution: HashMap<String, u64>,
-    },
+    IncorrectSolution { given_solution: Answer },
     #[error("A registration with this NUID exists")]
     DuplicateUser,
     #[error("One or more of the applicants requested not found")]
@@ -36,6 +21,82 @@ pub enum ModelError {
     SqlError,
     #[error("No user with this token exists")]
     NoUserFound,
+    #[error("The request failed validation")]
+    ValidationFailed { errors: Vec<FieldError> },
+    #[error("Missing or incorrect admin token")]
+    Unauthorized,
+    #[error("No dead job with this id exists")]
+    JobNotFound,
+    #[error("This Idempotency-Key was already used for a different request")]
+    IdempotencyKeyReused,
+    #[error("This token has been replaced by a newer one")]
+    TokenRevoked,
+    #[error("This token has expired")]
+    TokenExpired,
+    #[error("Email isn't set up, so tokens can't be sent out")]
+    EmailDisabled,
+    #[error("No cycle with this name exists")]
+    CycleNotFound,
+    #[error("A cycle with this name already exists")]
+    DuplicateCycle,
+    #[error("This stage takes a program, not an answer")]
+    ProgramRequired,
+    #[error("This stage takes an answer, not a program")]
+    AnswerRequired,
+    #[error("Couldn't run the program")]
+    SandboxFailed,
+    #[error("Programs can't be run on this server")]
+    SandboxUnavailable,
+    #[error("Another program of theirs is still running")]
+    RunInProgress,
+    #[error("Incorrect batch")]
+    IncorrectBatch { correct: u32, total: u32 },
+    #[error("This stage is answered in batches")]
+    BatchRequired,
+    #[error("No batch with this id exists for the stage they're on")]
+    BatchNotFound,
+    #[error("The batch's deadline has passed")]
+    BatchExpired,
+    #[error("This batch was already answered")]
+    BatchAnswered,
+    #[error("Answers can't be sent as {content_type}")]
+    UnsupportedMediaType { content_type: String },
+    #[error("Couldn't read the answer: {reason}")]
+    MalformedAnswer { reason: String },
 }
 
 impl reject::Reject for ModelError {}
+
+impl ModelError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ModelError::IncorrectSolution { .. } => codes::INCORRECT_SOLUTION,
+            ModelError::DuplicateUser => codes::DUPLICATE_USER,
+            ModelError::ApplicantsNotFound { .. } => codes::APPLICANTS_NOT_FOUND,
+            ModelError::SqlError => codes::INTERNAL_ERROR,
+            ModelError::NoUserFound => codes::NO_USER_FOUND,
+            ModelError::ValidationFailed { .. } => codes::VALIDATION_FAILED,
+            ModelError::Unauthorized => codes::UNAUTHORIZED,
+            ModelError::JobNotFound => codes::JOB_NOT_FOUND,
+            ModelError::IdempotencyKeyReused => codes::IDEMPOTENCY_KEY_REUSED,
+            ModelError::TokenRevoked => codes::TOKEN_REVOKED,
+            ModelError::TokenExpired => codes::TOKEN_EXPIRED,
+            ModelError::EmailDisabled => codes::EMAIL_DISABLED,
+            ModelError::CycleNotFound => codes::CYCLE_NOT_FOUND,
+            ModelError::DuplicateCycle => codes::DUPLICATE_CYCLE,
+            ModelError::ProgramRequired => codes::PROGRAM_REQUIRED,
+            ModelError::AnswerRequired => codes::ANSWER_REQUIRED,
+            ModelError::SandboxFailed => codes::INTERNAL_ERROR,
+            ModelError::SandboxUnavailable => codes::SANDBOX_UNAVAILABLE,
+            ModelError::RunInProgress => codes::RUN_IN_PROGRESS,
+            ModelError::IncorrectBatch { .. } => codes::INCORRECT_SOLUTION,
+            ModelError::BatchRequired => codes::BATCH_REQUIRED,
+            ModelError::BatchNotFound => codes::BATCH_NOT_FOUND,
+            ModelError::BatchExpired => codes::BATCH_EXPIRED,
+            ModelError::BatchAnswered => codes::BATCH_ANSWERED,
+            ModelError::UnsupportedMediaType { .. } => codes::UNSUPPORTED_MEDIA_TYPE,
+            // Same as any other body that doesn't parse
+            ModelError::MalformedAnswer { .. } => codes::BAD_REQUEST,
+        }
+    }
+}
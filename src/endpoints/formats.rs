@@ -0,0 +1,307 @@
+use std::collections::HashMap;
+
+use serde_json::Value;
+use warp::hyper::body::Bytes;
+use warp::{reject, Filter, Rejection};
+
+use super::errors::ModelError;
+use super::messages::Answer;
+
+pub const JSON: &str = "application/json";
+pub const TSV: &str = "text/tab-separated-values";
+pub const MSGPACK: &str = "application/msgpack";
+// What msgpack went by before it was registered
+const MSGPACK_ALIASES: [&str; 2] = ["application/x-msgpack", "application/vnd.msgpack"];
+
+// An answer in whichever format its Content-Type says, or JSON if it doesn't
+// say. They all come out as the same Answer, so grading and idempotency keys
+// don't care how it was sent - and every kind of stage takes every format
+pub fn answer() -> impl Filter<Extract = (Answer,), Error = Rejection> + Clone {
+    warp::header::optional::<String>("content-type")
+        .and(warp::body::bytes())
+        .and_then(|content_type: Option<String>, body: Bytes| async move {
+            decode_answer(content_type.as_deref(), &body).map_err(reject::custom)
+        })
+}
+
+// A batch's answers, in the same formats
+pub fn answers() -> impl Filter<Extract = (Vec<Answer>,), Error = Rejection> + Clone {
+    warp::header::optional::<String>("content-type")
+        .and(warp::body::bytes())
+        .and_then(|content_type: Option<String>, body: Bytes| async move {
+            decode_answers(content_type.as_deref(), &body).map_err(reject::custom)
+        })
+}
+
+pub fn decode_answer(content_type: Option<&str>, body: &[u8]) -> Result<Answer, ModelError> {
+    decode(content_type, body, from_value, from_tsv)
+}
+
+// A list of answers, or in TSV, one answer after another with a blank line
+// between each
+pub fn decode_answers(content_type: Option<&str>, body: &[u8]) -> Result<Vec<Answer>, ModelError> {
+    decode(content_type, body, from_values, from_tsv_blocks)
+}
+
+fn decode<T>(
+    content_type: Option<&str>,
+    body: &[u8],
+    from_value: fn(Value) -> Result<T, String>,
+    from_tsv: fn(&[u8]) -> Result<T, String>,
+) -> Result<T, ModelError> {
+    // Parameters like charset don't change anything
+    let media_type = content_type
+        .and_then(|content_type| content_type.split(';').next())
+        .map(|media_type| media_type.trim().to_ascii_lowercase());
+
+    let decoded = match media_type.as_deref() {
+        None | Some(JSON) => serde_json::from_slice(body)
+            .map_err(|e| e.to_string())
+            .and_then(from_value),
+        Some(TSV) => from_tsv(body),
+        Some(media_type) if media_type == MSGPACK || MSGPACK_ALIASES.contains(&media_type) => {
+            rmp_serde::from_slice(body)
+                .map_err(|e| e.to_string())
+                .and_then(from_value)
+        }
+        Some(media_type) => {
+            return Err(ModelError::UnsupportedMediaType {
+                content_type: media_type.to_string(),
+            })
+        }
+    };
+    decoded.map_err(|reason| ModelError::MalformedAnswer { reason })
+}
+
+fn from_values(value: Value) -> Result<Vec<Answer>, String> {
+    match value {
+        Value::Array(values) => values
+            .into_iter()
+            .enumerate()
+            .map(|(i, value)| from_value(value).map_err(|reason| answer_n(i, reason)))
+            .collect(),
+        _ => Err("a batch's answers go in a list".to_string()),
+    }
+}
+
+// Any shape an Answer can be, or k-mer counts as a list of pairs
+fn from_value(value: Value) -> Result<Answer, String> {
+    if let Some(counts) = pairs(&value)? {
+        return Ok(Answer::Counts(counts));
+    }
+    serde_json::from_value(value).map_err(|_| "it isn't shaped like any kind of answer".to_string())
+}
+
+// `[["ACG", 2], ...]`, for languages that don't have maps with string keys.
+// Anything that isn't a list of lists isn't trying to be pairs
+fn pairs(value: &Value) -> Result<Option<HashMap<String, u64>>, String> {
+    let items = match value.as_array() {
+        Some(items) if !items.is_empty() && items.iter().all(Value::is_array) => items,
+        _ => return Ok(None),
+    };
+    let mut counts = HashMap::new();
+    for item in items {
+        match item.as_array().map(Vec::as_slice) {
+            Some([Value::String(kmer), count]) => {
+                let count = count
+                    .as_u64()
+                    .ok_or_else(|| format!("the count for {} isn't a whole number", kmer))?;
+                insert(&mut counts, kmer, count)?;
+            }
+            _ => return Err("pairs should look like [\"ACG\", 2]".to_string()),
+        }
+    }
+    Ok(Some(counts))
+}
+
+// A k-mer and its count on each line, one number on each line for fractions,
+// or a sequence on a line by itself. A header is fine, as long as its count
+// isn't a number
+fn from_tsv(body: &[u8]) -> Result<Answer, String> {
+    let body = std::str::from_utf8(body).map_err(|_| "it isn't UTF-8".to_string())?;
+    let mut rows: Vec<Vec<&str>> = body
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| line.split('\t').map(str::trim).collect())
+        .collect();
+    if rows.is_empty() {
+        return Err("it's empty".to_string());
+    }
+
+    if rows.iter().all(|row| row.len() == 2) {
+        if rows[0][1].parse::<u64>().is_err() {
+            rows.remove(0);
+        }
+        let mut counts = HashMap::new();
+        for row in rows {
+            let count = row[1]
+                .parse()
+                .map_err(|_| format!("the count for {} isn't a whole number", row[0]))?;
+            insert(&mut counts, row[0], count)?;
+        }
+        return Ok(Answer::Counts(counts));
+    }
+
+    if rows.iter().all(|row| row.len() == 1) {
+        if let Ok(fractions) = rows.iter().map(|row| row[0].parse()).collect() {
+            return Ok(Answer::Fractions(fractions));
+        }
+        if let [row] = rows.as_slice() {
+            return Ok(Answer::Sequence(row[0].to_string()));
+        }
+    }
+    Err("rows should be a k-mer and its count, or one value each".to_string())
+}
+
+fn from_tsv_blocks(body: &[u8]) -> Result<Vec<Answer>, String> {
+    let body = std::str::from_utf8(body).map_err(|_| "it isn't UTF-8".to_string())?;
+    let mut blocks = vec![];
+    let mut block = String::new();
+    for line in body.lines() {
+        if !line.trim().is_empty() {
+            block.push_str(line);
+            block.push('\n');
+        } else if !block.is_empty() {
+            blocks.push(std::mem::take(&mut block));
+        }
+    }
+    if !block.is_empty() {
+        blocks.push(block);
+    }
+    blocks
+        .iter()
+        .enumerate()
+        .map(|(i, block)| from_tsv(block.as_bytes()).map_err(|reason| answer_n(i, reason)))
+        .collect()
+}
+
+// Which of a batch's answers was wrong, counting from 1
+fn answer_n(i: usize, reason: String) -> String {
+    format!("answer {}: {}", i + 1, reason)
+}
+
+fn insert(counts: &mut HashMap<String, u64>, kmer: &str, count: u64) -> Result<(), String> {
+    match counts.insert(kmer.to_string(), count) {
+        Some(_) => Err(format!("{} is listed more than once", kmer)),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+
+    use super::{decode_answer, decode_answers, JSON, MSGPACK, TSV};
+    use crate::endpoints::errors::ModelError;
+    use crate::endpoints::messages::Answer;
+
+    fn counts() -> Answer {
+        Answer::Counts(HashMap::from([
+            ("ACG".to_string(), 2),
+            ("CGT".to_string(), 1),
+        ]))
+    }
+
+    fn malformed(result: Result<Answer, ModelError>) -> bool {
+        matches!(result, Err(ModelError::MalformedAnswer { .. }))
+    }
+
+    #[test]
+    fn test_json() {
+        let object = br#"{"ACG": 2, "CGT": 1}"#;
+        assert_eq!(decode_answer(None, object).unwrap(), counts());
+        assert_eq!(
+            decode_answer(Some("application/json; charset=utf-8"), object).unwrap(),
+            counts()
+        );
+        let pairs = br#"[["ACG", 2], ["CGT", 1]]"#;
+        assert_eq!(decode_answer(Some(JSON), pairs).unwrap(), counts());
+        assert_eq!(
+            decode_answer(Some(JSON), b"[0.5, 0.25]").unwrap(),
+            Answer::Fractions(vec![0.5, 0.25])
+        );
+        assert!(malformed(decode_answer(
+            Some(JSON),
+            br#"[["ACG", 2], ["ACG", 1]]"#
+        )));
+        assert!(malformed(decode_answer(Some(JSON), br#"[["ACG", -1]]"#)));
+        assert!(malformed(decode_answer(Some(JSON), br#"{"ACG": "#)));
+    }
+
+    #[test]
+    fn test_tsv() {
+        assert_eq!(
+            decode_answer(Some(TSV), b"ACG\t2\r\nCGT\t1\n").unwrap(),
+            counts()
+        );
+        assert_eq!(
+            decode_answer(Some(TSV), b"kmer\tcount\nACG\t2\nCGT\t1\n").unwrap(),
+            counts()
+        );
+        assert_eq!(
+            decode_answer(Some(TSV), b"0.5\n0.25\n").unwrap(),
+            Answer::Fractions(vec![0.5, 0.25])
+        );
+        assert_eq!(
+            decode_answer(Some(TSV), b"ACGT\n").unwrap(),
+            Answer::Sequence("ACGT".to_string())
+        );
+        assert!(malformed(decode_answer(Some(TSV), b"")));
+        assert!(malformed(decode_answer(Some(TSV), b"ACG\t2\nCGT\tlots\n")));
+        assert!(malformed(decode_answer(Some(TSV), b"ACGT\nTTGA\n")));
+    }
+
+    #[test]
+    fn test_msgpack() {
+        let object = rmp_serde::to_vec(&json!({"ACG": 2, "CGT": 1})).unwrap();
+        assert_eq!(decode_answer(Some(MSGPACK), &object).unwrap(), counts());
+        let pairs = rmp_serde::to_vec(&json!([["ACG", 2], ["CGT", 1]])).unwrap();
+        assert_eq!(
+            decode_answer(Some("application/x-msgpack"), &pairs).unwrap(),
+            counts()
+        );
+        let sequence = rmp_serde::to_vec(&json!("ACGT")).unwrap();
+        assert_eq!(
+            decode_answer(Some(MSGPACK), &sequence).unwrap(),
+            Answer::Sequence("ACGT".to_string())
+        );
+        assert!(malformed(decode_answer(Some(MSGPACK), b"\xc1")));
+    }
+
+    #[test]
+    fn test_batches() {
+        let sequence = || Answer::Sequence("ACGT".to_string());
+        assert_eq!(
+            decode_answers(None, br#"[{"ACG": 2, "CGT": 1}, "ACGT"]"#).unwrap(),
+            vec![counts(), sequence()]
+        );
+        assert_eq!(
+            decode_answers(Some(TSV), b"ACG\t2\nCGT\t1\n\nACGT\n").unwrap(),
+            vec![counts(), sequence()]
+        );
+        let answers = rmp_serde::to_vec(&json!([[["ACG", 2], ["CGT", 1]], "ACGT"])).unwrap();
+        assert_eq!(
+            decode_answers(Some(MSGPACK), &answers).unwrap(),
+            vec![counts(), sequence()]
+        );
+        assert!(matches!(
+            decode_answers(Some(JSON), br#"{"ACG": 2}"#),
+            Err(ModelError::MalformedAnswer { .. })
+        ));
+        assert!(matches!(
+            decode_answers(Some(TSV), b"ACGT\n\nACG\t2\nCGT\tlots\n"),
+            Err(ModelError::MalformedAnswer { reason }) if reason.starts_with("answer 2:")
+        ));
+    }
+
+    #[test]
+    fn test_unsupported() {
+        assert!(matches!(
+            decode_answer(Some("Application/XML"), b"<answer/>"),
+            Err(ModelError::UnsupportedMediaType { content_type }) if content_type == "application/xml"
+        ));
+    }
+}
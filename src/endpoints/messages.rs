@@ -1,31 +1,76 @@
-use serde_derive::{Deserialize, Serialize};
+// The message types are shared with the client, so they live in api-types
+pub use api_types::messages::*;
 
-use super::errors;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
 
-#[derive(Serialize, Deserialize)]
-pub struct RegisterResponse {
-    pub token: String,
-    pub challenge_string: String,
+use crate::model::retention::RetentionAction;
+
+// Everything below is only used by the server - query strings, and the admin
+// routes' bodies
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LeaderboardQuery {
+    /// The cycle to rank - the active one by default
+    pub cycle: Option<String>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DeliveryQuery {
+    /// Only show deliveries in this state - pending, delivered or failed
+    pub status: Option<String>,
+    /// How many of the most recent deliveries to show, 50 by default
+    pub limit: Option<i64>,
 }
-#[derive(Serialize, Deserialize)]
-pub struct RegisterRequest {
-    pub name: String,
-    pub nuid: String,
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct JobQuery {
+    /// Only show jobs in this state - pending, running, done or dead
+    pub status: Option<String>,
+    /// Only show jobs of this kind, e.g. deliver_webhook
+    pub kind: Option<String>,
+    /// How many of the most recent jobs to show, 50 by default
+    pub limit: Option<i64>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuditQuery {
+    /// Only show this action, e.g. token.rotated
+    pub action: Option<String>,
+    /// Only show actions taken by applicants or by admins
+    pub actor: Option<String>,
+    /// Only show actions about this applicant
+    pub nuid: Option<String>,
+    /// Only show actions at or after this time (RFC 3339)
+    pub since: Option<DateTime<Utc>>,
+    /// Only show actions before this time (RFC 3339)
+    pub until: Option<DateTime<Utc>>,
+    /// How many of the most recent actions to show, 50 by default
+    pub limit: Option<i64>,
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct HandleForgotTokenResponse {
-    pub token: String,
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct FlagQuery {
+    /// Only show applicants from this cycle
+    pub cycle: Option<String>,
 }
-#[derive(Serialize, Deserialize)]
 
-pub struct GetChallengeString {
-    pub challenge_string: String,
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct StatsQuery {
+    /// The cycle to summarize - the active one by default
+    pub cycle: Option<String>,
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct ErrorResponse<'a> {
-    pub msg: &'a str,
-    #[serde(flatten)]
-    pub error: Option<errors::ApiError>,
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct RetentionPolicy {
+    /// Days after registration to keep applicants for - leave out to keep them forever
+    pub days: Option<i32>,
+    pub action: RetentionAction,
 }
@@ -1,6 +1,10 @@
 pub mod errors;
+pub mod formats;
 pub mod messages;
+pub mod openapi;
 pub mod routes;
 pub mod server;
+pub mod ui;
+pub mod validation;
 pub use errors::ApiError;
 pub use server::end;
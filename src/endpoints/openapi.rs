@@ -0,0 +1,250 @@
+use utoipa::openapi::security::{Http, HttpAuthScheme, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+use warp::{reply, Rejection, Reply};
+
+use super::errors::{ApiError, FieldError};
+use super::messages::{
+    Answer, BatchResponse, ErrorResponse, GetChallengeString, HandleForgotTokenResponse,
+    HintResponse, Language, ProgramSubmission, RegisterRequest, RegisterResponse, RetentionPolicy,
+    RotateTokenResponse, RunResponse,
+};
+use super::server;
+use crate::jobs::JobRecord;
+use crate::live::{LiveEvent, LiveKind};
+use crate::model::analysis::{ApplicantReview, Flag, FlaggedApplicant};
+use crate::model::audit::AuditEvent;
+use crate::model::cycles::Cycle;
+use crate::model::hints::HintUse;
+use crate::model::leaderboard::{Leaderboard, LeaderboardEntry};
+use crate::model::retention::RetentionAction;
+use crate::model::stages::{Challenge, Stage, Stages};
+use crate::model::stats::{AttemptCount, CycleStats, DailyRegistrations, Funnel, Percentiles};
+use crate::model::tokens::TokenRotation;
+use crate::model::types::{Applicant, StageTiming, TestOutcome, TestRun};
+use crate::webhooks::Delivery;
+
+// Everything in here is pulled off the handler annotations in server.rs and the
+// ToSchema derives on the message types - add new handlers to `paths` below
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Generate Technical Application",
+        description = "Register, fetch your challenge string, and submit your solution"
+    ),
+    servers((url = "/v1")),
+    paths(
+        server::handle_register,
+        server::handle_forgot_token,
+        server::handle_submit,
+        server::handle_run_program,
+        server::handle_start_batch,
+        server::handle_answer_batch,
+        server::handle_hint,
+        server::health_check,
+        server::handle_get_challenge,
+        server::handle_get_applicants,
+        server::handle_get_applicant,
+        server::handle_get_leaderboard,
+        server::handle_get_webhook_deliveries,
+        server::handle_get_jobs,
+        server::handle_retry_job,
+        server::handle_rotate_token,
+        server::handle_admin_rotate_token,
+        server::handle_get_token_rotations,
+        server::handle_get_audit_events,
+        server::handle_delete_my_data,
+        server::handle_delete_applicant,
+        server::handle_get_cycles,
+        server::handle_set_retention,
+        server::handle_get_stages,
+        server::handle_set_stages,
+        server::handle_get_applicant_review,
+        server::handle_get_flags,
+        server::handle_get_stats,
+        server::handle_live,
+    ),
+    components(schemas(
+        RegisterRequest,
+        RegisterResponse,
+        HandleForgotTokenResponse,
+        GetChallengeString,
+        ErrorResponse,
+        ApiError,
+        FieldError,
+        Applicant,
+        StageTiming,
+        Leaderboard,
+        LeaderboardEntry,
+        Delivery,
+        JobRecord,
+        RotateTokenResponse,
+        TokenRotation,
+        AuditEvent,
+        Cycle,
+        RetentionPolicy,
+        RetentionAction,
+        Stages,
+        Stage,
+        Challenge,
+        Answer,
+        ProgramSubmission,
+        Language,
+        RunResponse,
+        TestRun,
+        BatchResponse,
+        HintResponse,
+        HintUse,
+        TestOutcome,
+        ApplicantReview,
+        FlaggedApplicant,
+        Flag,
+        CycleStats,
+        DailyRegistrations,
+        Funnel,
+        Percentiles,
+        AttemptCount,
+        LiveEvent,
+        LiveKind,
+    )),
+    modifiers(&AdminToken)
+)]
+pub struct ApiDoc;
+
+struct AdminToken;
+
+impl Modify for AdminToken {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "admin_token",
+                SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)),
+            );
+        }
+    }
+}
+
+const DOCS_PAGE: &str = include_str!("docs.html");
+
+pub async fn handle_openapi() -> Result<impl Reply, Rejection> {
+    Ok(reply::json(&ApiDoc::openapi()))
+}
+
+pub async fn handle_docs() -> Result<impl Reply, Rejection> {
+    Ok(reply::html(DOCS_PAGE))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::Value;
+    use sqlx::postgres::PgPoolOptions;
+    use warp::filters::BoxedFilter;
+    use warp::http::Method;
+    use warp::reject::MethodNotAllowed;
+    use warp::reply::Response;
+    use warp::Filter;
+
+    use super::handle_openapi;
+    use crate::config::Settings;
+    use crate::endpoints::routes::openapi_route;
+    use crate::endpoints::server;
+    use crate::live::Live;
+    use crate::model::leaderboard::Leaderboards;
+
+    // Every route the server mounts, as it mounts them. Nothing's listening on
+    // the database, so handlers that get that far fail fast instead
+    fn routes() -> Vec<(&'static str, BoxedFilter<(Response,)>)> {
+        let pool = PgPoolOptions::new()
+            .acquire_timeout(Duration::from_millis(50))
+            .connect_lazy("postgres://nobody@localhost:1/nothing")
+            .unwrap();
+        let settings: Settings = config::Config::builder()
+            .add_source(config::File::with_name("configuration/base.yaml"))
+            .add_source(config::File::with_name("configuration/local.yaml"))
+            .build()
+            .unwrap()
+            .try_deserialize()
+            .unwrap();
+        server::routes(pool, &settings, Live::new(), Leaderboards::default(), None)
+    }
+
+    // Routes that serve the docs themselves, and so aren't documented
+    const UNDOCUMENTED: [&str; 2] = ["/openapi.json", "/docs"];
+
+    async fn served_operations() -> Vec<(Method, String)> {
+        let res = warp::test::request()
+            .method("GET")
+            .path("/openapi.json")
+            .reply(&openapi_route().and_then(handle_openapi))
+            .await;
+        let spec: Value = serde_json::from_slice(res.body()).unwrap();
+
+        let mut operations = vec![];
+        for (path, item) in spec["paths"].as_object().unwrap() {
+            for method in item.as_object().unwrap().keys() {
+                // Fill the path params in with something that parses
+                let path = path
+                    .replace("{token}", &uuid::Uuid::nil().to_string())
+                    .replace("{batch_id}", &uuid::Uuid::nil().to_string())
+                    .replace("{nuid}", "001453760")
+                    .replace("{job_id}", "1")
+                    .replace("{cycle}", "default");
+                operations.push((method.to_uppercase().parse().unwrap(), path));
+            }
+        }
+        operations
+    }
+
+    async fn is_routed(route: &BoxedFilter<(Response,)>, method: &Method, path: &str) -> bool {
+        match warp::test::request()
+            .method(method.as_str())
+            .path(path)
+            .filter(route)
+            .await
+        {
+            Ok(_) => true,
+            // A bad (or missing) body still means the path and method matched
+            Err(rejection) => {
+                !rejection.is_not_found() && rejection.find::<MethodNotAllowed>().is_none()
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_documented_operations_are_routed() {
+        let routes = routes();
+        for (method, path) in served_operations().await {
+            let mut routed = false;
+            for (_, route) in &routes {
+                routed |= is_routed(route, &method, &path).await;
+            }
+            assert!(routed, "{} {} is documented but not routed", method, path);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_routes_are_documented() {
+        let mut operations = served_operations().await;
+        operations.extend(UNDOCUMENTED.map(|path| (Method::GET, path.to_string())));
+        for (name, route) in routes() {
+            let mut documented = false;
+            for (method, path) in &operations {
+                documented |= is_routed(&route, method, path).await;
+            }
+            assert!(documented, "the {} route isn't in the OpenAPI spec", name);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_docs_routes_are_served() {
+        let routes = routes();
+        for path in UNDOCUMENTED {
+            let mut routed = false;
+            for (_, route) in &routes {
+                routed |= is_routed(route, &Method::GET, path).await;
+            }
+            assert!(routed, "{} isn't served", path);
+        }
+    }
+}
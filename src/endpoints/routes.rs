@@ -1,12 +1,24 @@
-use std::collections::HashMap;
-use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
 
-use sqlx::PgPool;
+use ipnet::IpNet;
 use uuid::Uuid;
 use warp::filters::BoxedFilter;
-use warp::{path, Filter, Rejection};
+use warp::http::HeaderMap;
+use warp::{path, reject, Filter, Rejection};
 
-use super::messages::RegisterRequest;
+use super::errors::ModelError;
+use super::formats;
+use super::messages::{
+    Answer, AuditQuery, DeliveryQuery, FlagQuery, JobQuery, LeaderboardQuery, ProgramSubmission,
+    RegisterRequest, RetentionPolicy, StatsQuery,
+};
+use crate::config::ProxySettings;
+use crate::model::audit::RequestContext;
+use crate::model::stages::Stages;
+
+// Headers are client controlled, so they get cut down before they're stored
+const MAX_USER_AGENT_LEN: usize = 512;
+const MAX_REQUEST_ID_LEN: usize = 128;
 
 pub fn register_route() -> BoxedFilter<(RegisterRequest,)> {
     let register = warp::path!("register");
@@ -24,9 +36,36 @@ pub fn health() -> BoxedFilter<()> {
     warp::get().and(health).boxed()
 }
 
-pub fn submit() -> BoxedFilter<(Uuid, HashMap<String, u64>)> {
+pub fn submit() -> BoxedFilter<(Uuid, Option<String>, Answer)> {
     let route = warp::path!("submit" / Uuid);
-    warp::post().and(route).and(warp::body::json()).boxed()
+    warp::post()
+        .and(route)
+        .and(warp::header::optional::<String>("idempotency-key"))
+        .and(formats::answer())
+        .boxed()
+}
+
+// Sources are capped at 64KB by validation - this just stops anything huge
+// being read in first
+pub fn run_program_route() -> BoxedFilter<(Uuid, ProgramSubmission)> {
+    let route = warp::path!("run" / Uuid);
+    warp::post()
+        .and(route)
+        .and(warp::body::content_length_limit(256 * 1024))
+        .and(warp::body::json())
+        .boxed()
+}
+
+pub fn start_batch_route() -> BoxedFilter<(Uuid,)> {
+    let route = warp::path!("batch" / Uuid);
+
+    warp::post().and(route).boxed()
+}
+
+pub fn answer_batch_route() -> BoxedFilter<(Uuid, Uuid, Vec<Answer>)> {
+    let route = warp::path!("batch" / Uuid / Uuid);
+
+    warp::post().and(route).and(formats::answers()).boxed()
 }
 
 pub fn get_challenge_string_route() -> BoxedFilter<(Uuid,)> {
@@ -35,6 +74,24 @@ pub fn get_challenge_string_route() -> BoxedFilter<(Uuid,)> {
     warp::get().and(route).boxed()
 }
 
+pub fn hint_route() -> BoxedFilter<(Uuid,)> {
+    let route = warp::path!("hint" / Uuid);
+
+    warp::get().and(route).boxed()
+}
+
+pub fn rotate_token_route() -> BoxedFilter<(Uuid,)> {
+    let route = warp::path!("rotate_token" / Uuid);
+
+    warp::post().and(route).boxed()
+}
+
+pub fn delete_my_data_route() -> BoxedFilter<(Uuid,)> {
+    let route = warp::path!("my_data" / Uuid);
+
+    warp::delete().and(route).boxed()
+}
+
 /*
 This route should return:
    - whether or not the applicant provided the correct solution
@@ -51,3 +108,320 @@ pub fn get_applicants_route() -> BoxedFilter<(Vec<String>,)> {
 
     warp::get().and(route).and(warp::body::json()).boxed()
 }
+
+pub fn leaderboard_route() -> BoxedFilter<(LeaderboardQuery,)> {
+    let route = path!("leaderboard");
+
+    warp::get().and(route).and(warp::query()).boxed()
+}
+
+pub fn openapi_route() -> BoxedFilter<()> {
+    let route = path!("openapi.json");
+
+    warp::get().and(route).boxed()
+}
+
+pub fn docs_route() -> BoxedFilter<()> {
+    let route = path!("docs");
+
+    warp::get().and(route).boxed()
+}
+
+pub fn webhook_deliveries_route() -> BoxedFilter<(DeliveryQuery,)> {
+    let route = path!("admin" / "webhooks" / "deliveries");
+
+    warp::get().and(route).and(warp::query()).boxed()
+}
+
+pub fn jobs_route() -> BoxedFilter<(JobQuery,)> {
+    let route = path!("admin" / "jobs");
+
+    warp::get().and(route).and(warp::query()).boxed()
+}
+
+pub fn retry_job_route() -> BoxedFilter<(i64,)> {
+    let route = path!("admin" / "jobs" / i64 / "retry");
+
+    warp::post().and(route).boxed()
+}
+
+pub fn admin_rotate_token_route() -> BoxedFilter<(String,)> {
+    let route = path!("admin" / "applicants" / String / "rotate_token");
+
+    warp::post().and(route).boxed()
+}
+
+pub fn token_rotations_route() -> BoxedFilter<(String,)> {
+    let route = path!("admin" / "applicants" / String / "rotations");
+
+    warp::get().and(route).boxed()
+}
+
+pub fn audit_route() -> BoxedFilter<(AuditQuery,)> {
+    let route = path!("admin" / "audit");
+
+    warp::get().and(route).and(warp::query()).boxed()
+}
+
+pub fn applicant_review_route() -> BoxedFilter<(String,)> {
+    let route = path!("admin" / "applicants" / String);
+
+    warp::get().and(route).boxed()
+}
+
+pub fn live_route() -> BoxedFilter<()> {
+    let route = path!("admin" / "live");
+
+    warp::get().and(route).boxed()
+}
+
+pub fn flags_route() -> BoxedFilter<(FlagQuery,)> {
+    let route = path!("admin" / "flags");
+
+    warp::get().and(route).and(warp::query()).boxed()
+}
+
+pub fn stats_route() -> BoxedFilter<(StatsQuery,)> {
+    let route = path!("admin" / "stats");
+
+    warp::get().and(route).and(warp::query()).boxed()
+}
+
+pub fn delete_applicant_route() -> BoxedFilter<(String,)> {
+    let route = path!("admin" / "applicants" / String);
+
+    warp::delete().and(route).boxed()
+}
+
+pub fn cycles_route() -> BoxedFilter<()> {
+    let route = path!("admin" / "cycles");
+
+    warp::get().and(route).boxed()
+}
+
+pub fn cycle_retention_route() -> BoxedFilter<(String, RetentionPolicy)> {
+    let route = path!("admin" / "cycles" / String / "retention");
+
+    warp::put().and(route).and(warp::body::json()).boxed()
+}
+
+pub fn cycle_stages_route() -> BoxedFilter<(String,)> {
+    let route = path!("admin" / "cycles" / String / "stages");
+
+    warp::get().and(route).boxed()
+}
+
+pub fn set_cycle_stages_route() -> BoxedFilter<(String, Stages)> {
+    let route = path!("admin" / "cycles" / String / "stages");
+
+    warp::put().and(route).and(warp::body::json()).boxed()
+}
+
+// Who's asking, for the audit log and abuse analysis. Callers can send their own
+// X-Request-Id to tie the log back to their side, otherwise we make one up
+pub fn request_context(proxies: &ProxySettings) -> BoxedFilter<(RequestContext,)> {
+    let trusted = proxies.trusted.clone();
+    warp::addr::remote()
+        .and(warp::header::headers_cloned())
+        .map(move |addr: Option<SocketAddr>, headers: HeaderMap| {
+            // Read leniently - a strange user agent is no reason to turn a request away
+            let header = |name: &str, max_len: usize| {
+                headers
+                    .get(name)
+                    .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned())
+                    .filter(|value| !value.is_empty())
+                    .map(|value| truncate(value, max_len))
+            };
+            RequestContext {
+                ip: addr.map(|addr| client_ip(addr.ip(), &headers, &trusted).to_string()),
+                user_agent: header("user-agent", MAX_USER_AGENT_LEN),
+                request_id: header("x-request-id", MAX_REQUEST_ID_LEN)
+                    .unwrap_or_else(|| Uuid::new_v4().to_string()),
+            }
+        })
+        .boxed()
+}
+
+// Behind a trusted proxy, the address the proxy says it's forwarding for.
+// Otherwise whoever connected, whatever their headers claim
+fn client_ip(peer: IpAddr, headers: &HeaderMap, trusted: &[IpNet]) -> IpAddr {
+    let is_trusted = |ip: &IpAddr| trusted.iter().any(|network| network.contains(ip));
+    if !is_trusted(&peer) {
+        return peer;
+    }
+
+    // Fly sets this itself, so it's the real client even if they sent one too
+    let fly_client_ip = headers
+        .get("fly-client-ip")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok());
+    if let Some(ip) = fly_client_ip {
+        return ip;
+    }
+
+    // Every proxy appends who it heard from, so the list is read from the right
+    // and the first hop we don't trust is the client. Anything to the left of
+    // that was made up by the client
+    let forwarded: Vec<&str> = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .collect();
+    let mut client = peer;
+    for hop in forwarded.iter().rev() {
+        match hop.trim().parse::<IpAddr>() {
+            Ok(ip) => {
+                client = ip;
+                if !is_trusted(&ip) {
+                    break;
+                }
+            }
+            // Can't tell who sent this, so stop at the last hop we could
+            Err(_) => break,
+        }
+    }
+    client
+}
+
+fn truncate(mut s: String, max_len: usize) -> String {
+    if s.len() > max_len {
+        let mut end = max_len;
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
+        s.truncate(end);
+    }
+    s
+}
+
+// Goes after the path on admin routes - expects `Authorization: Bearer <token>`
+pub fn admin(admin_token: Option<String>) -> BoxedFilter<()> {
+    warp::header::optional::<String>("authorization")
+        .and_then(move |header: Option<String>| {
+            let admin_token = admin_token.clone();
+            async move {
+                match (admin_token, header) {
+                    (Some(admin_token), Some(header)) if is_admin_token(&admin_token, &header) => {
+                        Ok(())
+                    }
+                    _ => Err::<(), Rejection>(reject::custom(ModelError::Unauthorized)),
+                }
+            }
+        })
+        .untuple_one()
+        .boxed()
+}
+
+fn is_admin_token(admin_token: &str, header: &str) -> bool {
+    match header.strip_prefix("Bearer ") {
+        Some(given) => tokens_match(admin_token, given.trim()),
+        None => false,
+    }
+}
+
+// Constant time, so the token can't be guessed a byte at a time
+pub fn tokens_match(expected: &str, given: &str) -> bool {
+    let (expected, given) = (expected.as_bytes(), given.as_bytes());
+    given.len() == expected.len()
+        && given
+            .iter()
+            .zip(expected)
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use super::request_context;
+    use crate::config::ProxySettings;
+
+    fn proxies(trusted: &[&str]) -> ProxySettings {
+        ProxySettings {
+            trusted: trusted
+                .iter()
+                .map(|network| {
+                    network
+                        .parse()
+                        .unwrap_or_else(|_| network.parse::<IpAddr>().unwrap().into())
+                })
+                .collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_request_context() {
+        let ctx = warp::test::request()
+            .remote_addr("10.0.0.7:5000".parse().unwrap())
+            .header("user-agent", "python-requests/2.31")
+            .header("x-request-id", "abc-123")
+            .filter(&request_context(&proxies(&[])))
+            .await
+            .unwrap();
+
+        assert_eq!(ctx.ip.as_deref(), Some("10.0.0.7"));
+        assert_eq!(ctx.user_agent.as_deref(), Some("python-requests/2.31"));
+        assert_eq!(ctx.request_id, "abc-123");
+    }
+
+    #[tokio::test]
+    async fn test_request_context_defaults() {
+        let ctx = warp::test::request()
+            .header("user-agent", "é".repeat(300))
+            .filter(&request_context(&proxies(&[])))
+            .await
+            .unwrap();
+
+        assert_eq!(ctx.ip, None);
+        // Cut down on a character boundary
+        assert_eq!(ctx.user_agent.unwrap().len(), 512);
+        // Made up, since the client didn't send one
+        assert!(uuid::Uuid::parse_str(&ctx.request_id).is_ok());
+    }
+
+    async fn client_ip(trusted: &[&str], peer: &str, headers: &[(&str, &str)]) -> String {
+        let mut request = warp::test::request().remote_addr(peer.parse().unwrap());
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        request
+            .filter(&request_context(&proxies(trusted)))
+            .await
+            .unwrap()
+            .ip
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_client_ip() {
+        let fly = [("fly-client-ip", "203.0.113.9")];
+        // Only a trusted proxy gets to say who the client is
+        assert_eq!(client_ip(&[], "10.0.0.7:5000", &fly).await, "10.0.0.7");
+        assert_eq!(
+            client_ip(&["10.0.0.0/8"], "10.0.0.7:5000", &fly).await,
+            "203.0.113.9"
+        );
+
+        let forwarded = [("x-forwarded-for", "6.6.6.6, 203.0.113.9, 10.0.0.3")];
+        assert_eq!(
+            client_ip(&["10.0.0.0/8"], "10.0.0.7:5000", &forwarded).await,
+            "203.0.113.9"
+        );
+        assert_eq!(
+            client_ip(&["10.0.0.7"], "10.0.0.7:5000", &forwarded).await,
+            "10.0.0.3"
+        );
+        assert_eq!(
+            client_ip(&[], "10.0.0.7:5000", &forwarded).await,
+            "10.0.0.7"
+        );
+        // Garbage from the client doesn't get passed off as an address
+        let garbage = [("x-forwarded-for", "6.6.6.6, nonsense, 10.0.0.3")];
+        assert_eq!(
+            client_ip(&["10.0.0.0/8"], "10.0.0.7:5000", &garbage).await,
+            "10.0.0.3"
+        );
+    }
+}
@@ -1,83 +1,335 @@
-use std::collections::HashMap;
 use std::convert::Infallible;
 
-use super::errors::ModelError;
+use super::errors::{codes, ModelError};
+use super::formats;
 use super::messages::{
-    ErrorResponse, GetChallengeString, HandleForgotTokenResponse, RegisterRequest, RegisterResponse,
+    Answer, AuditQuery, DeliveryQuery, ErrorResponse, FlagQuery, HandleForgotTokenResponse,
+    JobQuery, LeaderboardQuery, ProgramSubmission, RegisterRequest, RegisterResponse,
+    RetentionPolicy, RotateTokenResponse, RunResponse, StatsQuery,
 };
+use super::openapi::{handle_docs, handle_openapi};
 use super::routes::{
-    forgot_token_route, get_applicant_route, get_applicants_route, get_challenge_string_route,
-    health, register_route, submit,
+    admin, admin_rotate_token_route, answer_batch_route, applicant_review_route, audit_route,
+    cycle_retention_route, cycle_stages_route, cycles_route, delete_applicant_route,
+    delete_my_data_route, docs_route, flags_route, forgot_token_route, get_applicant_route,
+    get_applicants_route, get_challenge_string_route, health, hint_route, jobs_route,
+    leaderboard_route, live_route, openapi_route, register_route, request_context, retry_job_route,
+    rotate_token_route, run_program_route, set_cycle_stages_route, start_batch_route, stats_route,
+    submit, token_rotations_route, webhook_deliveries_route,
 };
+use super::ui;
+use super::validation::{
+    validate_idempotency_key, validate_program, validate_registration, validate_retention,
+    validate_stages,
+};
+use crate::config::{AnalysisSettings, MailBackend, Settings};
 use crate::endpoints::ApiError;
+use crate::jobs;
+use crate::live::{self, Live};
+use crate::model::audit::{self, RequestContext};
+use crate::model::leaderboard::Leaderboards;
+use crate::model::programs::Running;
+use crate::model::stages::{self, Stages};
+use crate::model::{analysis, batches, cycles, hints, programs, retention, stats};
 use crate::model::{
-    check_solution, get_applicants, register_user, retreive_challenge, retreive_token,
+    check_solution, get_applicants, get_rotations, register_user, retreive_challenge, rotate_token,
+    rotate_token_for, send_token,
 };
+use crate::sandbox::Sandbox;
+use crate::webhooks;
 use serde_json::json;
 use sqlx::PgPool;
 use uuid::Uuid;
 use warp::body::BodyDeserializeError;
+use warp::filters::BoxedFilter;
+use warp::http::header::{HeaderValue, ACCEPT, CONTENT_TYPE};
+use warp::http::HeaderMap;
 use warp::hyper::StatusCode;
-use warp::reject::MethodNotAllowed;
+use warp::reject::{InvalidQuery, MethodNotAllowed, UnsupportedMediaType};
+use warp::reply::Response;
 use warp::{reject, reply, Filter, Rejection, Reply};
 
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
 #[macro_export]
 macro_rules! api_err {
-    ($msg:expr, $api_err:expr) => {
+    ($status:expr, $code:expr, $msg:expr, $api_err:expr) => {
         $crate::endpoints::messages::ErrorResponse {
-            msg: $msg,
+            problem_type: "about:blank".into(),
+            title: $status.canonical_reason().unwrap_or_default().into(),
+            status: $status.as_u16(),
+            code: $code.into(),
+            msg: $msg.into(),
             error: Some($api_err),
         }
     };
-    ($msg:expr) => {
+    ($status:expr, $code:expr, $msg:expr) => {
         $crate::endpoints::messages::ErrorResponse {
-            msg: $msg,
+            problem_type: "about:blank".into(),
+            title: $status.canonical_reason().unwrap_or_default().into(),
+            status: $status.as_u16(),
+            code: $code.into(),
+            msg: $msg.into(),
             error: None,
         }
     };
 }
 
-pub fn end(o: Option<PgPool>) -> impl Filter<Extract = impl Reply, Error = Infallible> + Clone {
+pub fn end(
+    o: Option<PgPool>,
+    settings: &Settings,
+    live: Live,
+    leaderboards: Leaderboards,
+    sandbox: Option<Sandbox>,
+) -> impl Filter<Extract = impl Reply, Error = Infallible> + Clone {
     let pool = o.unwrap();
+    let ui = warp::path("admin")
+        .and(warp::path("ui"))
+        .and(ui::pages(pool.clone(), settings, live.clone()).recover(ui::handle_rejection));
+    let api = api(pool, settings, live, leaderboards, sandbox);
+
+    // Everything lives under /v1 now. The unversioned paths are still served for
+    // clients written before we versioned the API, but they're deprecated
+    let v1 = warp::path("v1").and(api.clone().recover(handle_rejection));
+    let unversioned = api
+        .map(|reply| reply::with_header(reply, "Deprecation", "true").into_response())
+        .recover(handle_deprecated_rejection);
+
+    // The admin pages are HTML, so they're left out of content negotiation
+    let api = warp::header::headers_cloned()
+        .and(v1.or(unversioned))
+        .map(negotiate_content_type);
+    ui.or(api)
+}
+
+fn api(
+    pool: PgPool,
+    settings: &Settings,
+    live: Live,
+    leaderboards: Leaderboards,
+    sandbox: Option<Sandbox>,
+) -> BoxedFilter<(Response,)> {
+    // Boxed one at a time - one long chain of nested futures is enough to
+    // overflow a worker thread's stack in debug builds
+    routes(pool, settings, live, leaderboards, sandbox)
+        .into_iter()
+        .map(|(_, route)| route)
+        .reduce(|api, route| api.or(route).unify().boxed())
+        .expect("the API serves something")
+}
+
+// Everything the API serves, by name. The OpenAPI tests check every one of these
+// against the spec, so a route can't be added here without being documented
+pub(super) fn routes(
+    pool: PgPool,
+    settings: &Settings,
+    live: Live,
+    leaderboards: Leaderboards,
+    // None when programs can't be run here
+    sandbox: Option<Sandbox>,
+) -> Vec<(&'static str, BoxedFilter<(Response,)>)> {
     let with_db = warp::any().map(move || pool.clone());
+    let token_lifetime = settings.tokens.lifetime();
+    let with_token_lifetime = warp::any().map(move || token_lifetime);
+    let analysis = settings.analysis;
+    let with_analysis = warp::any().map(move || analysis);
+    let with_leaderboards = warp::any().map(move || leaderboards.clone());
+    let with_live = warp::any().map(move || live.clone());
+    let with_sandbox = warp::any().map(move || sandbox.clone());
+    let running = Running::default();
+    let with_running = warp::any().map(move || running.clone());
+    let admin = admin(settings.admin.token.clone());
+    let context = request_context(&settings.proxies);
 
     let register = register_route()
         .and(with_db.clone())
+        .and(with_token_lifetime)
+        .and(context.clone())
         .and_then(handle_register);
+    let email_enabled = settings.email.backend != MailBackend::Off;
     let forgot_token = forgot_token_route()
         .and(with_db.clone())
+        .and(warp::any().map(move || email_enabled))
+        .and(context.clone())
         .and_then(handle_forgot_token);
 
-    let submit = submit().and(with_db.clone()).and_then(handle_submit);
+    let submit = submit()
+        .and(with_db.clone())
+        .and(context.clone())
+        .and_then(handle_submit);
+    let run_program = run_program_route()
+        .and(with_db.clone())
+        .and(with_sandbox)
+        .and(with_running)
+        .and(context.clone())
+        .and_then(handle_run_program);
+    let start_batch = start_batch_route()
+        .and(with_db.clone())
+        .and_then(handle_start_batch);
+    let answer_batch = answer_batch_route()
+        .and(with_db.clone())
+        .and(context.clone())
+        .and_then(handle_answer_batch);
     let health = health().and_then(health_check);
     let get_challenge = get_challenge_string_route()
         .and(with_db.clone())
         .and_then(handle_get_challenge);
+    let hint_penalty = settings.hints.penalty;
+    let hint = hint_route()
+        .and(with_db.clone())
+        .and(warp::any().map(move || hint_penalty))
+        .and(context.clone())
+        .and_then(handle_hint);
     let get_applicants = get_applicants_route()
         .and(with_db.clone())
         .and_then(handle_get_applicants);
     let get_applicant = get_applicant_route()
         .and(with_db.clone())
         .and_then(handle_get_applicant);
+    let leaderboard = leaderboard_route()
+        .and(with_db.clone())
+        .and(with_leaderboards)
+        .and_then(handle_get_leaderboard);
+    let openapi = openapi_route().and_then(handle_openapi);
+    let docs = docs_route().and_then(handle_docs);
+    let webhook_deliveries = webhook_deliveries_route()
+        .and(admin.clone())
+        .and(with_db.clone())
+        .and_then(handle_get_webhook_deliveries);
+    let jobs = jobs_route()
+        .and(admin.clone())
+        .and(with_db.clone())
+        .and_then(handle_get_jobs);
+    let retry_job = retry_job_route()
+        .and(admin.clone())
+        .and(with_db.clone())
+        .and(context.clone())
+        .and_then(handle_retry_job);
+    let rotate_token = rotate_token_route()
+        .and(with_db.clone())
+        .and(with_token_lifetime)
+        .and(context.clone())
+        .and_then(handle_rotate_token);
+    let admin_rotate_token = admin_rotate_token_route()
+        .and(admin.clone())
+        .and(with_db.clone())
+        .and(with_token_lifetime)
+        .and(context.clone())
+        .and_then(handle_admin_rotate_token);
+    let token_rotations = token_rotations_route()
+        .and(admin.clone())
+        .and(with_db.clone())
+        .and_then(handle_get_token_rotations);
+    let audit = audit_route()
+        .and(admin.clone())
+        .and(with_db.clone())
+        .and_then(handle_get_audit_events);
+    let delete_my_data = delete_my_data_route()
+        .and(with_db.clone())
+        .and(context.clone())
+        .and_then(handle_delete_my_data);
+    let delete_applicant = delete_applicant_route()
+        .and(admin.clone())
+        .and(with_db.clone())
+        .and(context.clone())
+        .and_then(handle_delete_applicant);
+    let cycles = cycles_route()
+        .and(admin.clone())
+        .and(with_db.clone())
+        .and_then(handle_get_cycles);
+    let cycle_retention = cycle_retention_route()
+        .and(admin.clone())
+        .and(with_db.clone())
+        .and(context.clone())
+        .and_then(handle_set_retention);
+    let cycle_stages = cycle_stages_route()
+        .and(admin.clone())
+        .and(with_db.clone())
+        .and_then(handle_get_stages);
+    let set_cycle_stages = set_cycle_stages_route()
+        .and(admin.clone())
+        .and(with_db.clone())
+        .and(context.clone())
+        .and_then(handle_set_stages);
+    let applicant_review = applicant_review_route()
+        .and(admin.clone())
+        .and(with_db.clone())
+        .and(with_analysis)
+        .and_then(handle_get_applicant_review);
+    let flags = flags_route()
+        .and(admin.clone())
+        .and(with_db.clone())
+        .and(with_analysis)
+        .and_then(handle_get_flags);
+    let stats = stats_route()
+        .and(admin.clone())
+        .and(with_db.clone())
+        .and_then(handle_get_stats);
+    let live = live_route()
+        .and(admin.clone())
+        .and(with_live)
+        .and_then(handle_live);
+
+    vec![
+        ("register", boxed(register)),
+        ("forgot_token", boxed(forgot_token)),
+        ("submit", boxed(submit)),
+        ("run", boxed(run_program)),
+        ("batch", boxed(start_batch)),
+        ("answer_batch", boxed(answer_batch)),
+        ("health", boxed(health)),
+        ("challenge", boxed(get_challenge)),
+        ("hint", boxed(hint)),
+        ("applicants", boxed(get_applicants)),
+        ("applicant", boxed(get_applicant)),
+        ("leaderboard", boxed(leaderboard)),
+        ("openapi", boxed(openapi)),
+        ("docs", boxed(docs)),
+        ("webhook_deliveries", boxed(webhook_deliveries)),
+        ("jobs", boxed(jobs)),
+        ("retry_job", boxed(retry_job)),
+        ("rotate_token", boxed(rotate_token)),
+        ("admin_rotate_token", boxed(admin_rotate_token)),
+        ("token_rotations", boxed(token_rotations)),
+        ("audit", boxed(audit)),
+        ("delete_my_data", boxed(delete_my_data)),
+        ("delete_applicant", boxed(delete_applicant)),
+        ("cycles", boxed(cycles)),
+        ("cycle_retention", boxed(cycle_retention)),
+        ("cycle_stages", boxed(cycle_stages)),
+        ("set_cycle_stages", boxed(set_cycle_stages)),
+        ("applicant_review", boxed(applicant_review)),
+        ("flags", boxed(flags)),
+        ("stats", boxed(stats)),
+        ("live", boxed(live)),
+    ]
+}
 
-    register
-        .or(forgot_token)
-        .or(submit)
-        .or(health)
-        .or(get_challenge)
-        .or(get_applicants)
-        .or(get_applicant)
-        .recover(handle_rejection)
+fn boxed<F, R>(route: F) -> BoxedFilter<(Response,)>
+where
+    F: Filter<Extract = (R,), Error = Rejection> + Clone + Send + Sync + 'static,
+    R: Reply,
+{
+    route.map(|reply: R| reply.into_response()).boxed()
 }
 
 // This is weird - if I use the WarpResult alias here, it forces me to use the same
 // concrete return types as the other functions that use the WarpResult alias
 // def because using `impl trait` syntax in aliases is experimental and on nightly
 // should switch away from nightly - it'll make deployment more stable as well
+#[utoipa::path(
+    get,
+    path = "/applicant/{nuid}",
+    params(("nuid" = String, Path, description = "NUID of the applicant")),
+    responses(
+        (status = 200, description = "The applicant's latest submission", body = Applicant),
+        (status = 404, description = "No submissions from this applicant", body = ErrorResponse),
+    )
+)]
 pub async fn handle_get_applicant(nuid: String, p: PgPool) -> Result<impl Reply, Rejection> {
     // look up the applicant
     info!("Fetching applicant: {}", nuid);
-    match get_applicants(p, &[nuid.clone()]).await {
+    match get_applicants(p, std::slice::from_ref(&nuid)).await {
         Ok(applicant) => {
             let code;
             if applicant.len() == 1 {
@@ -90,7 +342,11 @@ pub async fn handle_get_applicant(nuid: String, p: PgPool) -> Result<impl Reply,
                 }))
             } else {
                 let code = StatusCode::INTERNAL_SERVER_ERROR;
-                let msg = api_err!("Fetched the wrong number of applicants somehow ¯\\_(ツ)_/¯  ");
+                let msg = api_err!(
+                    code,
+                    codes::INTERNAL_ERROR,
+                    "Fetched the wrong number of applicants somehow ¯\\_(ツ)_/¯  "
+                );
                 Ok(reply::with_status(reply::json(&msg), code))
             }
         }
@@ -103,6 +359,15 @@ pub async fn handle_get_applicant(nuid: String, p: PgPool) -> Result<impl Reply,
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/applicants",
+    request_body(content = Vec<String>, description = "NUIDs of the applicants to fetch"),
+    responses(
+        (status = 200, description = "The latest submission from each applicant", body = Vec<Applicant>),
+        (status = 404, description = "Some of the applicants weren't found", body = ErrorResponse),
+    )
+)]
 pub async fn handle_get_applicants(nuids: Vec<String>, p: PgPool) -> Result<impl Reply, Rejection> {
     info!("Fetching applicants: {:#?}", nuids);
     match get_applicants(p, &nuids).await {
@@ -132,13 +397,46 @@ pub async fn handle_get_applicants(nuids: Vec<String>, p: PgPool) -> Result<impl
     }
 }
 
-pub async fn handle_register(request: RegisterRequest, p: PgPool) -> Result<impl Reply, Rejection> {
+#[utoipa::path(
+    post,
+    path = "/register",
+    request_body = RegisterRequest,
+    responses(
+        (status = 200, description = "Registered - keep the token safe", body = RegisterResponse),
+        (status = 409, description = "This NUID has already registered", body = ErrorResponse),
+        (status = 422, description = "One or more fields failed validation", body = ErrorResponse),
+    )
+)]
+pub async fn handle_register(
+    request: RegisterRequest,
+    p: PgPool,
+    token_lifetime: Option<chrono::Duration>,
+    ctx: RequestContext,
+) -> Result<impl Reply, Rejection> {
     info!(
         "registering user {}, with nuid {}",
         request.name, request.nuid
     );
 
-    match register_user(p, request.name, request.nuid).await {
+    let request = match validate_registration(request) {
+        Ok(request) => request,
+        Err(e) => {
+            info!("Registration failed validation: {:?}", e);
+            return Err(reject::custom(e));
+        }
+    };
+
+    match register_user(
+        p,
+        request.name,
+        request.nuid,
+        request.email,
+        request.display_name,
+        token_lifetime,
+        &ctx,
+    )
+    .await
+    {
         Ok((token, challenge_string)) => Ok(reply::json(&RegisterResponse {
             token: token.to_string(),
             challenge_string,
@@ -152,19 +450,50 @@ pub async fn handle_register(request: RegisterRequest, p: PgPool) -> Result<impl
 }
 
 // On error, send back a 400
+#[utoipa::path(
+    post,
+    path = "/submit/{token}",
+    params(
+        ("token" = Uuid, Path, description = "Token handed out at registration"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Any unique string, up to 255 characters. Retrying with the same key returns the original result instead of submitting again"),
+    ),
+    request_body(content = Answer, description = "The answer to the stage you're on, shaped as its `task` says. Every kind of stage takes every format: JSON, k-mer counts as a list of `[kmer, count]` pairs, `text/tab-separated-values` with a k-mer and count or a single value on each line, and `application/msgpack`"),
+    responses(
+        (status = 200, description = "The solution is correct, and says if it unlocked the next stage", body = String, content_type = "application/json"),
+        (status = 400, description = "The solution is incorrect", body = ErrorResponse),
+        (status = 401, description = "The token was revoked or has expired", body = ErrorResponse),
+        (status = 404, description = "No applicant has this token", body = ErrorResponse),
+        (status = 415, description = "Answers can't be sent with this Content-Type", body = ErrorResponse),
+        (status = 422, description = "The Idempotency-Key is invalid, or was used for a different solution", body = ErrorResponse),
+    )
+)]
 pub async fn handle_submit(
     token: Uuid,
-    soln: HashMap<String, u64>,
+    idempotency_key: Option<String>,
+    soln: Answer,
     p: PgPool,
+    ctx: RequestContext,
 ) -> Result<impl Reply, Rejection> {
     info!(
         "Receiving submission from user with token: {:?}\nsubmission: {:#?}",
         token, soln
     );
+    let idempotency_key = match validate_idempotency_key(idempotency_key) {
+        Ok(key) => key,
+        Err(e) => {
+            info!("Submission failed validation: {:?}", e);
+            return Err(reject::custom(e));
+        }
+    };
     // Depending on what check solution does, either return a reply json or a rejection
-    match check_solution(p, token, &soln).await {
-        Ok(is_correct) => {
-            if is_correct {
+    match check_solution(p, token, &soln, idempotency_key, &ctx).await {
+        Ok(graded) => {
+            if let Some(next) = graded.unlocked() {
+                Ok(reply::json(&format!(
+                    "Correct! Stage {} of {} is unlocked at /challenge",
+                    next, graded.stages
+                )))
+            } else if graded.ok {
                 Ok(reply::json(&"Correct! Nice work".to_string()))
             } else {
                 Err(reject::custom(ModelError::IncorrectSolution {
@@ -179,31 +508,244 @@ pub async fn handle_submit(
     }
 }
 
-pub async fn handle_forgot_token(nuid: String, p: PgPool) -> Result<impl Reply, Rejection> {
-    info!("Fetching token for user: {}", nuid);
-    match retreive_token(p, &nuid).await {
-        Ok(token) => Ok(reply::json(&HandleForgotTokenResponse {
-            token: token.to_string(),
-        })),
+#[utoipa::path(
+    post,
+    path = "/batch/{token}",
+    params(("token" = Uuid, Path, description = "Token handed out at registration")),
+    responses(
+        (status = 200, description = "Fresh challenge strings to answer before the deadline", body = BatchResponse),
+        (status = 401, description = "The token was revoked or has expired", body = ErrorResponse),
+        (status = 404, description = "No applicant has this token", body = ErrorResponse),
+        (status = 409, description = "The stage you're on isn't answered in batches", body = ErrorResponse),
+    )
+)]
+pub async fn handle_start_batch(token: Uuid, p: PgPool) -> Result<impl Reply, Rejection> {
+    info!("Starting a batch for user with token: {:?}", token);
+    match batches::start_batch(&p, token).await {
+        Ok(batch) => Ok(reply::json(&batch)),
+        Err(e) => {
+            error!("Starting a batch failed: {:?}", e);
+            Err(reject::custom(e))
+        }
+    }
+}
+
+// On error, send back a 400 with how many were right
+#[utoipa::path(
+    post,
+    path = "/batch/{token}/{batch_id}",
+    params(
+        ("token" = Uuid, Path, description = "Token handed out at registration"),
+        ("batch_id" = Uuid, Path, description = "The batch being answered"),
+    ),
+    request_body(content = Vec<Answer>, description = "An answer for each of the batch's challenge strings, in the same order. Takes the same formats as /submit - in `text/tab-separated-values`, put a blank line between answers"),
+    responses(
+        (status = 200, description = "Every answer is correct, and says if it unlocked the next stage", body = String, content_type = "application/json"),
+        (status = 400, description = "Some answers are incorrect", body = ErrorResponse),
+        (status = 401, description = "The token was revoked or has expired", body = ErrorResponse),
+        (status = 404, description = "No batch with this id for the stage you're on", body = ErrorResponse),
+        (status = 409, description = "The batch was already answered", body = ErrorResponse),
+        (status = 410, description = "The batch's deadline has passed", body = ErrorResponse),
+        (status = 415, description = "Answers can't be sent with this Content-Type", body = ErrorResponse),
+    )
+)]
+pub async fn handle_answer_batch(
+    token: Uuid,
+    batch_id: Uuid,
+    answers: Vec<Answer>,
+    p: PgPool,
+    ctx: RequestContext,
+) -> Result<impl Reply, Rejection> {
+    info!(
+        "Receiving {} answers to batch {} from user with token: {:?}",
+        answers.len(),
+        batch_id,
+        token
+    );
+    match batches::answer_batch(p, token, batch_id, &answers, &ctx).await {
+        Ok(batch) => {
+            if let Some(next) = batch.graded.unlocked() {
+                Ok(reply::json(&format!(
+                    "Correct! Stage {} of {} is unlocked at /challenge",
+                    next, batch.graded.stages
+                )))
+            } else if batch.graded.ok {
+                Ok(reply::json(&"Correct! Nice work".to_string()))
+            } else {
+                Err(reject::custom(ModelError::IncorrectBatch {
+                    correct: batch.correct,
+                    total: batch.total,
+                }))
+            }
+        }
+        Err(e) => {
+            error!("Answering a batch failed: {:?}", e);
+            Err(reject::custom(e))
+        }
+    }
+}
+
+// A program that fails some tests is still a 200 - the response says which
+#[utoipa::path(
+    post,
+    path = "/run/{token}",
+    params(("token" = Uuid, Path, description = "Token handed out at registration")),
+    request_body(content = ProgramSubmission, description = "A program for the stage you're on, which reads its challenge string on stdin and prints the answer as JSON"),
+    responses(
+        (status = 200, description = "How the program did on each hidden test, and if it unlocked the next stage", body = RunResponse),
+        (status = 401, description = "The token was revoked or has expired", body = ErrorResponse),
+        (status = 404, description = "No applicant has this token", body = ErrorResponse),
+        (status = 409, description = "The stage you're on takes an answer at /submit", body = ErrorResponse),
+        (status = 422, description = "The source is empty or too long", body = ErrorResponse),
+        (status = 429, description = "Another of your programs is still running - wait for it to finish", body = ErrorResponse),
+        (status = 503, description = "Programs can't be run on this server", body = ErrorResponse),
+    )
+)]
+pub async fn handle_run_program(
+    token: Uuid,
+    program: ProgramSubmission,
+    p: PgPool,
+    sandbox: Option<Sandbox>,
+    running: Running,
+    ctx: RequestContext,
+) -> Result<impl Reply, Rejection> {
+    info!(
+        "Receiving a {} program from user with token: {:?}",
+        program.language.as_str(),
+        token
+    );
+    let sandbox = match sandbox {
+        Some(sandbox) => sandbox,
+        None => return Err(reject::custom(ModelError::SandboxUnavailable)),
+    };
+    let program = match validate_program(program) {
+        Ok(program) => program,
+        Err(e) => {
+            info!("Program failed validation: {:?}", e);
+            return Err(reject::custom(e));
+        }
+    };
+    match programs::run_program(p, &sandbox, &running, token, &program, &ctx).await {
+        Ok(run) => {
+            let msg = if run.compiler_output.is_some() {
+                "Your program didn't compile".to_string()
+            } else if let Some(next) = run.graded.unlocked() {
+                format!(
+                    "Passed! Stage {} of {} is unlocked at /challenge",
+                    next, run.graded.stages
+                )
+            } else if run.graded.ok {
+                "Passed! Nice work".to_string()
+            } else {
+                "Your program didn't pass every test".to_string()
+            };
+            Ok(reply::json(&RunResponse {
+                ok: run.graded.ok,
+                msg,
+                compiler_output: run.compiler_output,
+                tests: run.tests,
+            }))
+        }
+        Err(e) => {
+            error!("Running a program failed: {:?}", e);
+            Err(reject::custom(e))
+        }
+    }
+}
+
+// Unlocks the next hint every time it's called, so it's not free to refresh
+#[utoipa::path(
+    get,
+    path = "/hint/{token}",
+    params(("token" = Uuid, Path, description = "Token handed out at registration")),
+    responses(
+        (status = 200, description = "Every hint unlocked so far for the stage you're on, including the one this unlocked", body = HintResponse),
+        (status = 401, description = "The token was revoked or has expired", body = ErrorResponse),
+        (status = 404, description = "No applicant has this token", body = ErrorResponse),
+    )
+)]
+pub async fn handle_hint(
+    token: Uuid,
+    p: PgPool,
+    penalty: i32,
+    ctx: RequestContext,
+) -> Result<impl Reply, Rejection> {
+    info!("Unlocking a hint for user with token: {:?}", token);
+    match hints::unlock_hint(p, token, penalty, &ctx).await {
+        Ok(hints) => Ok(reply::json(&hints)),
         Err(e) => {
-            error!("Fetching token failed for user {}: {:?}", nuid, e);
+            error!("Unlocking a hint failed: {:?}", e);
             Err(reject::custom(e))
         }
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/forgot_token/{nuid}",
+    params(("nuid" = String, Path, description = "NUID used to register")),
+    responses(
+        (status = 202, description = "The token is emailed to the address this NUID registered with, if there is one", body = HandleForgotTokenResponse),
+        (status = 503, description = "Email isn't set up, so there's nowhere to send it", body = ErrorResponse),
+    )
+)]
+pub async fn handle_forgot_token(
+    nuid: String,
+    p: PgPool,
+    email_enabled: bool,
+    ctx: RequestContext,
+) -> Result<impl Reply, Rejection> {
+    info!("Emailing the token for user: {}", nuid);
+    if !email_enabled {
+        return Err(reject::custom(ModelError::EmailDisabled));
+    }
+    match send_token(&p, &nuid, &ctx).await {
+        Ok(()) => Ok(reply::with_status(
+            reply::json(&HandleForgotTokenResponse {
+                msg: "If this NUID registered with an email address, its token is on the way there"
+                    .into(),
+            }),
+            StatusCode::ACCEPTED,
+        )),
+        Err(e) => {
+            error!("Emailing the token failed for user {}: {:?}", nuid, e);
+            Err(reject::custom(e))
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/health",
+    responses(
+        (status = 200, description = "The server is up", body = Object, example = json!({"healthy": true})),
+    )
+)]
 pub async fn health_check() -> Result<impl Reply, Rejection> {
     Ok(reply::json(&json!({
         "healthy": true
     })))
 }
 
+#[utoipa::path(
+    get,
+    path = "/challenge/{token}",
+    params(("token" = Uuid, Path, description = "Token handed out at registration")),
+    responses(
+        (status = 200, description = "The challenge string for the stage the applicant is on", body = GetChallengeString),
+        (status = 401, description = "The token was revoked or has expired", body = ErrorResponse),
+        (status = 404, description = "No applicant has this token", body = ErrorResponse),
+    )
+)]
 pub async fn handle_get_challenge(token: Uuid, pool: PgPool) -> Result<impl Reply, Rejection> {
     info!("Fetching challenge string for user with token: {}", token);
     match retreive_challenge(&pool, token).await {
-        Ok(challenge_string) => {
-            info!("Challenge string: {}", challenge_string);
-            Ok(reply::json(&GetChallengeString { challenge_string }))
+        Ok(challenge) => {
+            info!(
+                "Challenge string for stage {}: {}",
+                challenge.stage, challenge.challenge_string
+            );
+            Ok(reply::json(&challenge))
         }
         Err(e) => {
             error!("Fetching challenge_string failed {:?}", e);
@@ -212,24 +754,549 @@ pub async fn handle_get_challenge(token: Uuid, pool: PgPool) -> Result<impl Repl
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/leaderboard",
+    params(LeaderboardQuery),
+    responses(
+        (status = 200, description = "Applicants who chose a display name, by how long they took to solve it", body = Leaderboard),
+        (status = 404, description = "No cycle with this name exists", body = ErrorResponse),
+    )
+)]
+pub async fn handle_get_leaderboard(
+    query: LeaderboardQuery,
+    pool: PgPool,
+    leaderboards: Leaderboards,
+) -> Result<impl Reply, Rejection> {
+    match leaderboards.get(&pool, query.cycle.as_deref()).await {
+        Ok(leaderboard) => Ok(reply::json(&leaderboard)),
+        Err(e) => {
+            error!("Fetching the leaderboard failed: {:?}", e);
+            Err(reject::custom(e))
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/webhooks/deliveries",
+    params(DeliveryQuery),
+    responses(
+        (status = 200, description = "The most recent webhook deliveries, newest first", body = Vec<Delivery>),
+        (status = 401, description = "Missing or incorrect admin token", body = ErrorResponse),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn handle_get_webhook_deliveries(
+    query: DeliveryQuery,
+    pool: PgPool,
+) -> Result<impl Reply, Rejection> {
+    let limit = query.limit.unwrap_or(50).clamp(1, 500);
+    info!("Fetching {} webhook deliveries", limit);
+    match webhooks::get_deliveries(&pool, query.status, limit).await {
+        Ok(deliveries) => Ok(reply::json(&deliveries)),
+        Err(e) => {
+            error!("Fetching webhook deliveries failed: {:?}", e);
+            Err(reject::custom(e))
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/jobs",
+    params(JobQuery),
+    responses(
+        (status = 200, description = "The most recent background jobs, newest first", body = Vec<JobRecord>),
+        (status = 401, description = "Missing or incorrect admin token", body = ErrorResponse),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn handle_get_jobs(query: JobQuery, pool: PgPool) -> Result<impl Reply, Rejection> {
+    let limit = query.limit.unwrap_or(50).clamp(1, 500);
+    info!("Fetching {} jobs", limit);
+    match jobs::get_jobs(&pool, query.status, query.kind, limit).await {
+        Ok(jobs) => Ok(reply::json(&jobs)),
+        Err(e) => {
+            error!("Fetching jobs failed: {:?}", e);
+            Err(reject::custom(e))
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/jobs/{job_id}/retry",
+    params(("job_id" = i64, Path, description = "A dead job")),
+    responses(
+        (status = 200, description = "The job will be picked up again", body = String, content_type = "application/json"),
+        (status = 401, description = "Missing or incorrect admin token", body = ErrorResponse),
+        (status = 404, description = "No dead job has this id", body = ErrorResponse),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn handle_retry_job(
+    job_id: i64,
+    pool: PgPool,
+    ctx: RequestContext,
+) -> Result<impl Reply, Rejection> {
+    info!("Retrying job {}", job_id);
+    match jobs::retry_job(&pool, job_id, &ctx).await {
+        Ok(()) => Ok(reply::json(&"Queued for another run".to_string())),
+        Err(e) => {
+            error!("Retrying job {} failed: {:?}", job_id, e);
+            Err(reject::custom(e))
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/rotate_token/{token}",
+    params(("token" = Uuid, Path, description = "The token to replace")),
+    responses(
+        (status = 200, description = "The new token - the old one stops working", body = RotateTokenResponse),
+        (status = 401, description = "The token was already revoked or has expired", body = ErrorResponse),
+        (status = 404, description = "No applicant has this token", body = ErrorResponse),
+    )
+)]
+pub async fn handle_rotate_token(
+    token: Uuid,
+    pool: PgPool,
+    token_lifetime: Option<chrono::Duration>,
+    ctx: RequestContext,
+) -> Result<impl Reply, Rejection> {
+    match rotate_token(&pool, token, token_lifetime, &ctx).await {
+        Ok(token) => Ok(reply::json(&RotateTokenResponse {
+            token: token.to_string(),
+        })),
+        Err(e) => {
+            error!("Rotating a token failed: {:?}", e);
+            Err(reject::custom(e))
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/applicants/{nuid}/rotate_token",
+    params(("nuid" = String, Path, description = "NUID of the applicant")),
+    responses(
+        (status = 200, description = "The applicant's new token - the old one stops working", body = RotateTokenResponse),
+        (status = 401, description = "Missing or incorrect admin token", body = ErrorResponse),
+        (status = 404, description = "No applicant has this NUID", body = ErrorResponse),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn handle_admin_rotate_token(
+    nuid: String,
+    pool: PgPool,
+    token_lifetime: Option<chrono::Duration>,
+    ctx: RequestContext,
+) -> Result<impl Reply, Rejection> {
+    match rotate_token_for(&pool, &nuid, token_lifetime, &ctx).await {
+        Ok(token) => Ok(reply::json(&RotateTokenResponse {
+            token: token.to_string(),
+        })),
+        Err(e) => {
+            error!("Rotating the token for {} failed: {:?}", nuid, e);
+            Err(reject::custom(e))
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/applicants/{nuid}/rotations",
+    params(("nuid" = String, Path, description = "NUID of the applicant")),
+    responses(
+        (status = 200, description = "Every token the applicant has had replaced, newest first", body = Vec<TokenRotation>),
+        (status = 401, description = "Missing or incorrect admin token", body = ErrorResponse),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn handle_get_token_rotations(
+    nuid: String,
+    pool: PgPool,
+) -> Result<impl Reply, Rejection> {
+    match get_rotations(&pool, &nuid).await {
+        Ok(rotations) => Ok(reply::json(&rotations)),
+        Err(e) => {
+            error!("Fetching token rotations for {} failed: {:?}", nuid, e);
+            Err(reject::custom(e))
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/audit",
+    params(AuditQuery),
+    responses(
+        (status = 200, description = "The most recent audit events, newest first", body = Vec<AuditEvent>),
+        (status = 401, description = "Missing or incorrect admin token", body = ErrorResponse),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn handle_get_audit_events(
+    query: AuditQuery,
+    pool: PgPool,
+) -> Result<impl Reply, Rejection> {
+    match audit::get_events(&pool, query).await {
+        Ok(events) => Ok(reply::json(&events)),
+        Err(e) => {
+            error!("Fetching audit events failed: {:?}", e);
+            Err(reject::custom(e))
+        }
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/my_data/{token}",
+    params(("token" = Uuid, Path, description = "Token handed out at registration")),
+    responses(
+        (status = 200, description = "Your registration and submissions are gone, and the token no longer works", body = String, content_type = "application/json"),
+        (status = 401, description = "The token was revoked or has expired", body = ErrorResponse),
+        (status = 404, description = "No applicant has this token", body = ErrorResponse),
+    )
+)]
+pub async fn handle_delete_my_data(
+    token: Uuid,
+    pool: PgPool,
+    ctx: RequestContext,
+) -> Result<impl Reply, Rejection> {
+    match retention::delete_my_data(&pool, token, &ctx).await {
+        Ok(_) => Ok(reply::json(&"Your data has been deleted".to_string())),
+        Err(e) => {
+            error!("Deleting an applicant's data failed: {:?}", e);
+            Err(reject::custom(e))
+        }
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/admin/applicants/{nuid}",
+    params(("nuid" = String, Path, description = "NUID of the applicant")),
+    responses(
+        (status = 200, description = "The applicant and their submissions are gone", body = String, content_type = "application/json"),
+        (status = 401, description = "Missing or incorrect admin token", body = ErrorResponse),
+        (status = 404, description = "No applicant has this NUID", body = ErrorResponse),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn handle_delete_applicant(
+    nuid: String,
+    pool: PgPool,
+    ctx: RequestContext,
+) -> Result<impl Reply, Rejection> {
+    match retention::delete_applicant(&pool, &nuid, &ctx).await {
+        Ok(_) => Ok(reply::json(&"Deleted".to_string())),
+        Err(e) => {
+            error!("Deleting {} failed: {:?}", nuid, e);
+            Err(reject::custom(e))
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/cycles",
+    responses(
+        (status = 200, description = "Every cycle and its retention policy, newest first", body = Vec<Cycle>),
+        (status = 401, description = "Missing or incorrect admin token", body = ErrorResponse),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn handle_get_cycles(pool: PgPool) -> Result<impl Reply, Rejection> {
+    match cycles::get_cycles(&pool).await {
+        Ok(cycles) => Ok(reply::json(&cycles)),
+        Err(e) => {
+            error!("Fetching cycles failed: {:?}", e);
+            Err(reject::custom(e))
+        }
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/admin/cycles/{cycle}/retention",
+    params(("cycle" = String, Path, description = "Name of the cycle")),
+    request_body = RetentionPolicy,
+    responses(
+        (status = 200, description = "Applied from the next purge on", body = String, content_type = "application/json"),
+        (status = 401, description = "Missing or incorrect admin token", body = ErrorResponse),
+        (status = 404, description = "No cycle has this name", body = ErrorResponse),
+        (status = 422, description = "The policy failed validation", body = ErrorResponse),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn handle_set_retention(
+    cycle: String,
+    policy: RetentionPolicy,
+    pool: PgPool,
+    ctx: RequestContext,
+) -> Result<impl Reply, Rejection> {
+    let policy = match validate_retention(policy) {
+        Ok(policy) => policy,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    match cycles::set_retention(&pool, &cycle, &policy, &ctx).await {
+        Ok(()) => Ok(reply::json(&"Retention policy updated".to_string())),
+        Err(e) => {
+            error!("Setting the retention policy for {} failed: {:?}", cycle, e);
+            Err(reject::custom(e))
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/cycles/{cycle}/stages",
+    params(("cycle" = String, Path, description = "Name of the cycle")),
+    responses(
+        (status = 200, description = "The cycle's stages, in the order they unlock", body = Stages),
+        (status = 401, description = "Missing or incorrect admin token", body = ErrorResponse),
+        (status = 404, description = "No cycle has this name", body = ErrorResponse),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn handle_get_stages(cycle: String, pool: PgPool) -> Result<impl Reply, Rejection> {
+    match stages::get_cycle_stages(&pool, &cycle).await {
+        Ok(stages) => Ok(reply::json(&stages)),
+        Err(e) => {
+            error!("Fetching the stages for {} failed: {:?}", cycle, e);
+            Err(reject::custom(e))
+        }
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/admin/cycles/{cycle}/stages",
+    params(("cycle" = String, Path, description = "Name of the cycle")),
+    request_body = Stages,
+    responses(
+        (status = 200, description = "Unlocked from now on - applicants keep the stages they already have", body = String, content_type = "application/json"),
+        (status = 401, description = "Missing or incorrect admin token", body = ErrorResponse),
+        (status = 404, description = "No cycle has this name", body = ErrorResponse),
+        (status = 422, description = "The stages failed validation", body = ErrorResponse),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn handle_set_stages(
+    cycle: String,
+    stages: Stages,
+    pool: PgPool,
+    ctx: RequestContext,
+) -> Result<impl Reply, Rejection> {
+    let stages = match validate_stages(stages) {
+        Ok(stages) => stages,
+        Err(e) => return Err(reject::custom(e)),
+    };
+    match stages::set_stages(&pool, &cycle, &stages.stages, &ctx).await {
+        Ok(()) => Ok(reply::json(&"Stages updated".to_string())),
+        Err(e) => {
+            error!("Setting the stages for {} failed: {:?}", cycle, e);
+            Err(reject::custom(e))
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/applicants/{nuid}",
+    params(("nuid" = String, Path, description = "NUID of the applicant")),
+    responses(
+        (status = 200, description = "The applicant, and anything suspicious about their submissions", body = ApplicantReview),
+        (status = 401, description = "Missing or incorrect admin token", body = ErrorResponse),
+        (status = 404, description = "No applicant has this NUID", body = ErrorResponse),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn handle_get_applicant_review(
+    nuid: String,
+    pool: PgPool,
+    settings: AnalysisSettings,
+) -> Result<impl Reply, Rejection> {
+    match analysis::review(&pool, &settings, &nuid).await {
+        Ok(review) => Ok(reply::json(&review)),
+        Err(e) => {
+            error!("Reviewing {} failed: {:?}", nuid, e);
+            Err(reject::custom(e))
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/flags",
+    params(FlagQuery),
+    responses(
+        (status = 200, description = "Every applicant with something suspicious about their submissions", body = Vec<FlaggedApplicant>),
+        (status = 401, description = "Missing or incorrect admin token", body = ErrorResponse),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn handle_get_flags(
+    query: FlagQuery,
+    pool: PgPool,
+    settings: AnalysisSettings,
+) -> Result<impl Reply, Rejection> {
+    match analysis::flagged(&pool, &settings, query.cycle.as_deref()).await {
+        Ok(flagged) => Ok(reply::json(&flagged)),
+        Err(e) => {
+            error!("Fetching flagged applicants failed: {:?}", e);
+            Err(reject::custom(e))
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/stats",
+    params(StatsQuery),
+    responses(
+        (status = 200, description = "Registrations, the funnel from registering to solving, and how long and how many attempts solving took", body = CycleStats),
+        (status = 401, description = "Missing or incorrect admin token", body = ErrorResponse),
+        (status = 404, description = "No cycle with this name exists", body = ErrorResponse),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn handle_get_stats(query: StatsQuery, pool: PgPool) -> Result<impl Reply, Rejection> {
+    match stats::summarize(&pool, query.cycle.as_deref()).await {
+        Ok(stats) => Ok(reply::json(&stats)),
+        Err(e) => {
+            error!("Fetching stats failed: {:?}", e);
+            Err(reject::custom(e))
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/live",
+    responses(
+        (status = 200, description = "Server-sent events as applicants register, submit and solve, named by kind. A `lagged` event carries how many were missed by a client that fell behind. Browsers can't send the admin token with an EventSource, so the admin pages serve the same feed at /admin/ui/live/events to a logged-in session", body = LiveEvent, content_type = "text/event-stream"),
+        (status = 401, description = "Missing or incorrect admin token", body = ErrorResponse),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn handle_live(live: Live) -> Result<impl Reply, Rejection> {
+    info!("Someone's watching the live feed");
+    let events = live::stream(live.subscribe());
+    Ok(warp::sse::reply(warp::sse::keep_alive().stream(events)))
+}
+
+// Errors from routes that exist are deprecated along with them, but a path we
+// don't serve at all isn't deprecated - it's just not there
+async fn handle_deprecated_rejection(err: Rejection) -> Result<Response, Infallible> {
+    let (code, msg) = error_response(&err);
+    let mut res = reply::with_status(reply::json(&msg), code).into_response();
+    if msg.code != codes::NOT_FOUND {
+        res.headers_mut()
+            .insert("Deprecation", HeaderValue::from_static("true"));
+    }
+    Ok(res)
+}
+
 async fn handle_rejection(err: Rejection) -> Result<impl Reply, Infallible> {
+    let (code, msg) = error_response(&err);
+    Ok(reply::with_status(reply::json(&msg), code))
+}
+
+fn error_response(err: &Rejection) -> (StatusCode, ErrorResponse<'_>) {
     let code;
     let msg: ErrorResponse;
 
     if let Some(wrapped_err) = err.find::<ModelError>() {
+        let error_code = wrapped_err.code();
         match wrapped_err {
             ModelError::DuplicateUser => {
-                msg = api_err!("This NUID has already been used to register");
                 code = StatusCode::CONFLICT;
+                msg = api_err!(
+                    code,
+                    error_code,
+                    "This NUID has already been used to register"
+                );
             }
             ModelError::IncorrectSolution { given_solution } => {
+                code = StatusCode::BAD_REQUEST;
                 msg = api_err!(
+                    code,
+                    error_code,
                     "Incorrect solution",
                     ApiError::IncorrectSolution {
                         given_solution: given_solution.clone()
                     }
                 );
+            }
+            ModelError::IncorrectBatch { correct, total } => {
+                code = StatusCode::BAD_REQUEST;
+                msg = api_err!(
+                    code,
+                    error_code,
+                    format!("{} of {} answers were correct", correct, total),
+                    ApiError::IncorrectBatch {
+                        correct: *correct,
+                        total: *total
+                    }
+                );
+            }
+            ModelError::UnsupportedMediaType { content_type } => {
+                code = StatusCode::UNSUPPORTED_MEDIA_TYPE;
+                msg = api_err!(
+                    code,
+                    error_code,
+                    format!(
+                        "Answers can't be sent as {} - use {}, {} or {}",
+                        content_type,
+                        formats::JSON,
+                        formats::TSV,
+                        formats::MSGPACK
+                    )
+                )
+            }
+            ModelError::MalformedAnswer { reason } => {
                 code = StatusCode::BAD_REQUEST;
+                msg = api_err!(
+                    code,
+                    error_code,
+                    format!("Couldn't read your answer - {}", reason)
+                )
+            }
+            ModelError::BatchRequired => {
+                code = StatusCode::CONFLICT;
+                msg = api_err!(
+                    code,
+                    error_code,
+                    "This stage is answered in batches - start one at /batch instead"
+                )
+            }
+            ModelError::BatchNotFound => {
+                code = StatusCode::NOT_FOUND;
+                msg = api_err!(
+                    code,
+                    error_code,
+                    "No batch with this id for the stage you're on - start a new one at /batch"
+                )
+            }
+            ModelError::BatchExpired => {
+                code = StatusCode::GONE;
+                msg = api_err!(
+                    code,
+                    error_code,
+                    "This batch's time is up - start a new one at /batch"
+                )
+            }
+            ModelError::BatchAnswered => {
+                code = StatusCode::CONFLICT;
+                msg = api_err!(
+                    code,
+                    error_code,
+                    "This batch was already answered - start a new one at /batch"
+                )
             }
             ModelError::ApplicantsNotFound {
                 applicants_found,
@@ -237,6 +1304,8 @@ async fn handle_rejection(err: Rejection) -> Result<impl Reply, Infallible> {
             } => {
                 code = StatusCode::NOT_FOUND;
                 msg = api_err!(
+                    code,
+                    error_code,
                     "Submissions from one or more of the applicants requested was not found",
                     ApiError::ApplicantsNotFound {
                         applicants_found: applicants_found.to_vec(),
@@ -246,17 +1315,119 @@ async fn handle_rejection(err: Rejection) -> Result<impl Reply, Infallible> {
             }
             ModelError::SqlError => {
                 code = StatusCode::INTERNAL_SERVER_ERROR;
-                msg = api_err!("Something went wrong on our side - email me at bhat.am@northeastern.edu if this happens");
+                msg = api_err!(code, error_code, "Something went wrong on our side - email me at bhat.am@northeastern.edu if this happens");
                 warn!("{:?}", err)
             }
             ModelError::NoUserFound => {
                 code = StatusCode::NOT_FOUND;
-                msg = api_err!("No user with this token or nuid exists")
+                msg = api_err!(code, error_code, "No user with this token or nuid exists")
+            }
+            ModelError::JobNotFound => {
+                code = StatusCode::NOT_FOUND;
+                msg = api_err!(code, error_code, "No dead job with this id exists")
+            }
+            ModelError::IdempotencyKeyReused => {
+                code = StatusCode::UNPROCESSABLE_ENTITY;
+                msg = api_err!(
+                    code,
+                    error_code,
+                    "This Idempotency-Key was already used with a different solution - use a new key for a new submission"
+                )
+            }
+            ModelError::TokenRevoked => {
+                code = StatusCode::UNAUTHORIZED;
+                msg = api_err!(
+                    code,
+                    error_code,
+                    "This token has been replaced by a newer one - forgot_token will email you the current one"
+                )
+            }
+            ModelError::EmailDisabled => {
+                code = StatusCode::SERVICE_UNAVAILABLE;
+                msg = api_err!(
+                    code,
+                    error_code,
+                    "Tokens can only be sent by email, which isn't set up - ask an admin to rotate yours"
+                )
+            }
+            ModelError::TokenExpired => {
+                code = StatusCode::UNAUTHORIZED;
+                msg = api_err!(
+                    code,
+                    error_code,
+                    "This token has expired - get in touch with us for a new one"
+                )
+            }
+            ModelError::CycleNotFound => {
+                code = StatusCode::NOT_FOUND;
+                msg = api_err!(code, error_code, "No cycle with this name exists")
+            }
+            ModelError::DuplicateCycle => {
+                code = StatusCode::CONFLICT;
+                msg = api_err!(code, error_code, "A cycle with this name already exists")
+            }
+            ModelError::ProgramRequired => {
+                code = StatusCode::CONFLICT;
+                msg = api_err!(
+                    code,
+                    error_code,
+                    "This stage takes a program - upload it to /run instead"
+                )
+            }
+            ModelError::AnswerRequired => {
+                code = StatusCode::CONFLICT;
+                msg = api_err!(
+                    code,
+                    error_code,
+                    "This stage takes an answer - send it to /submit instead"
+                )
+            }
+            ModelError::SandboxFailed => {
+                code = StatusCode::INTERNAL_SERVER_ERROR;
+                msg = api_err!(code, error_code, "Couldn't run your program on our side - email me at bhat.am@northeastern.edu if this happens");
+            }
+            ModelError::RunInProgress => {
+                code = StatusCode::TOO_MANY_REQUESTS;
+                msg = api_err!(
+                    code,
+                    error_code,
+                    "Your last program is still running - wait for it to finish before sending another"
+                )
+            }
+            ModelError::SandboxUnavailable => {
+                code = StatusCode::SERVICE_UNAVAILABLE;
+                msg = api_err!(code, error_code, "Programs can't be run on this server right now - email me at bhat.am@northeastern.edu if this keeps happening");
+            }
+            ModelError::Unauthorized => {
+                code = StatusCode::UNAUTHORIZED;
+                msg = api_err!(code, error_code, "This route needs a valid admin token")
+            }
+            ModelError::ValidationFailed { errors } => {
+                code = StatusCode::UNPROCESSABLE_ENTITY;
+                msg = api_err!(
+                    code,
+                    error_code,
+                    "One or more fields failed validation",
+                    ApiError::ValidationFailed {
+                        errors: errors.clone()
+                    }
+                )
             }
         }
-    } else if err.find::<BodyDeserializeError>().is_some() {
+    } else if err.find::<UnsupportedMediaType>().is_some() {
+        code = StatusCode::UNSUPPORTED_MEDIA_TYPE;
+        msg = api_err!(
+            code,
+            codes::UNSUPPORTED_MEDIA_TYPE,
+            "Send your request body as application/json"
+        )
+    } else if err.find::<BodyDeserializeError>().is_some() || err.find::<InvalidQuery>().is_some() {
         code = StatusCode::BAD_REQUEST;
-        msg = api_err!("Bad request - check your request body")
+        msg = api_err!(
+            code,
+            codes::BAD_REQUEST,
+            "Bad request - check your request body"
+        )
     }
     // This is super jank - we're mapping a 405 to a 404
     // This issue explains why: https://github.com/seanmonstar/warp/issues/77
@@ -264,24 +1435,256 @@ async fn handle_rejection(err: Rejection) -> Result<impl Reply, Infallible> {
     // fucking warp man
     // This shit sucks - for some reason post request are being logged as
     // methodNotAllowed
-    else if err.find::<MethodNotAllowed>().is_some() {
+    else if err.is_not_found() || err.find::<MethodNotAllowed>().is_some() {
         code = StatusCode::NOT_FOUND;
         msg = api_err!(
+            code,
+            codes::NOT_FOUND,
             "The path you're trying to hit doesn't exist - check your endpoints and your request method"
         );
     } else {
         code = StatusCode::INTERNAL_SERVER_ERROR;
-        msg =
-            api_err!("Unhandled rejection - email me at bhat.am@northeastern.edu if this happens");
+        msg = api_err!(
+            code,
+            codes::INTERNAL_ERROR,
+            "Unhandled rejection - email me at bhat.am@northeastern.edu if this happens"
+        );
         warn!("{:?}", err)
     }
 
-    Ok(reply::with_status(reply::json(&msg), code))
+    (code, msg)
 }
 
+// Errors go out as problem+json when the client asks for it, and as plain json
+// otherwise so that older clients don't trip over the content type
+fn negotiate_content_type(headers: HeaderMap, reply: impl Reply) -> Response {
+    let mut res = reply.into_response();
+
+    let wants_problem = headers
+        .get(ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .map(|accept| accept.contains(PROBLEM_JSON))
+        .unwrap_or(false);
+
+    if wants_problem && (res.status().is_client_error() || res.status().is_server_error()) {
+        res.headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_static(PROBLEM_JSON));
+    }
+
+    res
+}
+
+#[cfg(test)]
 mod tests {
-    // We'll write API tests here eventually - i wonder if I can write service tests
-    // using docker compose. Should probably figure out how to do that for generate
+    use serde_json::Value;
+    use sqlx::PgPool;
+
+    use super::{end, PROBLEM_JSON};
+    use crate::config::Settings;
+    use crate::live::Live;
+    use crate::model::leaderboard::Leaderboards;
+
+    // None of these requests make it to the database, so the pool never connects
+    fn pool() -> PgPool {
+        PgPool::connect_lazy("postgres://nobody@localhost:1/nothing").unwrap()
+    }
+
+    fn settings() -> Settings {
+        config::Config::builder()
+            .add_source(config::File::with_name("configuration/base.yaml"))
+            .add_source(config::File::with_name("configuration/local.yaml"))
+            .set_override("admin.token", "admin")
+            .unwrap()
+            .build()
+            .unwrap()
+            .try_deserialize()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_v1_is_not_deprecated() {
+        let res = warp::test::request()
+            .path("/v1/health")
+            .reply(&end(
+                Some(pool()),
+                &settings(),
+                Live::new(),
+                Leaderboards::default(),
+                None,
+            ))
+            .await;
+
+        assert_eq!(res.status(), 200);
+        assert!(res.headers().get("Deprecation").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_unversioned_paths_are_deprecated() {
+        let res = warp::test::request()
+            .path("/health")
+            .reply(&end(
+                Some(pool()),
+                &settings(),
+                Live::new(),
+                Leaderboards::default(),
+                None,
+            ))
+            .await;
+
+        assert_eq!(res.status(), 200);
+        assert_eq!(res.headers()["Deprecation"], "true");
+    }
 
-    // You can just mock the DB, you absolute muppet
+    #[tokio::test]
+    async fn test_only_routes_that_exist_are_deprecated() {
+        let res = warp::test::request()
+            .path("/nowhere")
+            .reply(&end(
+                Some(pool()),
+                &settings(),
+                Live::new(),
+                Leaderboards::default(),
+                None,
+            ))
+            .await;
+        assert_eq!(res.status(), 404);
+        assert!(res.headers().get("Deprecation").is_none());
+
+        let res = warp::test::request()
+            .method("POST")
+            .path("/register")
+            .body("not json")
+            .reply(&end(
+                Some(pool()),
+                &settings(),
+                Live::new(),
+                Leaderboards::default(),
+                None,
+            ))
+            .await;
+        assert_eq!(res.status(), 400);
+        assert_eq!(res.headers()["Deprecation"], "true");
+    }
+
+    #[tokio::test]
+    async fn test_errors_carry_a_code() {
+        let res = warp::test::request()
+            .method("POST")
+            .path("/v1/register")
+            .body("not json")
+            .reply(&end(
+                Some(pool()),
+                &settings(),
+                Live::new(),
+                Leaderboards::default(),
+                None,
+            ))
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+
+        assert_eq!(res.status(), 400);
+        assert_eq!(res.headers()["content-type"], "application/json");
+        assert_eq!(body["code"], "bad_request");
+        assert_eq!(body["status"], 400);
+    }
+
+    #[tokio::test]
+    async fn test_problem_json_when_asked_for() {
+        let res = warp::test::request()
+            .path("/v1/nowhere")
+            .header("accept", PROBLEM_JSON)
+            .reply(&end(
+                Some(pool()),
+                &settings(),
+                Live::new(),
+                Leaderboards::default(),
+                None,
+            ))
+            .await;
+        let body: Value = serde_json::from_slice(res.body()).unwrap();
+
+        assert_eq!(res.status(), 404);
+        assert_eq!(res.headers()["content-type"], PROBLEM_JSON);
+        assert_eq!(body["code"], "not_found");
+        assert_eq!(body["type"], "about:blank");
+        assert_eq!(body["title"], "Not Found");
+    }
+
+    #[tokio::test]
+    async fn test_admin_routes_need_the_token() {
+        for authorization in ["", "Bearer nope", "Bearer admi", "admin"] {
+            let res = warp::test::request()
+                .path("/v1/admin/webhooks/deliveries")
+                .header("authorization", authorization)
+                .reply(&end(
+                    Some(pool()),
+                    &settings(),
+                    Live::new(),
+                    Leaderboards::default(),
+                    None,
+                ))
+                .await;
+            let body: Value = serde_json::from_slice(res.body()).unwrap();
+
+            assert_eq!(res.status(), 401);
+            assert_eq!(body["code"], "unauthorized");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_admin_pages_need_a_session() {
+        for cookie in ["", "generate_admin=nope", "generate_admin=99999999999.00"] {
+            let res = warp::test::request()
+                .path("/admin/ui/applicants")
+                .header("cookie", cookie)
+                .reply(&end(
+                    Some(pool()),
+                    &settings(),
+                    Live::new(),
+                    Leaderboards::default(),
+                    None,
+                ))
+                .await;
+
+            assert_eq!(res.status(), 303);
+            assert_eq!(res.headers()["location"], "/admin/ui/login");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_admin_login() {
+        let login = |token: &'static str| {
+            warp::test::request()
+                .method("POST")
+                .path("/admin/ui/login")
+                .header("content-type", "application/x-www-form-urlencoded")
+                .body(format!("token={}", token))
+        };
+
+        let res = login("nope")
+            .reply(&end(
+                Some(pool()),
+                &settings(),
+                Live::new(),
+                Leaderboards::default(),
+                None,
+            ))
+            .await;
+        assert_eq!(res.status(), 401);
+        assert!(res.headers().get("set-cookie").is_none());
+
+        let res = login("admin")
+            .reply(&end(
+                Some(pool()),
+                &settings(),
+                Live::new(),
+                Leaderboards::default(),
+                None,
+            ))
+            .await;
+        assert_eq!(res.status(), 303);
+        let cookie = res.headers()["set-cookie"].to_str().unwrap();
+        assert!(cookie.starts_with("generate_admin="));
+        assert!(cookie.contains("HttpOnly"));
+    }
 }
@@ -0,0 +1,686 @@
+use std::convert::Infallible;
+use std::sync::OnceLock;
+
+use chrono::{DateTime, Utc};
+use minijinja::{context, Environment, UndefinedBehavior, Value};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::Map;
+use sqlx::PgPool;
+use warp::body::BodyDeserializeError;
+use warp::filters::BoxedFilter;
+use warp::http::header::{LOCATION, SET_COOKIE};
+use warp::hyper::StatusCode;
+use warp::reply::Response;
+use warp::{path, reject, reply, Filter, Rejection, Reply};
+
+use super::errors::{FieldError, ModelError};
+use super::messages::RetentionPolicy;
+use super::routes::{request_context, tokens_match};
+use super::validation::{validate_cycle_name, validate_note, validate_retention};
+use crate::config::{AnalysisSettings, Settings};
+use crate::live::{self, Live};
+use crate::model::applicants::{self, ApplicantFilter, Status, PAGE_SIZE};
+use crate::model::audit::RequestContext;
+use crate::model::retention::RetentionAction;
+use crate::model::{analysis, cycles};
+
+pub mod session;
+
+// Forms are a handful of fields - anything bigger than this isn't one of ours
+const MAX_FORM_BYTES: u64 = 16 * 1024;
+
+const TEMPLATES: [(&str, &str); 7] = [
+    ("layout.html", include_str!("templates/layout.html")),
+    ("login.html", include_str!("templates/login.html")),
+    ("applicants.html", include_str!("templates/applicants.html")),
+    ("applicant.html", include_str!("templates/applicant.html")),
+    ("cycles.html", include_str!("templates/cycles.html")),
+    ("live.html", include_str!("templates/live.html")),
+    ("error.html", include_str!("templates/error.html")),
+];
+
+// The pages are compiled in, unlike the email templates, so they're parsed once
+fn views() -> &'static Environment<'static> {
+    static VIEWS: OnceLock<Environment<'static>> = OnceLock::new();
+    VIEWS.get_or_init(|| {
+        let mut env = Environment::new();
+        env.set_undefined_behavior(UndefinedBehavior::Strict);
+        env.add_filter("datetime", datetime);
+        for (name, source) in TEMPLATES {
+            env.add_template(name, source)
+                .expect("the admin templates should parse");
+        }
+        env
+    })
+}
+
+// Timestamps come through as RFC 3339 - this is easier on the eyes
+fn datetime(value: String) -> String {
+    match DateTime::parse_from_rfc3339(&value) {
+        Ok(at) => at
+            .with_timezone(&Utc)
+            .format("%b %-d %Y, %H:%M UTC")
+            .to_string(),
+        Err(_) => value,
+    }
+}
+
+#[derive(Debug)]
+struct LoggedOut;
+
+impl reject::Reject for LoggedOut {}
+
+#[derive(Debug)]
+struct RenderFailed;
+
+impl reject::Reject for RenderFailed {}
+
+#[derive(Deserialize)]
+struct LoginForm {
+    token: String,
+}
+
+// Straight off the filter form, where an empty field means "don't filter"
+#[derive(Deserialize, Default)]
+struct ApplicantsQuery {
+    cycle: Option<String>,
+    status: Option<String>,
+    solved: Option<String>,
+    q: Option<String>,
+    page: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct StatusForm {
+    status: Status,
+}
+
+#[derive(Deserialize)]
+struct NoteForm {
+    body: String,
+}
+
+#[derive(Deserialize)]
+struct CycleForm {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct RetentionForm {
+    // Blank to keep applicants forever
+    days: String,
+    action: RetentionAction,
+}
+
+// Everything under /admin/ui. Pages need a session cookie, which is handed out
+// for the admin token at /admin/ui/login
+pub fn pages(pool: PgPool, settings: &Settings, live: Live) -> BoxedFilter<(Response,)> {
+    let with_live = warp::any().map(move || live.clone());
+    let with_db = warp::any().map(move || pool.clone());
+    let analysis = settings.analysis;
+    let with_analysis = warp::any().map(move || analysis);
+    let admin_token = settings.admin.token.clone();
+    let with_admin_token = warp::any().map(move || admin_token.clone());
+    let logged_in = logged_in(settings.admin.token.clone());
+    let context = request_context(&settings.proxies);
+
+    let index = warp::get()
+        .and(path::end())
+        .map(|| see_other("/admin/ui/applicants"));
+    let login_page = warp::get().and(path!("login")).and_then(|| async {
+        render(
+            "login.html",
+            StatusCode::OK,
+            context! { logged_in => false, failed => false },
+        )
+    });
+    let login = warp::post()
+        .and(path!("login"))
+        .and(form())
+        .and(with_admin_token)
+        .and_then(handle_login);
+    let logout = warp::post().and(path!("logout")).map(|| {
+        let res = see_other("/admin/ui/login");
+        reply::with_header(res, SET_COOKIE, session::cookie(None)).into_response()
+    });
+
+    let applicants = warp::get()
+        .and(path!("applicants"))
+        .and(logged_in.clone())
+        .and(warp::query::<ApplicantsQuery>())
+        .and(with_db.clone())
+        .and(with_analysis)
+        .and_then(handle_applicants);
+    let applicant = warp::get()
+        .and(path!("applicants" / String))
+        .and(logged_in.clone())
+        .and(with_db.clone())
+        .and(with_analysis)
+        .and_then(handle_applicant);
+    let status = warp::post()
+        .and(path!("applicants" / String / "status"))
+        .and(logged_in.clone())
+        .and(form())
+        .and(with_db.clone())
+        .and(context.clone())
+        .and_then(handle_status);
+    let note = warp::post()
+        .and(path!("applicants" / String / "notes"))
+        .and(logged_in.clone())
+        .and(form())
+        .and(with_db.clone())
+        .and(context.clone())
+        .and_then(handle_note);
+
+    let cycles = warp::get()
+        .and(path!("cycles"))
+        .and(logged_in.clone())
+        .and(with_db.clone())
+        .and_then(handle_cycles);
+    let create_cycle = warp::post()
+        .and(path!("cycles"))
+        .and(logged_in.clone())
+        .and(form())
+        .and(with_db.clone())
+        .and(context.clone())
+        .and_then(handle_create_cycle);
+    let activate_cycle = warp::post()
+        .and(path!("cycles" / String / "activate"))
+        .and(logged_in.clone())
+        .and(with_db.clone())
+        .and(context.clone())
+        .and_then(handle_activate_cycle);
+    let retention = warp::post()
+        .and(path!("cycles" / String / "retention"))
+        .and(logged_in.clone())
+        .and(form())
+        .and(with_db)
+        .and(context)
+        .and_then(handle_retention);
+
+    // The same feed as /v1/admin/live, for the browser's EventSource - it can't
+    // send the admin token, but it does send the session cookie
+    let live_page = warp::get()
+        .and(path!("live"))
+        .and(logged_in.clone())
+        .and_then(|| async { render("live.html", StatusCode::OK, context! { logged_in => true }) });
+    let live_events = warp::get()
+        .and(path!("live" / "events"))
+        .and(logged_in)
+        .and(with_live)
+        .map(|live: Live| {
+            info!("Someone's watching the live feed from the admin pages");
+            let events = live::stream(live.subscribe());
+            warp::sse::reply(warp::sse::keep_alive().stream(events)).into_response()
+        });
+
+    index
+        .or(login_page)
+        .unify()
+        .or(login)
+        .unify()
+        .or(logout)
+        .unify()
+        .or(applicants)
+        .unify()
+        .or(applicant)
+        .unify()
+        .or(status)
+        .unify()
+        .or(note)
+        .unify()
+        .or(cycles)
+        .unify()
+        .or(create_cycle)
+        .unify()
+        .or(activate_cycle)
+        .unify()
+        .or(retention)
+        .unify()
+        .or(live_page)
+        .unify()
+        .or(live_events)
+        .unify()
+        .boxed()
+}
+
+fn form<T: DeserializeOwned + Send>() -> impl Filter<Extract = (T,), Error = Rejection> + Clone {
+    warp::body::content_length_limit(MAX_FORM_BYTES).and(warp::body::form())
+}
+
+// Goes after the path on every page but the login page
+fn logged_in(admin_token: Option<String>) -> BoxedFilter<()> {
+    warp::cookie::optional::<String>(session::COOKIE)
+        .and_then(move |cookie: Option<String>| {
+            let admin_token = admin_token.clone();
+            async move {
+                match (admin_token, cookie) {
+                    (Some(admin_token), Some(cookie))
+                        if session::is_valid(&admin_token, &cookie, Utc::now().timestamp()) =>
+                    {
+                        Ok(())
+                    }
+                    _ => Err::<(), Rejection>(reject::custom(LoggedOut)),
+                }
+            }
+        })
+        .untuple_one()
+        .boxed()
+}
+
+fn render(name: &str, status: StatusCode, ctx: Value) -> Result<Response, Rejection> {
+    let page = views()
+        .get_template(name)
+        .and_then(|template| template.render(ctx));
+    match page {
+        Ok(page) => Ok(reply::with_status(reply::html(page), status).into_response()),
+        Err(e) => {
+            error!("Failed to render {}: {:?}", name, e);
+            Err(reject::custom(RenderFailed))
+        }
+    }
+}
+
+// Every form posts back to a page rather than rendering one, so refreshing
+// doesn't send it again
+fn see_other(location: impl Into<String>) -> Response {
+    reply::with_header(StatusCode::SEE_OTHER, LOCATION, location.into()).into_response()
+}
+
+fn statuses() -> Vec<&'static str> {
+    Status::ALL.iter().map(|status| status.as_str()).collect()
+}
+
+async fn handle_login(form: LoginForm, admin_token: Option<String>) -> Result<Response, Rejection> {
+    match admin_token {
+        Some(admin_token) if tokens_match(&admin_token, form.token.trim()) => {
+            let session = session::issue(&admin_token, Utc::now().timestamp());
+            let res = see_other("/admin/ui/applicants");
+            Ok(
+                reply::with_header(res, SET_COOKIE, session::cookie(Some(&session)))
+                    .into_response(),
+            )
+        }
+        _ => render(
+            "login.html",
+            StatusCode::UNAUTHORIZED,
+            context! { logged_in => false, failed => true },
+        ),
+    }
+}
+
+async fn handle_applicants(
+    query: ApplicantsQuery,
+    pool: PgPool,
+    settings: AnalysisSettings,
+) -> Result<Response, Rejection> {
+    let given = |value: &Option<String>| {
+        value
+            .as_deref()
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(String::from)
+    };
+    let cycle = given(&query.cycle);
+    let search = given(&query.q);
+    let status = match given(&query.status) {
+        Some(status) => match Status::ALL.iter().find(|s| s.as_str() == status) {
+            Some(status) => Some(*status),
+            None => return Err(reject::custom(invalid("status", "Not a status"))),
+        },
+        None => None,
+    };
+    let solved = match given(&query.solved).as_deref() {
+        Some("yes") => Some(true),
+        Some("no") => Some(false),
+        Some(_) => return Err(reject::custom(invalid("solved", "Has to be yes or no"))),
+        None => None,
+    };
+    let page = query.page.unwrap_or(0).max(0);
+    // Past this the offset doesn't fit in the query
+    if page.checked_mul(PAGE_SIZE).is_none() {
+        return Err(reject::custom(invalid(
+            "page",
+            "There aren't that many pages",
+        )));
+    }
+
+    let filter = ApplicantFilter {
+        cycle: cycle.clone(),
+        status,
+        solved,
+        search: search.clone(),
+        page,
+    };
+    let rows = applicants::list(&pool, &filter).await?;
+    let flagged: Vec<String> = analysis::flagged(&pool, &settings, cycle.as_deref())
+        .await?
+        .into_iter()
+        .map(|flagged| flagged.nuid)
+        .collect();
+    let cycles = cycles::get_cycles(&pool).await?;
+
+    // The same filters, on another page
+    let link = |page: i64| {
+        let mut params = Map::new();
+        for (name, value) in [
+            ("cycle", &cycle),
+            ("status", &given(&query.status)),
+            ("solved", &given(&query.solved)),
+            ("q", &search),
+        ] {
+            if let Some(value) = value {
+                params.insert(name.into(), value.clone().into());
+            }
+        }
+        params.insert("page".into(), page.into());
+        let query = Value::from_serialize(&params);
+        let encoded = views()
+            .render_str("{{ query|urlencode }}", context! { query })
+            .unwrap_or_default();
+        format!("/admin/ui/applicants?{}", encoded)
+    };
+    let prev_url = (page > 0).then(|| link(page - 1));
+    let next_url = (rows.len() as i64 == PAGE_SIZE).then(|| link(page.saturating_add(1)));
+
+    render(
+        "applicants.html",
+        StatusCode::OK,
+        context! {
+            logged_in => true,
+            applicants => rows,
+            flagged,
+            cycles,
+            statuses => statuses(),
+            filter => context! {
+                cycle => cycle.unwrap_or_default(),
+                status => given(&query.status).unwrap_or_default(),
+                solved => given(&query.solved).unwrap_or_default(),
+                q => search.unwrap_or_default(),
+            },
+            prev_url,
+            next_url,
+        },
+    )
+}
+
+async fn handle_applicant(
+    nuid: String,
+    pool: PgPool,
+    settings: AnalysisSettings,
+) -> Result<Response, Rejection> {
+    let review = analysis::review(&pool, &settings, &nuid).await?;
+    let submissions = applicants::history(&pool, &nuid).await?;
+    let notes = applicants::notes(&pool, &nuid).await?;
+
+    render(
+        "applicant.html",
+        StatusCode::OK,
+        context! {
+            logged_in => true,
+            applicant => review,
+            submissions,
+            notes,
+            statuses => statuses(),
+        },
+    )
+}
+
+async fn handle_status(
+    nuid: String,
+    form: StatusForm,
+    pool: PgPool,
+    ctx: RequestContext,
+) -> Result<Response, Rejection> {
+    applicants::set_status(&pool, &nuid, form.status, &ctx).await?;
+    Ok(see_other(format!("/admin/ui/applicants/{}", nuid)))
+}
+
+async fn handle_note(
+    nuid: String,
+    form: NoteForm,
+    pool: PgPool,
+    ctx: RequestContext,
+) -> Result<Response, Rejection> {
+    let body = validate_note(&form.body)?;
+    applicants::add_note(&pool, &nuid, &body, &ctx).await?;
+    Ok(see_other(format!("/admin/ui/applicants/{}", nuid)))
+}
+
+async fn handle_cycles(pool: PgPool) -> Result<Response, Rejection> {
+    let cycles = cycles::get_cycles(&pool).await?;
+    render(
+        "cycles.html",
+        StatusCode::OK,
+        context! { logged_in => true, cycles },
+    )
+}
+
+async fn handle_create_cycle(
+    form: CycleForm,
+    pool: PgPool,
+    ctx: RequestContext,
+) -> Result<Response, Rejection> {
+    let name = validate_cycle_name(&form.name)?;
+    cycles::create_cycle(&pool, &name, &ctx).await?;
+    Ok(see_other("/admin/ui/cycles"))
+}
+
+async fn handle_activate_cycle(
+    cycle: String,
+    pool: PgPool,
+    ctx: RequestContext,
+) -> Result<Response, Rejection> {
+    cycles::activate_cycle(&pool, &cycle, &ctx).await?;
+    Ok(see_other("/admin/ui/cycles"))
+}
+
+async fn handle_retention(
+    cycle: String,
+    form: RetentionForm,
+    pool: PgPool,
+    ctx: RequestContext,
+) -> Result<Response, Rejection> {
+    let days = match form.days.trim() {
+        "" => None,
+        days => match days.parse() {
+            Ok(days) => Some(days),
+            Err(_) => return Err(reject::custom(invalid("days", "Has to be a whole number"))),
+        },
+    };
+    let policy = validate_retention(RetentionPolicy {
+        days,
+        action: form.action,
+    })?;
+    cycles::set_retention(&pool, &cycle, &policy, &ctx).await?;
+    Ok(see_other("/admin/ui/cycles"))
+}
+
+fn invalid(field: &str, msg: &str) -> ModelError {
+    ModelError::ValidationFailed {
+        errors: vec![FieldError {
+            field: field.into(),
+            msg: msg.into(),
+        }],
+    }
+}
+
+// The API's errors are JSON, but these are for people - so they're pages, and
+// running out of session just sends you back to log in
+pub async fn handle_rejection(err: Rejection) -> Result<Response, Infallible> {
+    if err.find::<LoggedOut>().is_some() {
+        return Ok(see_other("/admin/ui/login"));
+    }
+
+    // Look for what went wrong before what didn't match - a request that got
+    // as far as a handler was also turned away by every route for other methods
+    let (status, msg, errors) = if let Some(e) = err.find::<ModelError>() {
+        match e {
+            ModelError::NoUserFound => (
+                StatusCode::NOT_FOUND,
+                "No applicant with this NUID exists".to_string(),
+                vec![],
+            ),
+            ModelError::CycleNotFound => (StatusCode::NOT_FOUND, e.to_string(), vec![]),
+            ModelError::DuplicateCycle => (StatusCode::CONFLICT, e.to_string(), vec![]),
+            ModelError::ValidationFailed { errors } => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                e.to_string(),
+                errors.clone(),
+            ),
+            _ => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Something went wrong on our end".to_string(),
+                vec![],
+            ),
+        }
+    } else if err.find::<RenderFailed>().is_some() {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Something went wrong on our end".to_string(),
+            vec![],
+        )
+    } else if err.find::<BodyDeserializeError>().is_some()
+        || err.find::<reject::InvalidQuery>().is_some()
+    {
+        (
+            StatusCode::BAD_REQUEST,
+            "That form didn't make sense".to_string(),
+            vec![],
+        )
+    } else if err.is_not_found() || err.find::<reject::MethodNotAllowed>().is_some() {
+        (
+            StatusCode::NOT_FOUND,
+            "There's no page here".to_string(),
+            vec![],
+        )
+    } else {
+        (
+            StatusCode::BAD_REQUEST,
+            "That request didn't make sense".to_string(),
+            vec![],
+        )
+    };
+
+    let title = status.canonical_reason().unwrap_or_default();
+    let ctx = context! { logged_in => false, title, msg, errors };
+    match render("error.html", status, ctx) {
+        Ok(res) => Ok(res),
+        // Not much left to do but say so
+        Err(_) => Ok(reply::with_status(title.to_string(), status).into_response()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use minijinja::context;
+    use serde_json::json;
+
+    use super::{datetime, views, TEMPLATES};
+
+    #[test]
+    fn test_datetime() {
+        assert_eq!(
+            datetime("2026-10-19T14:05:00Z".into()),
+            "Oct 19 2026, 14:05 UTC"
+        );
+        assert_eq!(datetime("not a date".into()), "not a date");
+    }
+
+    // The templates are strict about undefined variables, so render every page
+    // once with everything it expects
+    #[test]
+    fn test_templates_render() {
+        let applicant = json!({
+            "nuid": "001453760",
+            "name": "Ada <script>",
+            "email": null,
+            "cycle": "default",
+            "status": "new",
+            "registered_at": "2026-10-19T14:05:00Z",
+            "registration_ip": "10.0.0.1",
+            "registration_user_agent": null,
+            "submissions": 2,
+            "solved": true,
+            "last_submission": "2026-10-19T15:05:00Z",
+            "flags": [
+                {"kind": "shared_answer", "shared_with": ["001453761"]},
+                {"kind": "ip_burst", "ip": "10.0.0.1", "applicants": ["001453761"]},
+                {"kind": "fast_solve", "seconds": 4.25},
+            ],
+            "hints": [
+                {"stage": 1, "hint": 1, "penalty": 10, "unlocked_at": "2026-10-19T14:35:00Z"},
+            ],
+            "score": 90,
+        });
+        let cycle = json!({
+            "name": "default",
+            "active": true,
+            "created_at": "2026-10-19T14:05:00Z",
+            "retention_days": null,
+            "retention_action": "anonymize",
+        });
+        let filter = json!({"cycle": "", "status": "", "solved": "", "q": ""});
+        let pages = [
+            (
+                "login.html",
+                context! { logged_in => false, failed => true },
+            ),
+            (
+                "applicants.html",
+                context! {
+                    logged_in => true,
+                    applicants => vec![&applicant],
+                    flagged => vec!["001453760"],
+                    cycles => vec![&cycle],
+                    statuses => vec!["new", "accepted"],
+                    filter,
+                    prev_url => Some("/admin/ui/applicants?page=0"),
+                    next_url => None::<String>,
+                },
+            ),
+            (
+                "applicant.html",
+                context! {
+                    logged_in => true,
+                    applicant,
+                    submissions => vec![json!({
+                        "submission_id": 1,
+                        "ok": true,
+                        "submitted_at": "2026-10-19T15:05:00Z",
+                        "ip": null,
+                        "user_agent": "curl/8.0",
+                    })],
+                    notes => vec![json!({
+                        "note_id": 1,
+                        "body": "Strong",
+                        "created_at": "2026-10-19T15:05:00Z",
+                    })],
+                    statuses => vec!["new", "accepted"],
+                },
+            ),
+            (
+                "cycles.html",
+                context! { logged_in => true, cycles => vec![cycle] },
+            ),
+            ("live.html", context! { logged_in => true }),
+            (
+                "error.html",
+                context! {
+                    logged_in => false,
+                    title => "Not Found",
+                    msg => "There's no page here",
+                    errors => vec![json!({"field": "days", "msg": "Has to be a whole number"})],
+                },
+            ),
+        ];
+
+        assert_eq!(pages.len() + 1, TEMPLATES.len());
+        for (name, ctx) in pages {
+            let page = views().get_template(name).unwrap().render(ctx);
+            let page = page.unwrap_or_else(|e| panic!("{} didn't render: {:?}", name, e));
+            assert!(!page.contains("Ada <script>"), "{} isn't escaped", name);
+        }
+    }
+}
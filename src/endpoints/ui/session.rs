@@ -0,0 +1,77 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+pub const COOKIE: &str = "generate_admin";
+// Reviewers log in again every morning
+pub const LIFETIME_SECS: i64 = 12 * 60 * 60;
+
+// A session is just when it expires, signed with the admin token - nothing is
+// kept server side, and changing the token logs everyone out
+pub fn issue(admin_token: &str, now: i64) -> String {
+    let expires = now + LIFETIME_SECS;
+    let signature = hex::encode(mac(admin_token, expires).finalize().into_bytes());
+    format!("{}.{}", expires, signature)
+}
+
+pub fn is_valid(admin_token: &str, session: &str, now: i64) -> bool {
+    let (expires, signature) = match session.split_once('.') {
+        Some(parts) => parts,
+        None => return false,
+    };
+    let (expires, signature) = match (expires.parse::<i64>(), hex::decode(signature)) {
+        (Ok(expires), Ok(signature)) => (expires, signature),
+        _ => return false,
+    };
+
+    // Constant time, so a signature can't be guessed a byte at a time
+    expires > now && mac(admin_token, expires).verify_slice(&signature).is_ok()
+}
+
+// The Set-Cookie header for a session - or for clearing it, with None
+pub fn cookie(session: Option<&str>) -> String {
+    let (value, max_age) = match session {
+        Some(session) => (session, LIFETIME_SECS),
+        None => ("", 0),
+    };
+    format!(
+        "{}={}; Path=/admin/ui; Max-Age={}; HttpOnly; Secure; SameSite=Strict",
+        COOKIE, value, max_age
+    )
+}
+
+fn mac(admin_token: &str, expires: i64) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(admin_token.as_bytes())
+        .expect("HMAC takes keys of any size");
+    mac.update(b"admin-session:");
+    mac.update(expires.to_string().as_bytes());
+    mac
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_valid, issue, LIFETIME_SECS};
+
+    #[test]
+    fn test_sessions() {
+        let session = issue("admin", 1_000);
+
+        assert!(is_valid("admin", &session, 1_000));
+        assert!(is_valid("admin", &session, 1_000 + LIFETIME_SECS - 1));
+        // Expired
+        assert!(!is_valid("admin", &session, 1_000 + LIFETIME_SECS));
+        // The admin token changed
+        assert!(!is_valid("admin2", &session, 1_000));
+    }
+
+    #[test]
+    fn test_tampered_sessions() {
+        let session = issue("admin", 1_000);
+        let (_, signature) = session.split_once('.').unwrap();
+
+        // Pushing the expiry out breaks the signature
+        assert!(!is_valid("admin", &format!("999999.{}", signature), 1_000));
+        for garbage in ["", ".", "abc", "1.2", "99999999999.zz"] {
+            assert!(!is_valid("admin", garbage, 1_000));
+        }
+    }
+}
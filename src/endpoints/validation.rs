@@ -0,0 +1,593 @@
+use super::errors::{FieldError, ModelError};
+use super::messages::{ProgramSubmission, RegisterRequest, RetentionPolicy};
+use crate::model::stages::{Challenge, Stages};
+
+const NUID_LEN: usize = 9;
+const MAX_NAME_LEN: usize = 100;
+const MAX_EMAIL_LEN: usize = 254;
+const MAX_DISPLAY_NAME_LEN: usize = 32;
+const MAX_IDEMPOTENCY_KEY_LEN: usize = 255;
+const MAX_CYCLE_LEN: usize = 50;
+const MAX_NOTE_LEN: usize = 5000;
+const MAX_STAGES: usize = 10;
+const MAX_K: usize = 12;
+const MAX_CHALLENGE_LEN: usize = 100_000;
+// Finding the longest repeat is the slowest thing grading does
+const MAX_REPEAT_LEN: usize = 10_000;
+const MAX_PROGRAM_TESTS: u32 = 20;
+const MAX_PROGRAM_LEN: usize = 64 * 1024;
+const MAX_BATCH_SIZE: u32 = 50;
+const MAX_BATCH_SECONDS: u32 = 600;
+const MAX_HINTS: usize = 10;
+const MAX_HINT_LEN: usize = 1000;
+
+// Checks a registration before it gets anywhere near the database. Every field
+// is checked so the applicant gets all of their mistakes back at once, and the
+// request that comes back out is trimmed and ready to insert.
+pub fn validate_registration(request: RegisterRequest) -> Result<RegisterRequest, ModelError> {
+    let mut errors = vec![];
+
+    let name = request.name.trim().to_string();
+    let nuid = request.nuid.trim().to_string();
+    let email = request
+        .email
+        .map(|email| email.trim().to_string())
+        .filter(|email| !email.is_empty());
+    let display_name = request
+        .display_name
+        .map(|display_name| display_name.trim().to_string())
+        .filter(|display_name| !display_name.is_empty());
+
+    if name.is_empty() {
+        errors.push(field_error("name", "Name can't be empty"));
+    } else if name.chars().count() > MAX_NAME_LEN {
+        errors.push(field_error(
+            "name",
+            &format!("Name can't be longer than {} characters", MAX_NAME_LEN),
+        ));
+    }
+
+    if nuid.len() != NUID_LEN || !nuid.chars().all(|c| c.is_ascii_digit()) {
+        errors.push(field_error(
+            "nuid",
+            &format!("NUID must be exactly {} digits", NUID_LEN),
+        ));
+    }
+
+    if let Some(email) = &email {
+        if !is_valid_email(email) {
+            errors.push(field_error("email", "Email address is not valid"));
+        }
+    }
+
+    // It's shown to the public, so nothing that could mess with the page
+    if let Some(display_name) = &display_name {
+        if display_name.chars().count() > MAX_DISPLAY_NAME_LEN
+            || display_name.chars().any(char::is_control)
+        {
+            errors.push(field_error(
+                "display_name",
+                &format!(
+                    "Display names are up to {} characters on one line",
+                    MAX_DISPLAY_NAME_LEN
+                ),
+            ));
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(RegisterRequest {
+            name,
+            nuid,
+            email,
+            display_name,
+        })
+    } else {
+        Err(ModelError::ValidationFailed { errors })
+    }
+}
+
+// Keys are opaque to us, they just have to be something we can store
+pub fn validate_idempotency_key(key: Option<String>) -> Result<Option<String>, ModelError> {
+    match key {
+        Some(key) if key.is_empty() || key.len() > MAX_IDEMPOTENCY_KEY_LEN => {
+            Err(ModelError::ValidationFailed {
+                errors: vec![field_error(
+                    "Idempotency-Key",
+                    &format!(
+                        "Idempotency-Key must be between 1 and {} characters",
+                        MAX_IDEMPOTENCY_KEY_LEN
+                    ),
+                )],
+            })
+        }
+        key => Ok(key),
+    }
+}
+
+pub fn validate_retention(policy: RetentionPolicy) -> Result<RetentionPolicy, ModelError> {
+    match policy.days {
+        Some(days) if days < 0 => Err(ModelError::ValidationFailed {
+            errors: vec![field_error("days", "Days can't be negative")],
+        }),
+        _ => Ok(policy),
+    }
+}
+
+// Cycle names end up in template paths, so they're kept to letters, digits and dashes
+pub fn validate_cycle_name(name: &str) -> Result<String, ModelError> {
+    let name = name.trim();
+    if name.is_empty()
+        || name.len() > MAX_CYCLE_LEN
+        || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    {
+        return Err(ModelError::ValidationFailed {
+            errors: vec![field_error(
+                "name",
+                &format!(
+                    "Cycle names are 1 to {} letters, digits and dashes",
+                    MAX_CYCLE_LEN
+                ),
+            )],
+        });
+    }
+    Ok(name.to_string())
+}
+
+pub fn validate_note(body: &str) -> Result<String, ModelError> {
+    let body = body.trim();
+    if body.is_empty() || body.chars().count() > MAX_NOTE_LEN {
+        return Err(ModelError::ValidationFailed {
+            errors: vec![field_error(
+                "body",
+                &format!("Notes are 1 to {} characters", MAX_NOTE_LEN),
+            )],
+        });
+    }
+    Ok(body.to_string())
+}
+
+// Keeps challenges to something we can generate and grade on every request
+pub fn validate_stages(stages: Stages) -> Result<Stages, ModelError> {
+    let mut errors = vec![];
+
+    if stages.stages.is_empty() || stages.stages.len() > MAX_STAGES {
+        errors.push(field_error(
+            "stages",
+            &format!("Cycles have 1 to {} stages", MAX_STAGES),
+        ));
+    }
+
+    for (i, stage) in stages.stages.iter().enumerate() {
+        let field = format!("stages[{}]", i);
+        validate_challenge(&field, &stage.challenge, &mut errors);
+        validate_hints(&field, &stage.hints, &mut errors);
+    }
+
+    if errors.is_empty() {
+        Ok(stages)
+    } else {
+        Err(ModelError::ValidationFailed { errors })
+    }
+}
+
+// Sources are kept with the submission, so they can't be huge
+pub fn validate_program(program: ProgramSubmission) -> Result<ProgramSubmission, ModelError> {
+    if program.source.trim().is_empty() || program.source.len() > MAX_PROGRAM_LEN {
+        return Err(ModelError::ValidationFailed {
+            errors: vec![field_error(
+                "source",
+                &format!("Programs are 1 to {} bytes", MAX_PROGRAM_LEN),
+            )],
+        });
+    }
+    Ok(program)
+}
+
+// `field` is where the challenge sits, so a program's challenge can point at its
+// own fields
+fn validate_challenge(field: &str, challenge: &Challenge, errors: &mut Vec<FieldError>) {
+    // Along with the shortest string the challenge makes sense on, and the
+    // longest it can be graded on quickly
+    let (length, shortest, longest) = match challenge {
+        Challenge::Kmers { k, length } => {
+            if *k == 0 || *k > MAX_K {
+                errors.push(field_error(
+                    &format!("{}.k", field),
+                    &format!("k is between 1 and {}", MAX_K),
+                ));
+            }
+            (*length, (*k).max(1), MAX_CHALLENGE_LEN)
+        }
+        Challenge::ReverseComplement { length } => (*length, 1, MAX_CHALLENGE_LEN),
+        Challenge::GcContent { window, length } => {
+            if *window == 0 || *window > *length {
+                errors.push(field_error(
+                    &format!("{}.window", field),
+                    "Windows are between 1 character and the whole string",
+                ));
+            }
+            (*length, 1, MAX_CHALLENGE_LEN)
+        }
+        // Nothing can repeat in a single character
+        Challenge::LongestRepeat { length } => (*length, 2, MAX_REPEAT_LEN),
+        // Every test is a run in the sandbox, so there can't be many
+        Challenge::Program { of, tests } => {
+            if *tests == 0 || *tests > MAX_PROGRAM_TESTS {
+                errors.push(field_error(
+                    &format!("{}.tests", field),
+                    &format!("Programs are run against 1 to {} tests", MAX_PROGRAM_TESTS),
+                ));
+            }
+            validate_wrapped(field, of, errors);
+            return;
+        }
+        Challenge::Batch { of, size, seconds } => {
+            if *size == 0 || *size > MAX_BATCH_SIZE {
+                errors.push(field_error(
+                    &format!("{}.size", field),
+                    &format!("Batches are 1 to {} strings", MAX_BATCH_SIZE),
+                ));
+            }
+            if *seconds == 0 || *seconds > MAX_BATCH_SECONDS {
+                errors.push(field_error(
+                    &format!("{}.seconds", field),
+                    &format!("Batches are due in 1 to {} seconds", MAX_BATCH_SECONDS),
+                ));
+            }
+            validate_wrapped(field, of, errors);
+            return;
+        }
+    };
+    if length < shortest || length > longest {
+        errors.push(field_error(
+            &format!("{}.length", field),
+            &format!(
+                "Challenge strings are between {} and {} characters",
+                shortest, longest
+            ),
+        ));
+    }
+}
+
+fn validate_hints(field: &str, hints: &[String], errors: &mut Vec<FieldError>) {
+    if hints.len() > MAX_HINTS {
+        errors.push(field_error(
+            &format!("{}.hints", field),
+            &format!("Stages have up to {} hints", MAX_HINTS),
+        ));
+    }
+    for (i, hint) in hints.iter().enumerate() {
+        if hint.trim().is_empty() || hint.chars().count() > MAX_HINT_LEN {
+            errors.push(field_error(
+                &format!("{}.hints[{}]", field, i),
+                &format!("Hints are 1 to {} characters", MAX_HINT_LEN),
+            ));
+        }
+    }
+}
+
+// Programs and batches wrap a challenge that's answered directly
+fn validate_wrapped(field: &str, of: &Challenge, errors: &mut Vec<FieldError>) {
+    let of_field = format!("{}.of", field);
+    match of {
+        Challenge::Program { .. } | Challenge::Batch { .. } => errors.push(field_error(
+            &of_field,
+            "Programs and batches wrap a challenge that takes an answer",
+        )),
+        _ => validate_challenge(&of_field, of, errors),
+    }
+}
+
+// Not trying to implement RFC 5322 here - just enough to catch typos
+fn is_valid_email(email: &str) -> bool {
+    if email.len() > MAX_EMAIL_LEN || email.chars().any(char::is_whitespace) {
+        return false;
+    }
+
+    match email.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty()
+                && !domain.contains('@')
+                && domain.contains('.')
+                && !domain.starts_with('.')
+                && !domain.ends_with('.')
+        }
+        None => false,
+    }
+}
+
+fn field_error(field: &str, msg: &str) -> FieldError {
+    FieldError {
+        field: field.to_string(),
+        msg: msg.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        validate_cycle_name, validate_idempotency_key, validate_note, validate_program,
+        validate_registration, validate_retention, validate_stages,
+    };
+    use crate::endpoints::errors::ModelError;
+    use crate::endpoints::messages::{
+        Language, ProgramSubmission, RegisterRequest, RetentionPolicy,
+    };
+    use crate::model::retention::RetentionAction;
+    use crate::model::stages::{Challenge, Stage, Stages};
+
+    // Without any hints
+    fn stages(challenges: Vec<Challenge>) -> Stages {
+        Stages {
+            stages: challenges
+                .into_iter()
+                .map(|challenge| Stage {
+                    challenge,
+                    hints: vec![],
+                })
+                .collect(),
+        }
+    }
+
+    fn request(name: &str, nuid: &str, email: Option<&str>) -> RegisterRequest {
+        RegisterRequest {
+            name: name.to_string(),
+            nuid: nuid.to_string(),
+            email: email.map(String::from),
+            display_name: None,
+        }
+    }
+
+    fn failed_fields(request: RegisterRequest) -> Vec<String> {
+        match validate_registration(request) {
+            Err(ModelError::ValidationFailed { errors }) => {
+                errors.into_iter().map(|e| e.field).collect()
+            }
+            _ => vec![],
+        }
+    }
+
+    #[test]
+    fn test_valid_registration_is_trimmed() {
+        let validated = validate_registration(request(
+            "  Ada Lovelace ",
+            " 001453760 ",
+            Some(" ada@x.io "),
+        ))
+        .unwrap();
+
+        assert_eq!(validated.name, "Ada Lovelace");
+        assert_eq!(validated.nuid, "001453760");
+        assert_eq!(validated.email, Some(String::from("ada@x.io")));
+    }
+
+    #[test]
+    fn test_blank_email_is_dropped() {
+        let validated = validate_registration(request("Ada", "001453760", Some("  "))).unwrap();
+        assert_eq!(validated.email, None);
+    }
+
+    #[test]
+    fn test_bad_nuids() {
+        assert_eq!(failed_fields(request("Ada", "", None)), vec!["nuid"]);
+        assert_eq!(
+            failed_fields(request("Ada", "12345678", None)),
+            vec!["nuid"]
+        );
+        assert_eq!(
+            failed_fields(request("Ada", "00145376a", None)),
+            vec!["nuid"]
+        );
+        assert_eq!(
+            failed_fields(request("Ada", "0014537600", None)),
+            vec!["nuid"]
+        );
+    }
+
+    #[test]
+    fn test_bad_names() {
+        assert_eq!(
+            failed_fields(request("   ", "001453760", None)),
+            vec!["name"]
+        );
+        assert_eq!(
+            failed_fields(request(&"a".repeat(101), "001453760", None)),
+            vec!["name"]
+        );
+    }
+
+    #[test]
+    fn test_bad_emails() {
+        for email in [
+            "ada", "@x.io", "ada@", "ada@x", "ada@x.", "a b@x.io", "a@b@x.io",
+        ] {
+            assert_eq!(
+                failed_fields(request("Ada", "001453760", Some(email))),
+                vec!["email"]
+            );
+        }
+    }
+
+    #[test]
+    fn test_display_names() {
+        let with_display_name = |display_name: &str| RegisterRequest {
+            display_name: Some(display_name.into()),
+            ..request("Ada", "001453760", None)
+        };
+
+        let validated = validate_registration(with_display_name(" ada99 ")).unwrap();
+        assert_eq!(validated.display_name, Some(String::from("ada99")));
+        let validated = validate_registration(with_display_name("  ")).unwrap();
+        assert_eq!(validated.display_name, None);
+        for display_name in ["a\nb", "a\u{7}", &"a".repeat(33)] {
+            assert_eq!(
+                failed_fields(with_display_name(display_name)),
+                vec!["display_name"]
+            );
+        }
+    }
+
+    #[test]
+    fn test_reports_every_bad_field() {
+        assert_eq!(
+            failed_fields(request("", "nope", Some("nope"))),
+            vec!["name", "nuid", "email"]
+        );
+    }
+
+    #[test]
+    fn test_idempotency_key() {
+        assert_eq!(validate_idempotency_key(None).unwrap(), None);
+        assert_eq!(
+            validate_idempotency_key(Some("retry-1".into())).unwrap(),
+            Some("retry-1".into())
+        );
+        assert!(validate_idempotency_key(Some("".into())).is_err());
+        assert!(validate_idempotency_key(Some("k".repeat(256))).is_err());
+    }
+
+    #[test]
+    fn test_retention() {
+        let policy = |days| RetentionPolicy {
+            days,
+            action: RetentionAction::Delete,
+        };
+        assert!(validate_retention(policy(None)).is_ok());
+        assert!(validate_retention(policy(Some(0))).is_ok());
+        assert!(validate_retention(policy(Some(-1))).is_err());
+    }
+
+    #[test]
+    fn test_cycle_names() {
+        assert_eq!(validate_cycle_name(" fall-2026 ").unwrap(), "fall-2026");
+        for name in ["", "../etc", "fall 2026", "fall_2026", &"a".repeat(51)] {
+            assert!(validate_cycle_name(name).is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_notes() {
+        assert_eq!(validate_note("  Strong\n").unwrap(), "Strong");
+        assert!(validate_note("   ").is_err());
+        assert!(validate_note(&"a".repeat(5001)).is_err());
+    }
+
+    #[test]
+    fn test_stages() {
+        let failed_fields = |challenges: Vec<Challenge>| match validate_stages(stages(challenges)) {
+            Err(ModelError::ValidationFailed { errors }) => {
+                errors.into_iter().map(|e| e.field).collect()
+            }
+            _ => vec![],
+        };
+        let kmers = |k, length| Challenge::Kmers { k, length };
+
+        assert!(validate_stages(stages(vec![kmers(3, 100), kmers(6, 1000)])).is_ok());
+        assert_eq!(failed_fields(vec![]), vec!["stages"]);
+        assert_eq!(failed_fields(vec![kmers(3, 100); 11]), vec!["stages"]);
+        assert_eq!(
+            failed_fields(vec![kmers(3, 100), kmers(0, 100), kmers(8, 5)]),
+            vec!["stages[1].k", "stages[2].length"]
+        );
+        assert!(validate_stages(stages(vec![
+            Challenge::ReverseComplement { length: 100 },
+            Challenge::GcContent {
+                window: 10,
+                length: 95
+            },
+            Challenge::LongestRepeat { length: 1000 },
+        ]))
+        .is_ok());
+        assert_eq!(
+            failed_fields(vec![
+                Challenge::ReverseComplement { length: 0 },
+                Challenge::GcContent {
+                    window: 20,
+                    length: 10
+                },
+                Challenge::LongestRepeat { length: 1 },
+                Challenge::LongestRepeat { length: 20_000 },
+            ]),
+            vec![
+                "stages[0].length",
+                "stages[1].window",
+                "stages[2].length",
+                "stages[3].length"
+            ]
+        );
+        let program = |of, tests| Challenge::Program {
+            of: Box::new(of),
+            tests,
+        };
+        assert!(validate_stages(stages(vec![kmers(3, 100), program(kmers(3, 100), 10)])).is_ok());
+        assert_eq!(
+            failed_fields(vec![
+                program(kmers(0, 100), 0),
+                program(program(kmers(3, 100), 5), 21),
+            ]),
+            vec![
+                "stages[0].tests",
+                "stages[0].of.k",
+                "stages[1].tests",
+                "stages[1].of"
+            ]
+        );
+        let batch = |of, size, seconds| Challenge::Batch {
+            of: Box::new(of),
+            size,
+            seconds,
+        };
+        assert!(validate_stages(stages(vec![batch(kmers(3, 100), 20, 10)])).is_ok());
+        assert_eq!(
+            failed_fields(vec![
+                batch(kmers(3, 100), 0, 601),
+                batch(program(kmers(3, 100), 5), 10, 10),
+                program(batch(kmers(3, 100), 10, 10), 5),
+            ]),
+            vec![
+                "stages[0].size",
+                "stages[0].seconds",
+                "stages[1].of",
+                "stages[2].of"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_hints() {
+        let hinted = |hints: Vec<String>| Stages {
+            stages: vec![Stage {
+                challenge: Challenge::default(),
+                hints,
+            }],
+        };
+        let failed_fields = |hints| match validate_stages(hinted(hints)) {
+            Err(ModelError::ValidationFailed { errors }) => {
+                errors.into_iter().map(|e| e.field).collect()
+            }
+            _ => vec![],
+        };
+
+        assert!(validate_stages(hinted(vec!["Count overlapping substrings".into()])).is_ok());
+        assert_eq!(
+            failed_fields(vec!["Fine".into(), " ".into(), "a".repeat(1001)]),
+            vec!["stages[0].hints[1]", "stages[0].hints[2]"]
+        );
+        assert_eq!(
+            failed_fields(vec!["Fine".into(); 11]),
+            vec!["stages[0].hints"]
+        );
+    }
+
+    #[test]
+    fn test_program() {
+        let program = |source: &str| ProgramSubmission {
+            language: Language::Python,
+            source: source.to_string(),
+        };
+        assert!(validate_program(program("print('{}')")).is_ok());
+        assert!(validate_program(program(" \n")).is_err());
+        assert!(validate_program(program(&"#".repeat(64 * 1024 + 1))).is_err());
+    }
+}
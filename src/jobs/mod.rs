@@ -0,0 +1,182 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Postgres, Transaction};
+use utoipa::ToSchema;
+
+use crate::db;
+use crate::endpoints::errors::ModelError;
+use crate::model::audit::{self, Action, Actor, RequestContext};
+use crate::model::events::Event;
+
+pub mod runner;
+
+pub use runner::{run, JobContext};
+
+// Everything the runner knows how to do. Handlers never do these inline, they
+// enqueue one inside their own transaction and let the runner get to it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", content = "args", rename_all = "snake_case")]
+pub enum Job {
+    // Something happened to an applicant - fans out into the jobs below
+    Notify {
+        event: Event,
+        data: serde_json::Value,
+    },
+    DeliverWebhook {
+        delivery_id: i64,
+    },
+    // Emails the applicant about the event, if they left an address
+    SendEmail {
+        nuid: String,
+        event: Event,
+    },
+}
+
+impl Job {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Job::Notify { .. } => "notify",
+            Job::DeliverWebhook { .. } => "deliver_webhook",
+            Job::SendEmail { .. } => "send_email",
+        }
+    }
+
+    fn max_attempts(&self) -> i32 {
+        match self {
+            Job::Notify { .. } => 5,
+            // With the runner's backoff this is a little over a day of retrying
+            Job::DeliverWebhook { .. } => 12,
+            Job::SendEmail { .. } => 8,
+        }
+    }
+}
+
+// A row of the jobs table
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct JobRecord {
+    pub job_id: i64,
+    pub kind: String,
+    #[schema(value_type = Object)]
+    pub payload: serde_json::Value,
+    pub status: String,
+    pub attempts: i32,
+    pub max_attempts: i32,
+    pub run_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct JobError {
+    pub msg: String,
+    // Some failures will never go away no matter how often we retry
+    pub permanent: bool,
+}
+
+impl JobError {
+    pub fn retry(msg: impl Into<String>) -> JobError {
+        JobError {
+            msg: msg.into(),
+            permanent: false,
+        }
+    }
+
+    pub fn permanent(msg: impl Into<String>) -> JobError {
+        JobError {
+            msg: msg.into(),
+            permanent: true,
+        }
+    }
+}
+
+impl From<sqlx::Error> for JobError {
+    fn from(e: sqlx::Error) -> JobError {
+        JobError::retry(e.to_string())
+    }
+}
+
+pub async fn enqueue(tx: &mut Transaction<'_, Postgres>, job: &Job) -> Result<i64, sqlx::Error> {
+    let payload = serde_json::to_value(job).expect("jobs always serialize");
+    db::jobs::enqueue_job_db(tx, job.kind(), &payload, job.max_attempts()).await
+}
+
+pub async fn get_jobs(
+    pool: &PgPool,
+    status: Option<String>,
+    kind: Option<String>,
+    limit: i64,
+) -> Result<Vec<JobRecord>, ModelError> {
+    match db::jobs::get_jobs_db(pool, status, kind, limit).await {
+        Ok(jobs) => Ok(jobs),
+        Err(_) => Err(ModelError::SqlError),
+    }
+}
+
+pub async fn retry_job(pool: &PgPool, job_id: i64, ctx: &RequestContext) -> Result<(), ModelError> {
+    let mut tx = pool.begin().await.map_err(|_| ModelError::SqlError)?;
+    let retried = async {
+        let retried = db::jobs::retry_job_db(&mut tx, job_id).await?;
+        if retried {
+            let details = serde_json::json!({ "job_id": job_id });
+            audit::record(
+                &mut tx,
+                ctx,
+                Action::JobRetried,
+                Actor::Admin,
+                None,
+                details,
+            )
+            .await?;
+        }
+        Ok::<_, sqlx::Error>(retried)
+    };
+    match retried.await {
+        Ok(true) => {}
+        Ok(false) => return Err(ModelError::JobNotFound),
+        Err(_) => return Err(ModelError::SqlError),
+    }
+
+    match tx.commit().await {
+        Ok(()) => Ok(()),
+        Err(_) => Err(ModelError::SqlError),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::Job;
+    use crate::model::events::Event;
+
+    #[test]
+    fn test_job_payloads() {
+        let job = Job::Notify {
+            event: Event::Solved,
+            data: json!({"nuid": "001453760"}),
+        };
+        let payload = serde_json::to_value(&job).unwrap();
+
+        assert_eq!(
+            payload,
+            json!({
+                "kind": "notify",
+                "args": {"event": "applicant.solved", "data": {"nuid": "001453760"}}
+            })
+        );
+        assert_eq!(payload["kind"], job.kind());
+        assert_eq!(serde_json::from_value::<Job>(payload).unwrap(), job);
+    }
+
+    #[test]
+    fn test_migrated_webhook_payloads() {
+        // This is the shape the jobs migration writes for old outbox rows
+        let payload = json!({"kind": "deliver_webhook", "args": {"delivery_id": 3}});
+
+        assert_eq!(
+            serde_json::from_value::<Job>(payload).unwrap(),
+            Job::DeliverWebhook { delivery_id: 3 }
+        );
+    }
+}
@@ -0,0 +1,147 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use sqlx::PgPool;
+
+use super::{Job, JobError, JobRecord};
+use crate::db;
+use crate::email::Mail;
+use crate::webhooks::{self, Webhooks};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+// Comfortably longer than any job takes. A job still running after this is
+// assumed to have lost its runner, and gets run again
+const LEASE: Duration = Duration::from_secs(5 * 60);
+
+// What jobs need to get their work done
+#[derive(Clone)]
+pub struct JobContext {
+    pub webhooks: Webhooks,
+    // None when email is switched off
+    pub mail: Option<Mail>,
+    pub http: reqwest::Client,
+}
+
+impl JobContext {
+    pub fn new(webhooks: Webhooks, mail: Option<Mail>) -> JobContext {
+        JobContext {
+            webhooks,
+            mail,
+            http: reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .expect("Failed to build the job runner's http client"),
+        }
+    }
+}
+
+// Works through the jobs table forever - spawned from main next to the server
+pub async fn run(pool: PgPool, ctx: JobContext) {
+    loop {
+        match run_next(&pool, &ctx).await {
+            // There might be more waiting, go straight back around
+            Ok(true) => continue,
+            Ok(false) => {}
+            Err(e) => error!("The job runner hit a database error: {:?}", e),
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+// Runs the next due job, if there is one. Nothing is held open while it runs,
+// since webhooks and email can take a while to answer
+async fn run_next(pool: &PgPool, ctx: &JobContext) -> Result<bool, sqlx::Error> {
+    let lease_until = Utc::now() + chrono::Duration::from_std(LEASE).unwrap();
+    let record = match db::jobs::claim_next_job_db(pool, lease_until).await? {
+        Some(record) => record,
+        None => return Ok(false),
+    };
+
+    let result = match serde_json::from_value::<Job>(record.payload.clone()) {
+        Ok(job) => perform(ctx, pool, &record, job).await,
+        Err(e) => Err(JobError::permanent(format!("Unreadable job: {}", e))),
+    };
+
+    match result {
+        Ok(()) => {
+            info!("Finished {} job {}", record.kind, record.job_id);
+            db::jobs::finish_job_db(pool, record.job_id, record.attempts).await?;
+        }
+        Err(e) => {
+            let attempts = record.attempts;
+            let dead = e.permanent || attempts >= record.max_attempts;
+            warn!(
+                "{} job {} failed on attempt {}{}: {}",
+                record.kind,
+                record.job_id,
+                attempts,
+                if dead { ", giving up" } else { "" },
+                e.msg
+            );
+            db::jobs::fail_job_db(
+                pool,
+                record.job_id,
+                attempts,
+                dead,
+                Utc::now() + backoff(attempts),
+                &e.msg,
+            )
+            .await?;
+        }
+    }
+
+    Ok(true)
+}
+
+async fn perform(
+    ctx: &JobContext,
+    pool: &PgPool,
+    record: &JobRecord,
+    job: Job,
+) -> Result<(), JobError> {
+    match job {
+        // Everything it queues goes in together, or not at all
+        Job::Notify { event, data } => {
+            let mut tx = pool.begin().await?;
+            if let Some(mail) = &ctx.mail {
+                mail.fan_out(&mut tx, event, &data).await?;
+            }
+            ctx.webhooks.fan_out(&mut tx, event, data).await?;
+            tx.commit().await?;
+            Ok(())
+        }
+        Job::DeliverWebhook { delivery_id } => {
+            let last_attempt = record.attempts >= record.max_attempts;
+            webhooks::deliver_queued(pool, &ctx.http, &ctx.webhooks, delivery_id, last_attempt)
+                .await
+        }
+        Job::SendEmail { nuid, event } => match &ctx.mail {
+            Some(mail) => mail.send(pool, &nuid, event).await,
+            // Queued before email was switched off
+            None => Err(JobError::permanent("Email isn't configured")),
+        },
+    }
+}
+
+// 30s, 1m, 2m, ... capped at 6 hours
+pub fn backoff(attempts: i32) -> chrono::Duration {
+    let exponent = attempts.clamp(1, 16) as u32 - 1;
+    std::cmp::min(
+        chrono::Duration::seconds(30 * 2_i64.pow(exponent)),
+        chrono::Duration::hours(6),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::backoff;
+
+    #[test]
+    fn test_backoff() {
+        assert_eq!(backoff(1), chrono::Duration::seconds(30));
+        assert_eq!(backoff(2), chrono::Duration::seconds(60));
+        assert_eq!(backoff(5), chrono::Duration::seconds(480));
+        assert_eq!(backoff(12), chrono::Duration::hours(6));
+    }
+}
@@ -0,0 +1,177 @@
+use std::convert::Infallible;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgListener;
+use sqlx::{PgPool, Postgres, Transaction};
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::{Stream, StreamExt};
+use utoipa::ToSchema;
+use warp::sse;
+
+use crate::db;
+
+// The Postgres channel every instance publishes to and listens on
+const CHANNEL: &str = "generate_live";
+// How far a slow dashboard can fall behind before it starts missing events
+const BACKLOG: usize = 256;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+pub enum LiveKind {
+    #[serde(rename = "applicant.registered")]
+    Registered,
+    #[serde(rename = "applicant.submitted")]
+    Submitted,
+    #[serde(rename = "applicant.solved")]
+    Solved,
+}
+
+impl LiveKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LiveKind::Registered => "applicant.registered",
+            LiveKind::Submitted => "applicant.submitted",
+            LiveKind::Solved => "applicant.solved",
+        }
+    }
+}
+
+// One thing happening, as the admin dashboard sees it
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct LiveEvent {
+    pub kind: LiveKind,
+    pub nuid: String,
+    pub occurred_at: DateTime<Utc>,
+    #[schema(value_type = Object)]
+    pub data: serde_json::Value,
+}
+
+// Goes out through Postgres rather than straight to the channel, so it only
+// shows up once the transaction commits, and every instance's dashboards see it
+pub async fn publish(
+    tx: &mut Transaction<'_, Postgres>,
+    kind: LiveKind,
+    nuid: &str,
+    data: serde_json::Value,
+) -> Result<(), sqlx::Error> {
+    let event = LiveEvent {
+        kind,
+        nuid: nuid.into(),
+        occurred_at: Utc::now(),
+        data,
+    };
+    let payload = serde_json::to_string(&event).expect("live events always serialize");
+    db::live::notify_db(tx, CHANNEL, &payload).await
+}
+
+// This instance's end of the feed - cheap to clone
+#[derive(Clone)]
+pub struct Live {
+    sender: broadcast::Sender<LiveEvent>,
+}
+
+impl Default for Live {
+    fn default() -> Live {
+        Live::new()
+    }
+}
+
+impl Live {
+    pub fn new() -> Live {
+        let (sender, _) = broadcast::channel(BACKLOG);
+        Live { sender }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<LiveEvent> {
+        self.sender.subscribe()
+    }
+
+    // Passes everything published on any instance to this one's subscribers.
+    // Runs for as long as the server does
+    pub async fn listen(self, pool: PgPool) {
+        loop {
+            if let Err(e) = self.forward(&pool).await {
+                warn!("Lost the live event listener, reconnecting: {:?}", e);
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+
+    async fn forward(&self, pool: &PgPool) -> Result<(), sqlx::Error> {
+        let mut listener = PgListener::connect_with(pool).await?;
+        listener.listen(CHANNEL).await?;
+        loop {
+            let notification = listener.recv().await?;
+            match serde_json::from_str::<LiveEvent>(notification.payload()) {
+                // Nobody watching isn't an error
+                Ok(event) => {
+                    let _ = self.sender.send(event);
+                }
+                Err(e) => warn!("Ignoring a live event we couldn't read: {}", e),
+            }
+        }
+    }
+}
+
+// The events as server-sent events, named by kind. A dashboard that falls too
+// far behind gets a `lagged` event saying how many it missed
+pub fn stream(
+    receiver: broadcast::Receiver<LiveEvent>,
+) -> impl Stream<Item = Result<sse::Event, Infallible>> {
+    BroadcastStream::new(receiver).map(|received| {
+        let event = match received {
+            Ok(event) => sse::Event::default()
+                .event(event.kind.as_str())
+                .json_data(&event)
+                .unwrap_or_else(|_| sse::Event::default().comment("unreadable event")),
+            Err(BroadcastStreamRecvError::Lagged(missed)) => sse::Event::default()
+                .event("lagged")
+                .data(missed.to_string()),
+        };
+        Ok(event)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use serde_json::json;
+    use tokio_stream::StreamExt;
+
+    use super::{stream, Live, LiveEvent, LiveKind};
+
+    fn event(nuid: &str) -> LiveEvent {
+        LiveEvent {
+            kind: LiveKind::Registered,
+            nuid: nuid.into(),
+            occurred_at: Utc::now(),
+            data: json!({"name": "Ada"}),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stream() {
+        let live = Live::new();
+        let mut events = Box::pin(stream(live.subscribe()));
+        live.sender.send(event("001453760")).unwrap();
+
+        let sent = events.next().await.unwrap().unwrap().to_string();
+        assert!(sent.starts_with("event:applicant.registered\n"));
+        assert!(sent.contains(r#""nuid":"001453760""#));
+    }
+
+    #[tokio::test]
+    async fn test_stream_lagged() {
+        let live = Live::new();
+        let mut events = Box::pin(stream(live.subscribe()));
+        for _ in 0..super::BACKLOG + 3 {
+            live.sender.send(event("001453760")).unwrap();
+        }
+
+        let sent = events.next().await.unwrap().unwrap().to_string();
+        assert!(sent.starts_with("event:lagged\ndata:3\n"));
+    }
+}
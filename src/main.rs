@@ -1,20 +1,49 @@
 extern crate pretty_env_logger;
 #[macro_use]
 extern crate log;
+use clap::{Parser, Subcommand};
 use sqlx::PgPool;
 
 use std::error::Error;
 
 use crate::config::get_configuration;
+use crate::endpoints::errors::ModelError;
 
 mod config;
 mod db;
+mod email;
 mod endpoints;
+mod jobs;
+mod live;
 mod model;
+mod sandbox;
+mod webhooks;
+
+#[derive(Parser)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the server (the default)
+    Serve,
+    /// Apply every cycle's retention policy now, or delete one applicant outright
+    Purge {
+        /// Only print what would be anonymized or deleted
+        #[arg(long)]
+        dry_run: bool,
+        /// Delete this applicant and their submissions, whatever their cycle's policy
+        #[arg(long)]
+        nuid: Option<String>,
+    },
+}
 
 // Gonna need to handle TLS certs here when I deploy - lets look at NGINX
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
+    let cli = Cli::parse();
     let _ = dotenv::dotenv();
     pretty_env_logger::init();
 
@@ -30,11 +59,80 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     sqlx::migrate!().run(&pool).await?;
 
+    if let Some(Command::Purge { dry_run, nuid }) = cli.command {
+        return purge(&pool, dry_run, nuid).await;
+    }
+
+    let webhooks = webhooks::Webhooks::new(configuration.webhooks.clone());
+    let mail = email::Mail::from_settings(&configuration.email)?;
+
+    info!("Starting job runner");
+    tokio::spawn(jobs::run(
+        pool.clone(),
+        jobs::JobContext::new(webhooks, mail),
+    ));
+
+    info!("Starting retention purges");
+    tokio::spawn(model::retention::run(pool.clone()));
+
+    let live = live::Live::new();
+    info!("Listening for live events");
+    tokio::spawn(live.clone().listen(pool.clone()));
+
+    // Only root can run programs as nobody in their own namespaces
+    let sandbox = match sandbox::Sandbox::new(configuration.sandbox) {
+        Ok(sandbox) => Some(sandbox),
+        Err(e) => {
+            warn!("Programs can't be run: {}", e);
+            None
+        }
+    };
+
+    // Cached between requests, and cleared whenever someone solves it
+    let leaderboards = model::leaderboard::Leaderboards::default();
+    tokio::spawn(leaderboards.clone().watch(live.subscribe()));
+
     info!("Starting submission server");
 
-    warp::serve(endpoints::end(Some(pool)))
-        .run(([0, 0, 0, 0], configuration.port()))
-        .await;
+    warp::serve(endpoints::end(
+        Some(pool),
+        &configuration,
+        live,
+        leaderboards,
+        sandbox,
+    ))
+    .run(([0, 0, 0, 0], configuration.port()))
+    .await;
+
+    Ok(())
+}
 
+async fn purge(pool: &PgPool, dry_run: bool, nuid: Option<String>) -> Result<(), Box<dyn Error>> {
+    let ctx = model::audit::RequestContext {
+        request_id: format!("purge-{}", uuid::Uuid::new_v4()),
+        ..Default::default()
+    };
+    let not_deleted = |nuid: &str, e: ModelError| match e {
+        ModelError::NoUserFound => format!("No applicant has NUID {}", nuid),
+        e => format!("Couldn't delete {}: {}", nuid, e),
+    };
+    let report = match nuid {
+        Some(nuid) if dry_run => model::retention::preview_delete(pool, &nuid)
+            .await
+            .map_err(|e| not_deleted(&nuid, e))?,
+        Some(nuid) => {
+            model::retention::delete_applicant(pool, &nuid, &ctx)
+                .await
+                .map_err(|e| not_deleted(&nuid, e))?;
+            model::retention::PurgeReport {
+                deleted: vec![nuid],
+                ..Default::default()
+            }
+        }
+        None => model::retention::purge(pool, dry_run)
+            .await
+            .map_err(|e| format!("Purge failed: {}", e))?,
+    };
+    println!("{}", serde_json::to_string_pretty(&report)?);
     Ok(())
 }
@@ -0,0 +1,169 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+use utoipa::ToSchema;
+
+use crate::config::AnalysisSettings;
+use crate::db;
+use crate::endpoints::errors::ModelError;
+use crate::model::hints::{self, HintUse};
+
+// Something about an applicant's submissions that a person should look at.
+// None of these are proof on their own - a shared IP might just be the library
+#[derive(Serialize, Debug, Clone, PartialEq, ToSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Flag {
+    // Submitted exactly what these applicants did, even though their challenge
+    // strings were different - at most one of them can have been right
+    SharedAnswer { shared_with: Vec<String> },
+    // These applicants all submitted from this IP within a few minutes
+    IpBurst { ip: String, applicants: Vec<String> },
+    // Solved it this many seconds after registering
+    FastSolve { seconds: f64 },
+}
+
+// What an admin sees about one applicant
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct ApplicantReview {
+    pub nuid: String,
+    pub name: String,
+    pub email: Option<String>,
+    pub cycle: String,
+    // new, reviewing, interviewing, accepted or rejected
+    pub status: String,
+    pub registered_at: DateTime<Utc>,
+    pub registration_ip: Option<String>,
+    pub registration_user_agent: Option<String>,
+    pub submissions: i64,
+    pub solved: bool,
+    pub flags: Vec<Flag>,
+    // Every hint they unlocked, and their score out of 100 after the penalties
+    pub hints: Vec<HintUse>,
+    pub score: u32,
+}
+
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct FlaggedApplicant {
+    pub nuid: String,
+    pub flags: Vec<Flag>,
+}
+
+pub async fn review(
+    pool: &PgPool,
+    settings: &AnalysisSettings,
+    nuid: &str,
+) -> Result<ApplicantReview, ModelError> {
+    let record = match db::analysis::get_review_db(pool, nuid).await {
+        Ok(Some(record)) => record,
+        Ok(None) => return Err(ModelError::NoUserFound),
+        Err(_) => return Err(ModelError::SqlError),
+    };
+    let flags = flags(pool, settings, Some(nuid), None)
+        .await?
+        .remove(nuid)
+        .unwrap_or_default();
+    let hints = hints::hint_uses(pool, nuid).await?;
+
+    Ok(ApplicantReview {
+        nuid: record.nuid,
+        name: record.applicant_name,
+        email: record.email,
+        cycle: record.cycle,
+        status: record.status,
+        registered_at: record.registration_time,
+        registration_ip: record.registration_ip,
+        registration_user_agent: record.registration_user_agent,
+        submissions: record.submissions,
+        solved: record.solved,
+        flags,
+        score: hints::score(&hints),
+        hints,
+    })
+}
+
+// Every flagged applicant, optionally just from one cycle
+pub async fn flagged(
+    pool: &PgPool,
+    settings: &AnalysisSettings,
+    cycle: Option<&str>,
+) -> Result<Vec<FlaggedApplicant>, ModelError> {
+    Ok(flags(pool, settings, None, cycle)
+        .await?
+        .into_iter()
+        .map(|(nuid, flags)| FlaggedApplicant { nuid, flags })
+        .collect())
+}
+
+async fn flags(
+    pool: &PgPool,
+    settings: &AnalysisSettings,
+    nuid: Option<&str>,
+    cycle: Option<&str>,
+) -> Result<BTreeMap<String, Vec<Flag>>, ModelError> {
+    let checked = async {
+        let shared = db::analysis::get_shared_answers_db(pool, nuid, cycle).await?;
+        let bursts = db::analysis::get_ip_bursts_db(
+            pool,
+            nuid,
+            cycle,
+            settings.burst.seconds as f64,
+            settings.burst.applicants,
+        )
+        .await?;
+        let fast =
+            db::analysis::get_fast_solves_db(pool, nuid, cycle, settings.fastest as f64).await?;
+        Ok::<_, sqlx::Error>((shared, bursts, fast))
+    };
+    let (shared, bursts, fast) = match checked.await {
+        Ok(found) => found,
+        Err(e) => {
+            error!("Failed to analyze submissions: {:?}", e);
+            return Err(ModelError::SqlError);
+        }
+    };
+
+    let mut flags: BTreeMap<String, Vec<Flag>> = BTreeMap::new();
+    for (nuid, shared_with) in shared {
+        flags
+            .entry(nuid)
+            .or_default()
+            .push(Flag::SharedAnswer { shared_with });
+    }
+    for (nuid, ip, applicants) in bursts {
+        flags
+            .entry(nuid)
+            .or_default()
+            .push(Flag::IpBurst { ip, applicants });
+    }
+    for (nuid, seconds) in fast {
+        flags
+            .entry(nuid)
+            .or_default()
+            .push(Flag::FastSolve { seconds });
+    }
+    Ok(flags)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::Flag;
+
+    #[test]
+    fn test_flags_are_tagged() {
+        let flag = Flag::SharedAnswer {
+            shared_with: vec!["001453761".into()],
+        };
+        assert_eq!(
+            serde_json::to_value(flag).unwrap(),
+            json!({"kind": "shared_answer", "shared_with": ["001453761"]})
+        );
+        assert_eq!(
+            serde_json::to_value(Flag::FastSolve { seconds: 4.5 }).unwrap(),
+            json!({"kind": "fast_solve", "seconds": 4.5})
+        );
+    }
+}
@@ -0,0 +1,213 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::PgPool;
+
+use crate::db;
+use crate::endpoints::errors::ModelError;
+use crate::jobs::{self, Job};
+use crate::model::audit::{self, Action, Actor, RequestContext};
+use crate::model::events::Event;
+
+// How many applicants fit on a page of the admin UI
+pub const PAGE_SIZE: i64 = 100;
+
+// Where an applicant is in review
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    New,
+    Reviewing,
+    Interviewing,
+    Accepted,
+    Rejected,
+}
+
+impl Status {
+    pub const ALL: [Status; 5] = [
+        Status::New,
+        Status::Reviewing,
+        Status::Interviewing,
+        Status::Accepted,
+        Status::Rejected,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Status::New => "new",
+            Status::Reviewing => "reviewing",
+            Status::Interviewing => "interviewing",
+            Status::Accepted => "accepted",
+            Status::Rejected => "rejected",
+        }
+    }
+
+    // Where review ends up - applicants are told about these
+    pub fn is_decision(&self) -> bool {
+        matches!(self, Status::Accepted | Status::Rejected)
+    }
+}
+
+// Everything's optional - leave it out to not filter on it
+#[derive(Debug, Default)]
+pub struct ApplicantFilter {
+    pub cycle: Option<String>,
+    pub status: Option<Status>,
+    pub solved: Option<bool>,
+    // Matches part of a name or NUID
+    pub search: Option<String>,
+    pub page: i64,
+}
+
+// A line of the applicant table
+#[derive(Serialize, Debug, Clone)]
+pub struct ApplicantRow {
+    pub nuid: String,
+    pub name: String,
+    pub email: Option<String>,
+    pub cycle: String,
+    pub status: String,
+    pub registered_at: DateTime<Utc>,
+    pub submissions: i64,
+    pub solved: bool,
+    pub last_submission: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SubmissionRecord {
+    pub submission_id: i32,
+    pub ok: bool,
+    pub submitted_at: DateTime<Utc>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Note {
+    pub note_id: i64,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+}
+
+pub async fn list(
+    pool: &PgPool,
+    filter: &ApplicantFilter,
+) -> Result<Vec<ApplicantRow>, ModelError> {
+    // The search is matched with ILIKE, so its wildcards have to be taken literally
+    let search = filter.search.as_ref().map(|search| {
+        let escaped = search
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_");
+        format!("%{}%", escaped)
+    });
+    match db::review::list_applicants_db(
+        pool,
+        filter.cycle.as_deref(),
+        filter.status.map(|status| status.as_str()),
+        filter.solved,
+        search.as_deref(),
+        PAGE_SIZE,
+        filter.page.max(0) * PAGE_SIZE,
+    )
+    .await
+    {
+        Ok(applicants) => Ok(applicants),
+        Err(e) => {
+            error!("Failed to list applicants: {:?}", e);
+            Err(ModelError::SqlError)
+        }
+    }
+}
+
+// Every submission, newest first
+pub async fn history(pool: &PgPool, nuid: &str) -> Result<Vec<SubmissionRecord>, ModelError> {
+    match db::review::get_submissions_db(pool, nuid).await {
+        Ok(submissions) => Ok(submissions),
+        Err(_) => Err(ModelError::SqlError),
+    }
+}
+
+pub async fn notes(pool: &PgPool, nuid: &str) -> Result<Vec<Note>, ModelError> {
+    match db::review::get_notes_db(pool, nuid).await {
+        Ok(notes) => Ok(notes),
+        Err(_) => Err(ModelError::SqlError),
+    }
+}
+
+pub async fn add_note(
+    pool: &PgPool,
+    nuid: &str,
+    body: &str,
+    ctx: &RequestContext,
+) -> Result<(), ModelError> {
+    if let Ok(None) = db::review::get_status_db(pool, nuid).await {
+        return Err(ModelError::NoUserFound);
+    }
+
+    let mut tx = pool.begin().await.map_err(|_| ModelError::SqlError)?;
+    let added = async {
+        let note_id = db::review::add_note_db(&mut tx, nuid, body).await?;
+        // Not the note itself - the audit log outlives it
+        let details = json!({ "note_id": note_id });
+        audit::record(
+            &mut tx,
+            ctx,
+            Action::Noted,
+            Actor::Admin,
+            Some(nuid),
+            details,
+        )
+        .await
+    };
+    if let Err(e) = added.await {
+        error!("Failed to add a note to {}: {:?}", nuid, e);
+        return Err(ModelError::SqlError);
+    }
+
+    tx.commit().await.map_err(|_| ModelError::SqlError)
+}
+
+pub async fn set_status(
+    pool: &PgPool,
+    nuid: &str,
+    status: Status,
+    ctx: &RequestContext,
+) -> Result<(), ModelError> {
+    let mut tx = pool.begin().await.map_err(|_| ModelError::SqlError)?;
+    let updated = async {
+        let previous = db::review::set_status_db(&mut tx, nuid, status.as_str()).await?;
+        if let Some(previous) = &previous {
+            let details = json!({ "from": previous, "to": status });
+            audit::record(
+                &mut tx,
+                ctx,
+                Action::StatusChanged,
+                Actor::Admin,
+                Some(nuid),
+                details,
+            )
+            .await?;
+            // Only when it changes, so saving the same decision twice doesn't
+            // email them twice
+            if status.is_decision() && previous != status.as_str() {
+                let notify = Job::Notify {
+                    event: Event::Decided,
+                    data: json!({ "nuid": nuid, "status": status }),
+                };
+                jobs::enqueue(&mut tx, &notify).await?;
+            }
+        }
+        Ok::<_, sqlx::Error>(previous)
+    };
+    match updated.await {
+        Ok(Some(_)) => {}
+        Ok(None) => return Err(ModelError::NoUserFound),
+        Err(e) => {
+            error!("Failed to set the status of {}: {:?}", nuid, e);
+            return Err(ModelError::SqlError);
+        }
+    }
+
+    tx.commit().await.map_err(|_| ModelError::SqlError)
+}
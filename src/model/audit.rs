@@ -0,0 +1,121 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{PgPool, Postgres, Transaction};
+use utoipa::ToSchema;
+
+use crate::db;
+use crate::endpoints::errors::ModelError;
+use crate::endpoints::messages::AuditQuery;
+
+// Where a request came from, for the audit trail. Built by the routes and
+// handed down to anything that changes state
+#[derive(Debug, Clone, Default)]
+pub struct RequestContext {
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub request_id: String,
+}
+
+// Everything that lands in the audit log
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Registered,
+    Submitted,
+    TokenRotated,
+    TokenRequested,
+    JobRetried,
+    Anonymized,
+    Deleted,
+    RetentionChanged,
+    StatusChanged,
+    Noted,
+    CycleCreated,
+    CycleActivated,
+    StagesChanged,
+    HintUnlocked,
+}
+
+impl Action {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Action::Registered => "applicant.registered",
+            Action::Submitted => "applicant.submitted",
+            Action::TokenRotated => "token.rotated",
+            Action::TokenRequested => "token.requested",
+            Action::JobRetried => "job.retried",
+            Action::Anonymized => "applicant.anonymized",
+            Action::Deleted => "applicant.deleted",
+            Action::RetentionChanged => "cycle.retention_changed",
+            Action::StatusChanged => "applicant.status_changed",
+            Action::Noted => "applicant.noted",
+            Action::CycleCreated => "cycle.created",
+            Action::CycleActivated => "cycle.activated",
+            Action::StagesChanged => "cycle.stages_changed",
+            Action::HintUnlocked => "applicant.hint_unlocked",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Actor {
+    Applicant,
+    Admin,
+    // The server itself, e.g. applying retention policies
+    System,
+}
+
+impl Actor {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Actor::Applicant => "applicant",
+            Actor::Admin => "admin",
+            Actor::System => "system",
+        }
+    }
+}
+
+// A row of the audit_events table
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct AuditEvent {
+    pub event_id: i64,
+    pub occurred_at: DateTime<Utc>,
+    pub action: String,
+    pub actor: String,
+    pub nuid: Option<String>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub request_id: Option<String>,
+    #[schema(value_type = Object)]
+    pub details: serde_json::Value,
+}
+
+// Goes in the same transaction as the change it describes, so there's never a
+// change without its audit event or the other way round
+pub async fn record(
+    tx: &mut Transaction<'_, Postgres>,
+    ctx: &RequestContext,
+    action: Action,
+    actor: Actor,
+    nuid: Option<&str>,
+    details: serde_json::Value,
+) -> Result<(), sqlx::Error> {
+    db::audit::record_event_db(tx, ctx, action.as_str(), actor.as_str(), nuid, &details).await
+}
+
+pub async fn get_events(pool: &PgPool, query: AuditQuery) -> Result<Vec<AuditEvent>, ModelError> {
+    let limit = query.limit.unwrap_or(50).clamp(1, 500);
+    match db::audit::get_events_db(
+        pool,
+        query.action,
+        query.actor,
+        query.nuid,
+        query.since,
+        query.until,
+        limit,
+    )
+    .await
+    {
+        Ok(events) => Ok(events),
+        Err(_) => Err(ModelError::SqlError),
+    }
+}
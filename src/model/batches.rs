@@ -0,0 +1,183 @@
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::db::{self, batches::NewBatch, transactions::NewSubmission};
+use crate::endpoints::errors::ModelError;
+use crate::endpoints::messages::{Answer, BatchResponse};
+use crate::model::audit::RequestContext;
+use crate::model::engine::{advance, record_submission, solution_hash, Graded};
+use crate::model::stages::{self, Challenge};
+use crate::model::tokens;
+
+// How a batch went. It only counts if every answer was right
+pub struct BatchGraded {
+    pub graded: Graded,
+    pub correct: u32,
+    pub total: u32,
+}
+
+// Every batch is fresh strings from new seeds, so answers to one are no use for
+// the next
+pub async fn start_batch(pool: &PgPool, token: Uuid) -> Result<BatchResponse, ModelError> {
+    let nuid = tokens::authenticate(pool, token).await?;
+    let current = match db::stages::get_current_stage_db(pool, &nuid).await {
+        Ok(Some(current)) => current,
+        Ok(None) => return Err(ModelError::NoUserFound),
+        Err(_) => return Err(ModelError::SqlError),
+    };
+    let challenge = stages::read_challenge(&current)?;
+    let (of, size, seconds) = match &challenge {
+        Challenge::Batch { of, size, seconds } => (of, *size, *seconds),
+        Challenge::Program { .. } => return Err(ModelError::ProgramRequired),
+        _ => return Err(ModelError::AnswerRequired),
+    };
+
+    let seeds: Vec<i64> = (0..size).map(|_| stages::new_seed()).collect();
+    let issued_at = Utc::now();
+    let batch = NewBatch {
+        batch_id: Uuid::new_v4(),
+        nuid: &nuid,
+        stage: current.stage,
+        seeds: &seeds,
+        issued_at,
+        deadline: issued_at + chrono::Duration::seconds(seconds as i64),
+    };
+    if let Err(e) = db::batches::write_batch_db(pool, &batch).await {
+        error!("Failed to start a batch for {}: {:?}", nuid, e);
+        return Err(ModelError::SqlError);
+    }
+
+    Ok(BatchResponse {
+        batch_id: batch.batch_id.to_string(),
+        challenge_strings: seeds.iter().map(|&seed| of.generate(seed)).collect(),
+        task: challenge.describe(),
+        seconds,
+    })
+}
+
+// Only for the stage they're on, and only once - a wrong batch means starting
+// a new one
+pub async fn answer_batch(
+    pool: PgPool,
+    token: Uuid,
+    batch_id: Uuid,
+    answers: &[Answer],
+    ctx: &RequestContext,
+) -> Result<BatchGraded, ModelError> {
+    let nuid = tokens::authenticate(&pool, token).await?;
+    let answered_at = Utc::now();
+
+    let mut tx = pool.begin().await.map_err(|_| ModelError::SqlError)?;
+
+    // Locked, so two correct batches can't both move them on
+    let current = match db::stages::get_current_stage_db(&mut tx, &nuid).await {
+        Ok(Some(current)) => current,
+        Ok(None) => return Err(ModelError::NoUserFound),
+        Err(_) => return Err(ModelError::SqlError),
+    };
+    let batch = match db::batches::get_batch_db(&mut tx, batch_id, &nuid).await {
+        Ok(Some(batch)) if batch.stage == current.stage => batch,
+        Ok(_) => return Err(ModelError::BatchNotFound),
+        Err(_) => return Err(ModelError::SqlError),
+    };
+    if batch.answered_at.is_some() {
+        return Err(ModelError::BatchAnswered);
+    }
+    if answered_at > batch.deadline {
+        return Err(ModelError::BatchExpired);
+    }
+    let of = match stages::read_challenge(&current)? {
+        Challenge::Batch { of, .. } => of,
+        _ => return Err(ModelError::BatchNotFound),
+    };
+
+    // Missing answers are wrong, and extra ones are ignored
+    let correct = {
+        let (seeds, given) = (batch.seeds.clone(), answers.to_vec());
+        stages::off_thread(move || {
+            seeds
+                .iter()
+                .zip(&given)
+                .filter(|(&seed, answer)| of.verify(&of.generate(seed), answer))
+                .count() as u32
+        })
+        .await?
+    };
+    let total = batch.seeds.len() as u32;
+    let ok = correct == total;
+
+    let stages = stages::get_stages(&mut tx, &current.cycle).await?;
+    let hash = batch_hash(answers);
+    let submission = NewSubmission {
+        nuid: &nuid,
+        stage: current.stage,
+        ok,
+        solution_hash: &hash,
+        ip: ctx.ip.as_deref(),
+        user_agent: ctx.user_agent.as_deref(),
+    };
+    let recorded = async {
+        db::batches::answer_batch_db(&mut tx, batch_id, answered_at).await?;
+        let (_, submitted_at) = record_submission(&mut tx, &submission, None, ctx).await?;
+        if ok {
+            advance(&mut tx, &nuid, current.stage, &stages, submitted_at).await?;
+        }
+        Ok::<_, sqlx::Error>(())
+    };
+    if let Err(e) = recorded.await {
+        error!("Failed to record the batch: {:?}", e);
+        return Err(ModelError::SqlError);
+    }
+    tx.commit().await.map_err(|_| ModelError::SqlError)?;
+
+    Ok(BatchGraded {
+        graded: Graded {
+            ok,
+            stage: current.stage as u32,
+            stages: (stages.len() as u32).max(current.stage as u32),
+        },
+        correct,
+        total,
+    })
+}
+
+// Built from each answer's own hash, so the order of k-mer counts doesn't matter
+fn batch_hash(answers: &[Answer]) -> String {
+    let mut hasher = Sha256::new();
+    for answer in answers {
+        hasher.update(solution_hash(answer));
+    }
+    hex::encode(hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::batch_hash;
+    use crate::endpoints::messages::Answer;
+
+    #[test]
+    fn test_batch_hash() {
+        let counts = |pairs: &[(&str, u64)]| {
+            Answer::Counts(
+                pairs
+                    .iter()
+                    .map(|&(kmer, count)| (kmer.to_string(), count))
+                    .collect::<HashMap<_, _>>(),
+            )
+        };
+        let sequence = Answer::Sequence("ACGT".to_string());
+
+        assert_eq!(
+            batch_hash(&[counts(&[("ACG", 1), ("CGT", 2)]), sequence.clone()]),
+            batch_hash(&[counts(&[("CGT", 2), ("ACG", 1)]), sequence.clone()])
+        );
+        assert_ne!(
+            batch_hash(&[counts(&[("ACG", 1)]), sequence.clone()]),
+            batch_hash(&[sequence, counts(&[("ACG", 1)])])
+        );
+    }
+}
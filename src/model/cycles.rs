@@ -0,0 +1,134 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::json;
+use sqlx::PgPool;
+use utoipa::ToSchema;
+
+use crate::db;
+use crate::endpoints::errors::ModelError;
+use crate::endpoints::messages::RetentionPolicy;
+use crate::model::audit::{self, Action, Actor, RequestContext};
+
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct Cycle {
+    pub name: String,
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+    // Days after registration that applicants are kept for - forever if missing
+    pub retention_days: Option<i32>,
+    // anonymize or delete
+    pub retention_action: String,
+}
+
+pub async fn get_cycles(pool: &PgPool) -> Result<Vec<Cycle>, ModelError> {
+    match db::retention::get_cycles_db(pool).await {
+        Ok(cycles) => Ok(cycles),
+        Err(_) => Err(ModelError::SqlError),
+    }
+}
+
+pub async fn create_cycle(
+    pool: &PgPool,
+    name: &str,
+    ctx: &RequestContext,
+) -> Result<(), ModelError> {
+    let mut tx = pool.begin().await.map_err(|_| ModelError::SqlError)?;
+    let created = async {
+        let created = db::review::create_cycle_db(&mut tx, name, Utc::now()).await?;
+        if created {
+            let details = json!({ "cycle": name });
+            audit::record(
+                &mut tx,
+                ctx,
+                Action::CycleCreated,
+                Actor::Admin,
+                None,
+                details,
+            )
+            .await?;
+        }
+        Ok::<_, sqlx::Error>(created)
+    };
+    match created.await {
+        Ok(true) => {}
+        Ok(false) => return Err(ModelError::DuplicateCycle),
+        Err(e) => {
+            error!("Failed to create cycle {}: {:?}", name, e);
+            return Err(ModelError::SqlError);
+        }
+    }
+
+    tx.commit().await.map_err(|_| ModelError::SqlError)
+}
+
+// New registrations join this cycle from now on
+pub async fn activate_cycle(
+    pool: &PgPool,
+    name: &str,
+    ctx: &RequestContext,
+) -> Result<(), ModelError> {
+    let mut tx = pool.begin().await.map_err(|_| ModelError::SqlError)?;
+    let activated = async {
+        let activated = db::review::activate_cycle_db(&mut tx, name).await?;
+        if activated {
+            let details = json!({ "cycle": name });
+            audit::record(
+                &mut tx,
+                ctx,
+                Action::CycleActivated,
+                Actor::Admin,
+                None,
+                details,
+            )
+            .await?;
+        }
+        Ok::<_, sqlx::Error>(activated)
+    };
+    match activated.await {
+        Ok(true) => {}
+        Ok(false) => return Err(ModelError::CycleNotFound),
+        Err(e) => {
+            error!("Failed to activate cycle {}: {:?}", name, e);
+            return Err(ModelError::SqlError);
+        }
+    }
+
+    tx.commit().await.map_err(|_| ModelError::SqlError)
+}
+
+pub async fn set_retention(
+    pool: &PgPool,
+    cycle: &str,
+    policy: &RetentionPolicy,
+    ctx: &RequestContext,
+) -> Result<(), ModelError> {
+    let mut tx = pool.begin().await.map_err(|_| ModelError::SqlError)?;
+    let updated = async {
+        let updated =
+            db::retention::set_retention_db(&mut tx, cycle, policy.days, policy.action.as_str())
+                .await?;
+        if updated {
+            let details = json!({ "cycle": cycle, "days": policy.days, "action": policy.action });
+            audit::record(
+                &mut tx,
+                ctx,
+                Action::RetentionChanged,
+                Actor::Admin,
+                None,
+                details,
+            )
+            .await?;
+        }
+        Ok::<_, sqlx::Error>(updated)
+    };
+    match updated.await {
+        Ok(true) => {}
+        Ok(false) => return Err(ModelError::CycleNotFound),
+        Err(e) => {
+            error!("Failed to set the retention policy for {}: {:?}", cycle, e);
+            return Err(ModelError::SqlError);
+        }
+    }
+
+    tx.commit().await.map_err(|_| ModelError::SqlError)
+}
@@ -1,95 +1,380 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::Duration;
 
-use sqlx::PgPool;
+use chrono::{DateTime, Utc};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use sqlx::{PgPool, Postgres, Transaction};
 
 use uuid::Uuid;
 
 use crate::{
-    db::{self},
+    db::{
+        self,
+        stages::NewStage,
+        transactions::{NewApplicant, NewSubmission},
+    },
     endpoints::errors::ModelError,
+    endpoints::messages::{Answer, GetChallengeString},
+    jobs::{self, Job},
+    live::{self, LiveKind},
 };
 
-use super::types::Applicant;
+use super::audit::{self, Action, Actor, RequestContext};
+use super::events::Event;
+use super::stages::{self, Challenge, Stage};
+use super::tokens;
+
+use super::types::{Applicant, StageTiming};
 
 pub async fn get_applicants(
     pool: PgPool,
     applicants: &[String],
 ) -> Result<Vec<Applicant>, ModelError> {
-    match db::transactions::get_applicants_db(&pool, applicants).await {
-        Ok(vec) => Ok(vec
-            .iter()
-            .map(|(nuid, name, reg_time, sub_time, ok)| {
-                let time_to_completion = match sub_time.signed_duration_since(*reg_time).to_std() {
-                    Ok(d) => d,
-                    Err(_) => std::time::Duration::ZERO,
-                };
-                Applicant {
-                    nuid: nuid.clone(),
-                    name: name.clone(),
-                    time_to_completion,
-                    ok: *ok,
-                }
-            })
-            .collect()),
-        Err(_) => Err(ModelError::SqlError),
+    let found = db::transactions::get_applicants_db(&pool, applicants).await;
+    let stages = db::stages::get_stage_records_db(&pool, applicants).await;
+    let (found, stages) = match (found, stages) {
+        (Ok(found), Ok(stages)) => (found, stages),
+        _ => return Err(ModelError::SqlError),
+    };
+
+    let mut timings: HashMap<String, Vec<StageTiming>> = HashMap::new();
+    for record in stages {
+        let since = |start: DateTime<Utc>, end: DateTime<Utc>| {
+            end.signed_duration_since(start)
+                .to_std()
+                .unwrap_or(Duration::ZERO)
+        };
+        timings.entry(record.nuid).or_default().push(StageTiming {
+            stage: record.stage as u32,
+            unlocked_after: since(record.registration_time, record.unlocked_at),
+            time_to_solve: record
+                .solved_at
+                .map(|solved_at| since(record.unlocked_at, solved_at)),
+        });
     }
+
+    Ok(found
+        .iter()
+        .map(|(nuid, name, reg_time, sub_time, ok)| {
+            let time_to_completion = match sub_time.signed_duration_since(*reg_time).to_std() {
+                Ok(d) => d,
+                Err(_) => Duration::ZERO,
+            };
+            Applicant {
+                nuid: nuid.clone(),
+                name: name.clone(),
+                time_to_completion,
+                ok: *ok,
+                stages: timings.remove(nuid).unwrap_or_default(),
+            }
+        })
+        .collect())
 }
+
 pub async fn register_user(
     pool: PgPool,
     name: String,
     nuid: String,
+    email: Option<String>,
+    display_name: Option<String>,
+    token_lifetime: Option<chrono::Duration>,
+    ctx: &RequestContext,
 ) -> Result<(Uuid, String), ModelError> {
-    let token = Uuid::new_v4();
-    let challenge_str = generate_challenge_string();
-    let soln = find_kmers(&challenge_str, 3);
+    let notify = Job::Notify {
+        event: Event::Registered,
+        data: json!({ "nuid": nuid, "name": name }),
+    };
+    let applicant = NewApplicant {
+        name,
+        nuid,
+        email,
+        display_name,
+        token: Uuid::new_v4(),
+        token_expires_at: tokens::expiry(token_lifetime),
+        ip: ctx.ip.clone(),
+        user_agent: ctx.user_agent.clone(),
+    };
 
-    match db::transactions::register_user_db(&pool, token, name, nuid, &challenge_str, soln).await {
-        Ok(()) => Ok((token, challenge_str)),
+    let mut tx = pool.begin().await.map_err(|_| ModelError::SqlError)?;
+    let (cycle, registered_at) = match db::transactions::register_user_db(&mut tx, &applicant).await
+    {
+        Ok(registered) => registered,
         // there's a bunch of different ways that this can fail, I should probably
         // handle the error -
-        Err(_e) => Err(ModelError::DuplicateUser),
+        Err(_e) => return Err(ModelError::DuplicateUser),
+    };
+
+    // Everyone starts on the first stage
+    let first = &stages::get_stages(&mut tx, &cycle).await?[0].challenge;
+    let challenge_string = match add_stage(&mut tx, &applicant.nuid, 1, first, registered_at).await
+    {
+        Ok(challenge_string) => challenge_string,
+        Err(e) => {
+            error!("Failed to add the first stage: {:?}", e);
+            return Err(ModelError::SqlError);
+        }
+    };
+
+    if let Err(e) = jobs::enqueue(&mut tx, &notify).await {
+        error!("Failed to queue the registration notification: {:?}", e);
+        return Err(ModelError::SqlError);
+    }
+
+    let audited = audit::record(
+        &mut tx,
+        ctx,
+        Action::Registered,
+        Actor::Applicant,
+        Some(&applicant.nuid),
+        json!({}),
+    );
+    if let Err(e) = audited.await {
+        error!("Failed to audit the registration: {:?}", e);
+        return Err(ModelError::SqlError);
     }
-}
 
-pub async fn retreive_token(pool: PgPool, nuid: &String) -> Result<Uuid, ModelError> {
-    match db::transactions::retreive_token_db(&pool, nuid).await {
-        Ok(token) => Ok(token),
-        Err(_) => Err(ModelError::NoUserFound),
+    let data = json!({ "name": applicant.name });
+    if let Err(e) = live::publish(&mut tx, LiveKind::Registered, &applicant.nuid, data).await {
+        error!("Failed to publish the registration: {:?}", e);
+        return Err(ModelError::SqlError);
     }
+
+    match tx.commit().await {
+        Ok(()) => Ok((applicant.token, challenge_string)),
+        Err(_) => Err(ModelError::SqlError),
+    }
+}
+
+// The stage they're on - the last one they solved, once they've solved them all
+pub async fn retreive_challenge(
+    pool: &PgPool,
+    token: Uuid,
+) -> Result<GetChallengeString, ModelError> {
+    tokens::authenticate(pool, token).await?;
+    let nuid = match db::transactions::retreive_challenge_db(pool, token).await {
+        Ok(nuid) => nuid,
+        Err(_) => return Err(ModelError::NoUserFound),
+    };
+    let current = match db::stages::get_current_stage_db(pool, &nuid).await {
+        Ok(Some(current)) => current,
+        Ok(None) => return Err(ModelError::NoUserFound),
+        Err(_) => return Err(ModelError::SqlError),
+    };
+    let stages = stages::get_stages(pool, &current.cycle).await?.len() as u32;
+    let task = stages::read_challenge(&current)?.describe();
+
+    Ok(GetChallengeString {
+        challenge_string: current.challenge_string,
+        task,
+        stage: current.stage as u32,
+        // They stay on their last stage if the cycle loses some after they unlock it
+        stages: stages.max(current.stage as u32),
+        complete: current.solved_at.is_some(),
+    })
+}
+
+// How a submission went, and which stage it was for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Graded {
+    pub ok: bool,
+    pub stage: u32,
+    pub stages: u32,
 }
 
-pub async fn retreive_challenge(pool: &PgPool, token: Uuid) -> Result<String, ModelError> {
-    match db::transactions::retreive_challenge_db(pool, token).await {
-        Ok(challenge) => Ok(challenge),
-        Err(_) => Err(ModelError::NoUserFound),
+impl Graded {
+    // Solving anything but the last stage unlocks the next one
+    pub fn unlocked(&self) -> Option<u32> {
+        (self.ok && self.stage < self.stages).then_some(self.stage + 1)
     }
 }
 
+// Grades a submission against the stage they're on and records it. With an
+// idempotency key, a retry of a submission we've already graded gets the same
+// answer back without being recorded a second time
 pub async fn check_solution(
     pool: PgPool,
     token: Uuid,
-    given_soln: &HashMap<String, u64>,
-) -> Result<bool, ModelError> {
-    // Check if the solution is correct - write the row to the solutions table
-    match db::transactions::retreive_soln(&pool, token).await {
-        Ok((soln, nuid)) => {
-            let ok = soln == *given_soln;
-            if let Err(_e) = db::transactions::write_submission(pool, nuid, ok).await {
-                return Err(ModelError::SqlError);
+    given_soln: &Answer,
+    idempotency_key: Option<String>,
+    ctx: &RequestContext,
+) -> Result<Graded, ModelError> {
+    let nuid = tokens::authenticate(&pool, token).await?;
+    let hash = solution_hash(given_soln);
+
+    let mut tx = pool.begin().await.map_err(|_| ModelError::SqlError)?;
+
+    // Locked, so two correct submissions can't both move them on
+    let current = match db::stages::get_current_stage_db(&mut tx, &nuid).await {
+        Ok(Some(current)) => current,
+        Ok(None) => return Err(ModelError::NoUserFound),
+        Err(_) => return Err(ModelError::SqlError),
+    };
+    let stages = stages::get_stages(&mut tx, &current.cycle).await?;
+    let stage_count = (stages.len() as u32).max(current.stage as u32);
+
+    if let Some(key) = &idempotency_key {
+        let claimed = db::idempotency::claim_key_db(&mut tx, &nuid, key, &hash)
+            .await
+            .map_err(|_| ModelError::SqlError)?;
+        if !claimed {
+            let (first_hash, first_ok, first_stage) =
+                db::idempotency::get_key_db(&mut tx, &nuid, key)
+                    .await
+                    .map_err(|_| ModelError::SqlError)?;
+            if first_hash != hash {
+                return Err(ModelError::IdempotencyKeyReused);
             }
-            Ok(ok)
+            info!("Replaying submission from {} for key {}", nuid, key);
+            return Ok(Graded {
+                ok: first_ok,
+                stage: first_stage as u32,
+                stages: stage_count,
+            });
+        }
+    }
+
+    let ok = {
+        let (current, given) = (current.clone(), given_soln.clone());
+        stages::off_thread(move || stages::grade(&current, &given)).await??
+    };
+    let submission = NewSubmission {
+        nuid: &nuid,
+        stage: current.stage,
+        ok,
+        solution_hash: &hash,
+        ip: ctx.ip.as_deref(),
+        user_agent: ctx.user_agent.as_deref(),
+    };
+    let recorded = async {
+        let (_, submitted_at) =
+            record_submission(&mut tx, &submission, idempotency_key.as_deref(), ctx).await?;
+        if ok {
+            advance(&mut tx, &nuid, current.stage, &stages, submitted_at).await?;
         }
-        Err(_) => Err(ModelError::NoUserFound),
+        Ok::<_, sqlx::Error>(())
+    };
+    if let Err(e) = recorded.await {
+        error!("Failed to record the submission: {:?}", e);
+        return Err(ModelError::SqlError);
+    }
+
+    match tx.commit().await {
+        Ok(()) => Ok(Graded {
+            ok,
+            stage: current.stage as u32,
+            stages: stage_count,
+        }),
+        Err(_) => Err(ModelError::SqlError),
     }
 }
 
-fn generate_challenge_string() -> String {
-    let charset = "ACTG";
-    random_string::generate(100, charset)
+pub(super) async fn record_submission(
+    tx: &mut Transaction<'_, Postgres>,
+    submission: &NewSubmission<'_>,
+    idempotency_key: Option<&str>,
+    ctx: &RequestContext,
+) -> Result<(i32, DateTime<Utc>), sqlx::Error> {
+    let nuid = submission.nuid;
+    let (submission_id, submitted_at) = db::transactions::write_submission(tx, submission).await?;
+    if let Some(key) = idempotency_key {
+        db::idempotency::record_key_db(tx, nuid, key, submission_id).await?;
+    }
+    let details = json!({
+        "submission_id": submission_id,
+        "stage": submission.stage,
+        "ok": submission.ok,
+    });
+    audit::record(
+        tx,
+        ctx,
+        Action::Submitted,
+        Actor::Applicant,
+        Some(nuid),
+        details.clone(),
+    )
+    .await?;
+    live::publish(tx, LiveKind::Submitted, nuid, details).await?;
+    Ok((submission_id, submitted_at))
+}
+
+// Unlocks the stage after the one they just solved, or marks them done if that
+// was the last. Solving a stage they'd already solved changes nothing. Goes by
+// the submission's time, so stage timings line up with the submissions
+pub(super) async fn advance(
+    tx: &mut Transaction<'_, Postgres>,
+    nuid: &str,
+    solved: i32,
+    stages: &[Stage],
+    solved_at: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    if !db::stages::solve_stage_db(tx, nuid, solved, solved_at).await? {
+        return Ok(());
+    }
+
+    // Stages count from 1, so the next one is at the index of this one
+    if let Some(next) = stages.get(solved as usize) {
+        add_stage(tx, nuid, solved + 1, &next.challenge, solved_at).await?;
+        return Ok(());
+    }
+
+    if db::stages::complete_db(tx, nuid, solved_at).await? {
+        live::publish(tx, LiveKind::Solved, nuid, json!({})).await?;
+        let notify = Job::Notify {
+            event: Event::Solved,
+            data: json!({ "nuid": nuid }),
+        };
+        jobs::enqueue(tx, &notify).await?;
+    }
+    Ok(())
+}
+
+// Generates the stage from a fresh seed and hands back its challenge string
+async fn add_stage(
+    tx: &mut Transaction<'_, Postgres>,
+    nuid: &str,
+    stage: i32,
+    challenge: &Challenge,
+    unlocked_at: DateTime<Utc>,
+) -> Result<String, sqlx::Error> {
+    let seed = stages::new_seed();
+    let challenge_string = challenge.generate(seed);
+    let stage = NewStage {
+        nuid,
+        stage,
+        challenge: &challenge.to_stored(),
+        seed,
+        challenge_string: &challenge_string,
+        unlocked_at,
+    };
+    db::stages::add_stage_db(tx, &stage).await?;
+    Ok(challenge_string)
+}
+
+// The same solution always hashes the same, whatever order the map is in
+pub(super) fn solution_hash(soln: &Answer) -> String {
+    let body = match soln {
+        Answer::Counts(counts) => serde_json::to_vec(&counts.iter().collect::<BTreeMap<_, _>>()),
+        other => serde_json::to_vec(other),
+    }
+    .expect("solutions always serialize");
+    hex::encode(Sha256::digest(body))
+}
+
+// ChaCha8 rather than rand's StdRng, which is free to change algorithm between
+// versions - a seed has to give the same string for as long as it's stored
+pub(crate) fn generate_challenge_string(seed: u64, length: usize) -> String {
+    let charset = ['A', 'C', 'T', 'G'];
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    (0..length)
+        .map(|_| charset[rng.gen_range(0..charset.len())])
+        .collect()
 }
 
 // Return the kmers as a map from strings of length k to
-fn find_kmers(challenge_str: &String, k: usize) -> HashMap<String, u64> {
+pub(crate) fn find_kmers(challenge_str: &str, k: usize) -> HashMap<String, u64> {
     let mut start_ind = 0;
     let mut soln: HashMap<String, u64> = HashMap::new();
     while start_ind + k <= challenge_str.len() {
@@ -103,16 +388,67 @@ fn find_kmers(challenge_str: &String, k: usize) -> HashMap<String, u64> {
     soln
 }
 
+pub(crate) fn reverse_complement(challenge_str: &str) -> String {
+    challenge_str
+        .chars()
+        .rev()
+        .map(|base| match base {
+            'A' => 'T',
+            'T' => 'A',
+            'C' => 'G',
+            'G' => 'C',
+            other => other,
+        })
+        .collect()
+}
+
+// The fraction of each window that's G or C - the last window is whatever's left
+pub(crate) fn gc_content(challenge_str: &str, window: usize) -> Vec<f64> {
+    challenge_str
+        .as_bytes()
+        .chunks(window)
+        .map(|chunk| {
+            let gc = chunk.iter().filter(|&&b| b == b'G' || b == b'C').count();
+            gc as f64 / chunk.len() as f64
+        })
+        .collect()
+}
+
+// The first of the longest substrings that show up at least twice, overlapping
+// or not. Empty if nothing repeats
+pub(crate) fn longest_repeat(challenge_str: &str) -> &str {
+    // If some substring of length n repeats, so does one of every length under
+    // n. In a random string the longest repeat is only a handful of letters, so
+    // counting up from one stops long before a search over every length would
+    let repeat_of = |len: usize| {
+        let mut seen = HashSet::new();
+        (0..=challenge_str.len().saturating_sub(len))
+            .map(|start| &challenge_str[start..start + len])
+            .find(|window| !seen.insert(*window))
+    };
+    let mut longest = "";
+    for len in 1..challenge_str.len() {
+        match repeat_of(len) {
+            Some(repeat) => longest = repeat,
+            None => break,
+        }
+    }
+    longest
+}
+
 #[cfg(test)]
 mod tests {
     use std::io::Error;
 
     use super::find_kmers;
     use super::generate_challenge_string;
+    use super::solution_hash;
+    use super::{gc_content, longest_repeat, reverse_complement};
+    use crate::endpoints::messages::Answer;
 
     #[test]
     fn test_rand_str() -> Result<(), Error> {
-        assert!(generate_challenge_string().len() == 100);
+        assert!(generate_challenge_string(0, 100).len() == 100);
         Ok(())
     }
 
@@ -136,7 +472,6 @@ mod tests {
 
     #[test]
     fn test_long_challenge_string() -> Result<(), Error> {
-        #[macro_export]
         macro_rules! fuck_your_strings {
             ($(($key:expr, $value: expr),)+) => {
                 {
@@ -167,4 +502,50 @@ mod tests {
         assert_eq!(soln, correct_soln);
         Ok(())
     }
+
+    #[test]
+    fn test_solution_hash() {
+        let soln = find_kmers("ACTGACTGAC", 3);
+        let mut reordered = std::collections::HashMap::new();
+        let mut kmers: Vec<_> = soln.iter().collect();
+        kmers.reverse();
+        for (kmer, count) in kmers {
+            reordered.insert(kmer.clone(), *count);
+        }
+
+        assert_eq!(
+            solution_hash(&Answer::Counts(soln.clone())),
+            solution_hash(&Answer::Counts(reordered))
+        );
+        assert_ne!(
+            solution_hash(&Answer::Counts(soln)),
+            solution_hash(&Answer::Counts(find_kmers("ACTGACTGAA", 3)))
+        );
+    }
+
+    #[test]
+    fn test_reverse_complement() {
+        assert_eq!(reverse_complement("AACGT"), "ACGTT");
+        assert_eq!(reverse_complement(""), "");
+    }
+
+    #[test]
+    fn test_gc_content() {
+        assert_eq!(gc_content("GCAT", 2), vec![1.0, 0.0]);
+        assert_eq!(gc_content("GCATG", 2), vec![1.0, 0.0, 1.0]);
+        assert_eq!(gc_content("ACGTAC", 6), vec![0.5]);
+    }
+
+    #[test]
+    fn test_longest_repeat() {
+        assert_eq!(longest_repeat("ACGTTACGA"), "ACG");
+        // Overlapping counts
+        assert_eq!(longest_repeat("AAAA"), "AAA");
+        assert_eq!(longest_repeat("ACGT"), "");
+        assert_eq!(longest_repeat("A"), "");
+        assert_eq!(longest_repeat(""), "");
+        // As long as validation lets a string get, a repeat is still short
+        let long = generate_challenge_string(0, 10_000);
+        assert!((8..20).contains(&longest_repeat(&long).len()));
+    }
 }
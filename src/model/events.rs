@@ -0,0 +1,28 @@
+use serde::{Deserialize, Serialize};
+
+// The things that can happen to an applicant that the rest of the system
+// (webhooks, for one) reacts to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Event {
+    #[serde(rename = "applicant.registered")]
+    Registered,
+    #[serde(rename = "applicant.solved")]
+    Solved,
+    // Accepted or rejected
+    #[serde(rename = "applicant.decided")]
+    Decided,
+    // Asked for their token to be emailed to them again
+    #[serde(rename = "applicant.token_requested")]
+    TokenRequested,
+}
+
+impl Event {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Event::Registered => "applicant.registered",
+            Event::Solved => "applicant.solved",
+            Event::Decided => "applicant.decided",
+            Event::TokenRequested => "applicant.token_requested",
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::json;
+use sqlx::PgPool;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::db;
+use crate::endpoints::errors::ModelError;
+use crate::endpoints::messages::HintResponse;
+use crate::model::audit::{self, Action, Actor, RequestContext};
+use crate::model::stages;
+use crate::model::tokens;
+
+// A hint an applicant unlocked, for reviewers
+#[derive(Serialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct HintUse {
+    pub stage: i32,
+    // Counting from 1, in the order the stage gives them out
+    pub hint: i32,
+    // Points it took off their score
+    pub penalty: i32,
+    pub unlocked_at: DateTime<Utc>,
+}
+
+// Hands back every hint they've unlocked for the stage they're on, unlocking
+// the next one first if there is one. A solved stage doesn't unlock any more
+pub async fn unlock_hint(
+    pool: PgPool,
+    token: Uuid,
+    penalty: i32,
+    ctx: &RequestContext,
+) -> Result<HintResponse, ModelError> {
+    let nuid = tokens::authenticate(&pool, token).await?;
+
+    let mut tx = pool.begin().await.map_err(|_| ModelError::SqlError)?;
+
+    // Locked, so two requests at once can't both unlock the same hint
+    let current = match db::stages::get_current_stage_db(&mut tx, &nuid).await {
+        Ok(Some(current)) => current,
+        Ok(None) => return Err(ModelError::NoUserFound),
+        Err(_) => return Err(ModelError::SqlError),
+    };
+    let hints = stages::get_stages(&mut tx, &current.cycle)
+        .await?
+        .into_iter()
+        .nth(current.stage as usize - 1)
+        .map(|stage| stage.hints)
+        .unwrap_or_default();
+
+    let unlocked = async {
+        let unlocked = db::hints::count_hints_db(&mut tx, &nuid, current.stage).await? as usize;
+        if unlocked >= hints.len() || current.solved_at.is_some() {
+            return Ok(unlocked);
+        }
+        let hint = unlocked as i32 + 1;
+        db::hints::unlock_hint_db(&mut tx, &nuid, current.stage, hint, penalty, Utc::now()).await?;
+        let details = json!({ "stage": current.stage, "hint": hint, "penalty": penalty });
+        audit::record(
+            &mut tx,
+            ctx,
+            Action::HintUnlocked,
+            Actor::Applicant,
+            Some(&nuid),
+            details,
+        )
+        .await?;
+        Ok::<_, sqlx::Error>(unlocked + 1)
+    };
+    let unlocked = match unlocked.await {
+        Ok(unlocked) => unlocked.min(hints.len()),
+        Err(e) => {
+            error!("Failed to unlock a hint for {}: {:?}", nuid, e);
+            return Err(ModelError::SqlError);
+        }
+    };
+    tx.commit().await.map_err(|_| ModelError::SqlError)?;
+
+    Ok(HintResponse {
+        stage: current.stage as u32,
+        remaining: (hints.len() - unlocked) as u32,
+        hints: hints.into_iter().take(unlocked).collect(),
+        // Never negative - the settings keep it between 0 and 100
+        penalty: penalty as u32,
+    })
+}
+
+pub async fn hint_uses(pool: &PgPool, nuid: &str) -> Result<Vec<HintUse>, ModelError> {
+    db::hints::get_hint_uses_db(pool, nuid).await.map_err(|e| {
+        error!("Failed to get the hints {} unlocked: {:?}", nuid, e);
+        ModelError::SqlError
+    })
+}
+
+// Out of 100, less the penalty for every hint they unlocked
+pub fn score(hints: &[HintUse]) -> u32 {
+    let penalties: i64 = hints.iter().map(|hint| hint.penalty as i64).sum();
+    (100 - penalties).max(0) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::{score, HintUse};
+
+    #[test]
+    fn test_score() {
+        let hint = |penalty| HintUse {
+            stage: 1,
+            hint: 1,
+            penalty,
+            unlocked_at: Utc::now(),
+        };
+        assert_eq!(score(&[]), 100);
+        assert_eq!(score(&[hint(10), hint(0), hint(15)]), 75);
+        assert_eq!(score(&[hint(60), hint(60)]), 0);
+    }
+}
@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use sqlx::PgPool;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+pub use api_types::types::{Leaderboard, LeaderboardEntry};
+
+use crate::db;
+use crate::endpoints::errors::ModelError;
+use crate::live::{LiveEvent, LiveKind};
+
+const MAX_ENTRIES: i64 = 100;
+// Solves clear the cache as they happen, so this only has to catch what they
+// don't - someone deleting their data, or a new cycle going active
+const FRESH_FOR: Duration = Duration::from_secs(60);
+
+// Built leaderboards by the cycle asked for (None being the active one). Cheap
+// to clone, and every clone shares the cache
+#[derive(Clone, Default)]
+pub struct Leaderboards {
+    cache: Arc<Mutex<Cache>>,
+}
+
+#[derive(Default)]
+struct Cache {
+    boards: HashMap<Option<String>, (Instant, Leaderboard)>,
+    // Bumped on every invalidation, so a board that was being built while
+    // someone solved isn't cached without them
+    generation: u64,
+}
+
+impl Leaderboards {
+    pub async fn get(&self, pool: &PgPool, cycle: Option<&str>) -> Result<Leaderboard, ModelError> {
+        let key = cycle.map(String::from);
+        let generation = {
+            let cache = self.cache.lock().unwrap();
+            if let Some((built_at, board)) = cache.boards.get(&key) {
+                if built_at.elapsed() < FRESH_FOR {
+                    return Ok(board.clone());
+                }
+            }
+            cache.generation
+        };
+
+        let board = build(pool, cycle).await?;
+
+        let mut cache = self.cache.lock().unwrap();
+        if cache.generation == generation {
+            cache.boards.insert(key, (Instant::now(), board.clone()));
+        }
+        Ok(board)
+    }
+
+    pub fn invalidate(&self) {
+        let mut cache = self.cache.lock().unwrap();
+        cache.boards.clear();
+        cache.generation += 1;
+    }
+
+    // Clears the cache whenever anyone solves it, on any instance. Runs until
+    // the live feed goes away
+    pub async fn watch(self, mut events: broadcast::Receiver<LiveEvent>) {
+        loop {
+            match events.recv().await {
+                Ok(event) if event.kind == LiveKind::Solved => self.invalidate(),
+                Ok(_) => {}
+                // Might have missed a solve
+                Err(RecvError::Lagged(_)) => self.invalidate(),
+                Err(RecvError::Closed) => return,
+            }
+        }
+    }
+}
+
+async fn build(pool: &PgPool, cycle: Option<&str>) -> Result<Leaderboard, ModelError> {
+    let cycle = match db::leaderboard::get_cycle_name_db(pool, cycle).await {
+        Ok(Some(cycle)) => cycle,
+        Ok(None) => return Err(ModelError::CycleNotFound),
+        Err(_) => return Err(ModelError::SqlError),
+    };
+    let solves = match db::leaderboard::get_solves_db(pool, &cycle, MAX_ENTRIES).await {
+        Ok(solves) => solves,
+        Err(e) => {
+            error!("Failed to build the {} leaderboard: {:?}", cycle, e);
+            return Err(ModelError::SqlError);
+        }
+    };
+
+    let times = solves.into_iter().map(|solve| {
+        let time = solve
+            .solved_at
+            .signed_duration_since(solve.registration_time)
+            .to_std()
+            .unwrap_or(Duration::ZERO);
+        (solve.display_name, time)
+    });
+    Ok(Leaderboard {
+        cycle,
+        entries: rank(times),
+    })
+}
+
+// Comes in fastest first. Ties share a rank and the next one skips ahead, so
+// two firsts are followed by a third
+fn rank(times: impl IntoIterator<Item = (String, Duration)>) -> Vec<LeaderboardEntry> {
+    let mut entries: Vec<LeaderboardEntry> = vec![];
+    for (i, (display_name, time_to_completion)) in times.into_iter().enumerate() {
+        let rank = match entries.last() {
+            Some(last) if last.time_to_completion == time_to_completion => last.rank,
+            _ => i as u32 + 1,
+        };
+        entries.push(LeaderboardEntry {
+            rank,
+            display_name,
+            time_to_completion,
+        });
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::Utc;
+    use tokio::sync::broadcast;
+
+    use super::{rank, Leaderboard, Leaderboards};
+    use crate::live::{LiveEvent, LiveKind};
+
+    #[test]
+    fn test_rank() {
+        let secs = Duration::from_secs;
+        let ranked = rank([
+            ("ada".to_string(), secs(10)),
+            ("bob".to_string(), secs(20)),
+            ("cy".to_string(), secs(20)),
+            ("di".to_string(), secs(30)),
+        ]);
+        let ranks: Vec<_> = ranked.iter().map(|entry| entry.rank).collect();
+        assert_eq!(ranks, vec![1, 2, 2, 4]);
+    }
+
+    #[tokio::test]
+    async fn test_solves_invalidate() {
+        let (sender, receiver) = broadcast::channel(8);
+        let leaderboards = Leaderboards::default();
+
+        let cache = |leaderboards: &Leaderboards| {
+            let board = Leaderboard {
+                cycle: "default".into(),
+                entries: vec![],
+            };
+            let mut cache = leaderboards.cache.lock().unwrap();
+            cache
+                .boards
+                .insert(None, (std::time::Instant::now(), board));
+        };
+        let event = |kind| LiveEvent {
+            kind,
+            nuid: "001453760".into(),
+            occurred_at: Utc::now(),
+            data: serde_json::json!({}),
+        };
+        let cached = |leaderboards: &Leaderboards| leaderboards.cache.lock().unwrap().boards.len();
+
+        cache(&leaderboards);
+        sender.send(event(LiveKind::Submitted)).unwrap();
+        drop(sender);
+        leaderboards.clone().watch(receiver).await;
+        // Only solves change the leaderboard
+        assert_eq!(cached(&leaderboards), 1);
+
+        let (sender, receiver) = broadcast::channel(8);
+        sender.send(event(LiveKind::Solved)).unwrap();
+        drop(sender);
+        leaderboards.clone().watch(receiver).await;
+        assert_eq!(cached(&leaderboards), 0);
+        assert_eq!(leaderboards.cache.lock().unwrap().generation, 1);
+    }
+}
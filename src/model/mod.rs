@@ -1,5 +1,17 @@
+pub mod analysis;
+pub mod applicants;
+pub mod audit;
+pub mod batches;
+pub mod cycles;
 pub mod engine;
+pub mod events;
+pub mod hints;
+pub mod leaderboard;
+pub mod programs;
+pub mod retention;
+pub mod stages;
+pub mod stats;
+pub mod tokens;
 pub mod types;
-pub use engine::{
-    check_solution, get_applicants, register_user, retreive_challenge, retreive_token,
-};
+pub use engine::{check_solution, get_applicants, register_user, retreive_challenge};
+pub use tokens::{get_rotations, rotate_token, rotate_token_for, send_token};
@@ -0,0 +1,235 @@
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::db::{self, transactions::NewSubmission};
+use crate::endpoints::errors::ModelError;
+use crate::endpoints::messages::{Answer, ProgramSubmission};
+use crate::model::audit::RequestContext;
+use crate::model::engine::{advance, record_submission, Graded};
+use crate::model::stages::{self, Challenge};
+use crate::model::tokens;
+use crate::model::types::{TestOutcome, TestRun};
+use crate::sandbox::{Compiled, Run, Sandbox, Status};
+
+// One hidden test, with the seed its input came from so it can be made again
+pub struct TestRecord {
+    pub seed: i64,
+    pub run: TestRun,
+}
+
+// How a program did. Nothing was run if it didn't compile
+pub struct ProgramRun {
+    pub graded: Graded,
+    pub compiler_output: Option<String>,
+    pub tests: Vec<TestRun>,
+}
+
+// Who has a program running right now. It's one at a time each, so no one
+// applicant can take up every slot in the sandbox
+#[derive(Clone, Default)]
+pub struct Running {
+    nuids: Arc<Mutex<HashSet<String>>>,
+}
+
+// Their run, until it's dropped
+pub struct RunSlot {
+    running: Running,
+    nuid: String,
+}
+
+impl Running {
+    fn start(&self, nuid: &str) -> Result<RunSlot, ModelError> {
+        if !self.nuids.lock().unwrap().insert(nuid.to_string()) {
+            return Err(ModelError::RunInProgress);
+        }
+        Ok(RunSlot {
+            running: self.clone(),
+            nuid: nuid.to_string(),
+        })
+    }
+}
+
+impl Drop for RunSlot {
+    fn drop(&mut self) {
+        self.running.nuids.lock().unwrap().remove(&self.nuid);
+    }
+}
+
+// Compiles and runs the program before touching the stage, so a slow program
+// doesn't hold their row locked. It's recorded against the stage it was tested
+// on, even if another submission moved them on in the meantime
+pub async fn run_program(
+    pool: PgPool,
+    sandbox: &Sandbox,
+    running: &Running,
+    token: Uuid,
+    program: &ProgramSubmission,
+    ctx: &RequestContext,
+) -> Result<ProgramRun, ModelError> {
+    let nuid = tokens::authenticate(&pool, token).await?;
+    let _slot = running.start(&nuid)?;
+    let current = match db::stages::get_current_stage_db(&pool, &nuid).await {
+        Ok(Some(current)) => current,
+        Ok(None) => return Err(ModelError::NoUserFound),
+        Err(_) => return Err(ModelError::SqlError),
+    };
+    let (of, tests) = match stages::read_challenge(&current)? {
+        Challenge::Program { of, tests } => (of, tests),
+        Challenge::Batch { .. } => return Err(ModelError::BatchRequired),
+        _ => return Err(ModelError::AnswerRequired),
+    };
+
+    let (compiler_output, records) = match sandbox.compile(program.language, &program.source).await
+    {
+        Ok(Compiled::Ready(compiled)) => {
+            let mut records = vec![];
+            for test in 1..=tests {
+                let seed = stages::new_seed();
+                let input = of.generate(seed);
+                let run = compiled.run(&format!("{}\n", input)).await.map_err(|e| {
+                    error!("Couldn't run a program from {}: {}", nuid, e);
+                    ModelError::SandboxFailed
+                })?;
+                let runtime = run.runtime;
+                let of = of.clone();
+                let outcome = stages::off_thread(move || outcome(&of, &input, &run)).await?;
+                records.push(TestRecord {
+                    seed,
+                    run: TestRun {
+                        test,
+                        outcome,
+                        runtime,
+                    },
+                });
+            }
+            (None, records)
+        }
+        Ok(Compiled::Failed(output)) => (Some(output), vec![]),
+        Err(e) => {
+            error!("Couldn't compile a program from {}: {}", nuid, e);
+            return Err(ModelError::SandboxFailed);
+        }
+    };
+    let ok = compiler_output.is_none()
+        && records
+            .iter()
+            .all(|record| record.run.outcome == TestOutcome::Passed);
+
+    let mut tx = pool.begin().await.map_err(|_| ModelError::SqlError)?;
+    let stages = stages::get_stages(&mut tx, &current.cycle).await?;
+    let hash = program_hash(program);
+    let submission = NewSubmission {
+        nuid: &nuid,
+        stage: current.stage,
+        ok,
+        solution_hash: &hash,
+        ip: ctx.ip.as_deref(),
+        user_agent: ctx.user_agent.as_deref(),
+    };
+    let recorded = async {
+        // Locked, so two passing programs can't both move them on
+        db::stages::get_current_stage_db(&mut tx, &nuid).await?;
+        let (submission_id, submitted_at) =
+            record_submission(&mut tx, &submission, None, ctx).await?;
+        db::programs::write_program_db(
+            &mut tx,
+            submission_id,
+            program.language.as_str(),
+            &program.source,
+        )
+        .await?;
+        db::programs::write_tests_db(&mut tx, submission_id, &records).await?;
+        if ok {
+            advance(&mut tx, &nuid, current.stage, &stages, submitted_at).await?;
+        }
+        Ok::<_, sqlx::Error>(())
+    };
+    if let Err(e) = recorded.await {
+        error!("Failed to record the program: {:?}", e);
+        return Err(ModelError::SqlError);
+    }
+    tx.commit().await.map_err(|_| ModelError::SqlError)?;
+
+    Ok(ProgramRun {
+        graded: Graded {
+            ok,
+            stage: current.stage as u32,
+            stages: (stages.len() as u32).max(current.stage as u32),
+        },
+        compiler_output,
+        tests: records.into_iter().map(|record| record.run).collect(),
+    })
+}
+
+// Programs print their answer as JSON, the same as they'd submit it
+fn outcome(of: &Challenge, input: &str, run: &Run) -> TestOutcome {
+    match run.status {
+        Status::Exited(0) => match serde_json::from_slice::<Answer>(&run.stdout) {
+            Ok(answer) if of.verify(input, &answer) => TestOutcome::Passed,
+            _ => TestOutcome::WrongAnswer,
+        },
+        Status::Exited(_) | Status::Killed => TestOutcome::Crashed,
+        Status::TimedOut => TestOutcome::TimedOut,
+        Status::OutputTooLong => TestOutcome::OutputTooLong,
+    }
+}
+
+// Same program in the same language, same hash - for spotting shared code
+fn program_hash(program: &ProgramSubmission) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(program.language.as_str());
+    hasher.update(b"\n");
+    hasher.update(&program.source);
+    hex::encode(hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{outcome, Running};
+    use crate::endpoints::errors::ModelError;
+    use crate::model::stages::Challenge;
+    use crate::model::types::TestOutcome;
+    use crate::sandbox::{Run, Status};
+
+    #[test]
+    fn test_outcome() {
+        let run = |status, stdout: &str| Run {
+            status,
+            stdout: stdout.as_bytes().to_vec(),
+            stderr: vec![],
+            runtime: Duration::from_millis(5),
+        };
+        let of = Challenge::ReverseComplement { length: 4 };
+        let check = |status, stdout| outcome(&of, "AACG", &run(status, stdout));
+
+        assert_eq!(check(Status::Exited(0), "\"CGTT\"\n"), TestOutcome::Passed);
+        assert_eq!(
+            check(Status::Exited(0), "\"CGTA\""),
+            TestOutcome::WrongAnswer
+        );
+        assert_eq!(check(Status::Exited(0), "CGTT"), TestOutcome::WrongAnswer);
+        assert_eq!(check(Status::Exited(1), "\"CGTT\""), TestOutcome::Crashed);
+        assert_eq!(check(Status::Killed, ""), TestOutcome::Crashed);
+        assert_eq!(check(Status::TimedOut, ""), TestOutcome::TimedOut);
+        assert_eq!(check(Status::OutputTooLong, ""), TestOutcome::OutputTooLong);
+    }
+
+    #[test]
+    fn test_one_run_at_a_time() {
+        let running = Running::default();
+        let slot = running.start("001453760").unwrap();
+        assert!(matches!(
+            running.start("001453760"),
+            Err(ModelError::RunInProgress)
+        ));
+        assert!(running.start("001453761").is_ok());
+        drop(slot);
+        assert!(running.start("001453760").is_ok());
+    }
+}
@@ -0,0 +1,193 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{PgPool, Postgres, Transaction};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::db;
+use crate::endpoints::errors::ModelError;
+use crate::model::audit::{self, Action, Actor, RequestContext};
+use crate::model::tokens;
+
+// How often the server applies the retention policies on its own
+const PURGE_EVERY: Duration = Duration::from_secs(60 * 60);
+
+// What happens to an applicant once their cycle's retention period is up
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum RetentionAction {
+    // Keep the submissions for the numbers, drop everything that says who it was
+    Anonymize,
+    // Gone, submissions and all
+    Delete,
+}
+
+impl RetentionAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RetentionAction::Anonymize => "anonymize",
+            RetentionAction::Delete => "delete",
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Default, PartialEq, Eq)]
+pub struct PurgeReport {
+    pub anonymized: Vec<String>,
+    pub deleted: Vec<String>,
+}
+
+// What an applicant's NUID becomes everywhere once they're gone
+fn pseudonym() -> String {
+    format!("anon-{}", &Uuid::new_v4().simple().to_string()[..12])
+}
+
+// The applicant asked for their data to be deleted - no grace period, no
+// anonymized copy. Returns the pseudonym left in the audit log
+pub async fn delete_my_data(
+    pool: &PgPool,
+    token: Uuid,
+    ctx: &RequestContext,
+) -> Result<String, ModelError> {
+    let nuid = tokens::authenticate(pool, token).await?;
+    remove(pool, &nuid, RetentionAction::Delete, Actor::Applicant, ctx).await
+}
+
+// Deletes an applicant on an admin's say so, whatever their cycle's policy is
+pub async fn delete_applicant(
+    pool: &PgPool,
+    nuid: &str,
+    ctx: &RequestContext,
+) -> Result<String, ModelError> {
+    remove(pool, nuid, RetentionAction::Delete, Actor::Admin, ctx).await
+}
+
+// What delete_applicant would do, without doing it
+pub async fn preview_delete(pool: &PgPool, nuid: &str) -> Result<PurgeReport, ModelError> {
+    match db::transactions::get_contact_db(pool, nuid).await {
+        Ok(Some(_)) => Ok(PurgeReport {
+            deleted: vec![nuid.into()],
+            ..Default::default()
+        }),
+        Ok(None) => Err(ModelError::NoUserFound),
+        Err(_) => Err(ModelError::SqlError),
+    }
+}
+
+// Applies every cycle's retention policy. With dry_run nothing changes, and the
+// report says what would have
+pub async fn purge(pool: &PgPool, dry_run: bool) -> Result<PurgeReport, ModelError> {
+    let expired = match db::retention::get_expired_applicants_db(pool).await {
+        Ok(expired) => expired,
+        Err(e) => {
+            error!("Failed to find applicants past retention: {:?}", e);
+            return Err(ModelError::SqlError);
+        }
+    };
+
+    let mut report = PurgeReport::default();
+    let ctx = RequestContext {
+        request_id: format!("purge-{}", Uuid::new_v4()),
+        ..Default::default()
+    };
+    for (nuid, action) in expired {
+        let action = match action.as_str() {
+            "delete" => RetentionAction::Delete,
+            _ => RetentionAction::Anonymize,
+        };
+        if !dry_run {
+            remove(pool, &nuid, action, Actor::System, &ctx).await?;
+        }
+        match action {
+            RetentionAction::Anonymize => report.anonymized.push(nuid),
+            RetentionAction::Delete => report.deleted.push(nuid),
+        }
+    }
+    Ok(report)
+}
+
+// Keeps purging in the background for as long as the server is up. A failed
+// run just waits for the next one
+pub async fn run(pool: PgPool) {
+    let mut interval = tokio::time::interval(PURGE_EVERY);
+    loop {
+        interval.tick().await;
+        match purge(&pool, false).await {
+            Ok(report) if report == PurgeReport::default() => {}
+            Ok(report) => info!(
+                "Retention: anonymized {}, deleted {}",
+                report.anonymized.len(),
+                report.deleted.len()
+            ),
+            Err(e) => warn!("Retention purge failed: {:?}", e),
+        }
+    }
+}
+
+async fn remove(
+    pool: &PgPool,
+    nuid: &str,
+    action: RetentionAction,
+    actor: Actor,
+    ctx: &RequestContext,
+) -> Result<String, ModelError> {
+    let pseudonym = pseudonym();
+
+    let mut tx = pool.begin().await.map_err(|_| ModelError::SqlError)?;
+    match remove_in(&mut tx, nuid, &pseudonym, action, actor, ctx).await {
+        Ok(true) => {}
+        Ok(false) => return Err(ModelError::NoUserFound),
+        Err(e) => {
+            error!("Failed to {} {}: {:?}", action.as_str(), nuid, e);
+            return Err(ModelError::SqlError);
+        }
+    }
+
+    match tx.commit().await {
+        Ok(()) => {
+            info!(
+                "Applied {} to {} ({})",
+                action.as_str(),
+                nuid,
+                actor.as_str()
+            );
+            Ok(pseudonym)
+        }
+        Err(_) => Err(ModelError::SqlError),
+    }
+}
+
+// The audit event goes in under the pseudonym, so the log still shows that
+// something happened without saying who to
+async fn remove_in(
+    tx: &mut Transaction<'_, Postgres>,
+    nuid: &str,
+    pseudonym: &str,
+    action: RetentionAction,
+    actor: Actor,
+    ctx: &RequestContext,
+) -> Result<bool, sqlx::Error> {
+    db::retention::redact_applicant_db(tx, nuid, pseudonym).await?;
+    let (removed, audited) = match action {
+        RetentionAction::Anonymize => (
+            db::retention::anonymize_applicant_db(tx, nuid, pseudonym).await?,
+            Action::Anonymized,
+        ),
+        RetentionAction::Delete => (
+            db::retention::delete_applicant_db(tx, nuid).await?,
+            Action::Deleted,
+        ),
+    };
+    if removed {
+        // Where the request came from would tie the pseudonym straight back to them
+        let ctx = RequestContext {
+            ip: None,
+            user_agent: None,
+            ..ctx.clone()
+        };
+        audit::record(tx, &ctx, audited, actor, Some(pseudonym), json!({})).await?;
+    }
+    Ok(removed)
+}
@@ -0,0 +1,473 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{PgExecutor, PgPool};
+use utoipa::ToSchema;
+
+use crate::db;
+use crate::db::stages::{CurrentStage, CycleStage, StoredChallenge};
+use crate::endpoints::errors::ModelError;
+use crate::endpoints::messages::Answer;
+use crate::model::audit::{self, Action, Actor, RequestContext};
+use crate::model::engine::{
+    find_kmers, gc_content, generate_challenge_string, longest_repeat, reverse_complement,
+};
+
+// Bump this when Challenge changes shape, and teach `Challenge::from_stored` to
+// read the old shape - rows written under every earlier version stay around.
+// Adding a kind doesn't change the shape of the ones already stored
+pub const CHALLENGE_VERSION: i32 = 1;
+
+// How far off a GC content fraction can be, since clients round differently
+const GC_TOLERANCE: f64 = 1e-6;
+
+// What one stage of a cycle asks applicants to do. Every kind works on the same
+// random DNA strings, and has its own shape of answer
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Challenge {
+    // Count every substring of length k
+    Kmers {
+        k: usize,
+        length: usize,
+    },
+    // Reverse the string and swap each base for its pair
+    ReverseComplement {
+        length: usize,
+    },
+    // The fraction of each window that's G or C, windows back to back from the
+    // start. The last one is shorter if the window doesn't divide the length
+    GcContent {
+        window: usize,
+        length: usize,
+    },
+    // The longest substring that shows up more than once - any of them, if
+    // there's a tie
+    LongestRepeat {
+        length: usize,
+    },
+    // A program that answers the challenge it wraps, instead of an answer. It's
+    // run against this many hidden challenge strings
+    Program {
+        of: Box<Challenge>,
+        tests: u32,
+    },
+    // The challenge it wraps, answered for a batch of fresh strings at once
+    // before a deadline - so one hard-coded answer gets nowhere
+    Batch {
+        of: Box<Challenge>,
+        size: u32,
+        seconds: u32,
+    },
+}
+
+// The original challenge, for cycles that never set any stages
+impl Default for Challenge {
+    fn default() -> Self {
+        Challenge::Kmers { k: 3, length: 100 }
+    }
+}
+
+impl Challenge {
+    // The same seed always gives the same challenge string
+    pub fn generate(&self, seed: i64) -> String {
+        match self {
+            Challenge::Kmers { length, .. }
+            | Challenge::ReverseComplement { length }
+            | Challenge::GcContent { length, .. }
+            | Challenge::LongestRepeat { length } => {
+                generate_challenge_string(seed as u64, *length)
+            }
+            // An example of what it'll be run against
+            Challenge::Program { of, .. } | Challenge::Batch { of, .. } => of.generate(seed),
+        }
+    }
+
+    // Sent along with the challenge string, so applicants know what to do with it
+    pub fn describe(&self) -> String {
+        match self {
+            Challenge::Kmers { k, .. } => format!(
+                "Count every {}-mer, as an object from each one to its count",
+                k
+            ),
+            Challenge::ReverseComplement { .. } => {
+                "Give the reverse complement, as a string".to_string()
+            }
+            Challenge::GcContent { window, .. } => format!(
+                "Give the fraction of G and C in each {}-character window, back to back \
+                from the start, as a list",
+                window
+            ),
+            Challenge::LongestRepeat { .. } => {
+                "Give the longest substring that appears more than once, as a string".to_string()
+            }
+            Challenge::Program { of, tests } => format!(
+                "Upload a program to /run that reads a challenge string like this one on \
+                stdin and prints its answer to stdout as JSON - it's run against {} hidden \
+                ones. {}",
+                tests,
+                of.describe()
+            ),
+            Challenge::Batch { of, size, seconds } => format!(
+                "POST to /batch/{{token}} for {} challenge strings like this one, then POST \
+                a list of their answers in the same order to /batch/{{token}}/{{batch_id}} \
+                within {} seconds. {}",
+                size,
+                seconds,
+                of.describe()
+            ),
+        }
+    }
+
+    // One right answer - LongestRepeat can have others
+    pub fn solve(&self, challenge_string: &str) -> Answer {
+        match self {
+            Challenge::Kmers { k, .. } => Answer::Counts(find_kmers(challenge_string, *k)),
+            Challenge::ReverseComplement { .. } => {
+                Answer::Sequence(reverse_complement(challenge_string))
+            }
+            Challenge::GcContent { window, .. } => {
+                Answer::Fractions(gc_content(challenge_string, *window))
+            }
+            Challenge::LongestRepeat { .. } => {
+                Answer::Sequence(longest_repeat(challenge_string).to_string())
+            }
+            Challenge::Program { of, .. } | Challenge::Batch { of, .. } => {
+                of.solve(challenge_string)
+            }
+        }
+    }
+
+    // An answer of the wrong shape for the challenge is just wrong
+    pub fn verify(&self, challenge_string: &str, given: &Answer) -> bool {
+        match (self, given) {
+            (Challenge::GcContent { .. }, Answer::Fractions(given)) => {
+                match self.solve(challenge_string) {
+                    Answer::Fractions(expected) => {
+                        expected.len() == given.len()
+                            && expected
+                                .iter()
+                                .zip(given)
+                                .all(|(e, g)| (e - g).abs() <= GC_TOLERANCE)
+                    }
+                    _ => false,
+                }
+            }
+            (Challenge::LongestRepeat { .. }, Answer::Sequence(given)) => {
+                given.len() == longest_repeat(challenge_string).len()
+                    && challenge_string
+                        .find(given.as_str())
+                        .is_some_and(|first| challenge_string[first + 1..].contains(given.as_str()))
+            }
+            (Challenge::Program { of, .. } | Challenge::Batch { of, .. }, given) => {
+                of.verify(challenge_string, given)
+            }
+            _ => self.solve(challenge_string) == *given,
+        }
+    }
+
+    pub fn to_stored(&self) -> StoredChallenge {
+        StoredChallenge {
+            version: CHALLENGE_VERSION,
+            challenge: serde_json::to_value(self).expect("challenges always serialize"),
+        }
+    }
+
+    pub fn from_stored(stored: &StoredChallenge) -> Result<Challenge, StoredChallengeError> {
+        match stored.version {
+            1 => serde_json::from_value(stored.challenge.clone())
+                .map_err(|e| StoredChallengeError::Malformed(stored.version, e)),
+            version => Err(StoredChallengeError::UnknownVersion(version)),
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum StoredChallengeError {
+    #[error("challenge version {0} is newer than this server")]
+    UnknownVersion(i32),
+    #[error("challenge doesn't match version {0}: {1}")]
+    Malformed(i32, serde_json::Error),
+}
+
+pub fn new_seed() -> i64 {
+    rand::random()
+}
+
+pub fn read_challenge(current: &CurrentStage) -> Result<Challenge, ModelError> {
+    Challenge::from_stored(&current.challenge).map_err(|e| {
+        error!("Can't read stage {}: {}", current.stage, e);
+        ModelError::SqlError
+    })
+}
+
+// Working answers out is CPU-bound, and slow on the longest strings, so it gets
+// a blocking thread instead of holding up the ones serving requests
+pub async fn off_thread<T, F>(work: F) -> Result<T, ModelError>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(work).await.map_err(|e| {
+        error!("Grading didn't finish: {:?}", e);
+        ModelError::SqlError
+    })
+}
+
+// Checked against the string worked out again from the stage's seed. Stages
+// from before challenges were seeded only have the challenge string to go on
+pub fn grade(current: &CurrentStage, given: &Answer) -> Result<bool, ModelError> {
+    let challenge = read_challenge(current)?;
+    match challenge {
+        Challenge::Program { .. } => return Err(ModelError::ProgramRequired),
+        Challenge::Batch { .. } => return Err(ModelError::BatchRequired),
+        _ => (),
+    }
+    Ok(match current.seed {
+        Some(seed) => challenge.verify(&challenge.generate(seed), given),
+        None => challenge.verify(&current.challenge_string, given),
+    })
+}
+
+// One stage of a cycle - its challenge, alongside the hints written for it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct Stage {
+    #[serde(flatten)]
+    pub challenge: Challenge,
+    // Unlocked one at a time at /hint, each giving away more than the last.
+    // Read from here when they're unlocked, so fixing one fixes it for everyone
+    #[serde(default)]
+    pub hints: Vec<String>,
+}
+
+// A cycle's stages, in the order they unlock
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct Stages {
+    pub stages: Vec<Stage>,
+}
+
+// Never empty - a cycle without any set up gets the default one, which has no
+// hints
+pub async fn get_stages(
+    executor: impl PgExecutor<'_>,
+    cycle: &str,
+) -> Result<Vec<Stage>, ModelError> {
+    let stages = match db::stages::get_stages_db(executor, cycle).await {
+        Ok(stages) => stages,
+        Err(e) => {
+            error!("Failed to get the stages for {}: {:?}", cycle, e);
+            return Err(ModelError::SqlError);
+        }
+    };
+    if stages.is_empty() {
+        return Ok(vec![Stage {
+            challenge: Challenge::default(),
+            hints: vec![],
+        }]);
+    }
+
+    stages
+        .into_iter()
+        .map(|stage| {
+            Ok(Stage {
+                challenge: Challenge::from_stored(&stage.challenge)?,
+                hints: stage.hints,
+            })
+        })
+        .collect::<Result<_, StoredChallengeError>>()
+        .map_err(|e| {
+            error!("Can't read a stage of {}: {}", cycle, e);
+            ModelError::SqlError
+        })
+}
+
+pub async fn get_cycle_stages(pool: &PgPool, cycle: &str) -> Result<Stages, ModelError> {
+    match db::leaderboard::get_cycle_name_db(pool, Some(cycle)).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return Err(ModelError::CycleNotFound),
+        Err(_) => return Err(ModelError::SqlError),
+    }
+    Ok(Stages {
+        stages: get_stages(pool, cycle).await?,
+    })
+}
+
+// Only changes what's unlocked from now on - applicants keep the challenge
+// strings they already have
+pub async fn set_stages(
+    pool: &PgPool,
+    cycle: &str,
+    stages: &[Stage],
+    ctx: &RequestContext,
+) -> Result<(), ModelError> {
+    let records: Vec<_> = stages
+        .iter()
+        .map(|stage| CycleStage {
+            challenge: stage.challenge.to_stored(),
+            hints: stage.hints.clone(),
+        })
+        .collect();
+
+    let mut tx = pool.begin().await.map_err(|_| ModelError::SqlError)?;
+    let set = async {
+        if db::leaderboard::get_cycle_name_db(&mut tx, Some(cycle))
+            .await?
+            .is_none()
+        {
+            return Ok(false);
+        }
+        db::stages::set_stages_db(&mut tx, cycle, &records).await?;
+        let details = json!({ "cycle": cycle, "stages": stages });
+        audit::record(
+            &mut tx,
+            ctx,
+            Action::StagesChanged,
+            Actor::Admin,
+            None,
+            details,
+        )
+        .await?;
+        Ok::<_, sqlx::Error>(true)
+    };
+    match set.await {
+        Ok(true) => {}
+        Ok(false) => return Err(ModelError::CycleNotFound),
+        Err(e) => {
+            error!("Failed to set the stages for {}: {:?}", cycle, e);
+            return Err(ModelError::SqlError);
+        }
+    }
+
+    tx.commit().await.map_err(|_| ModelError::SqlError)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{grade, read_challenge, Challenge, Stage, StoredChallenge, StoredChallengeError};
+    use crate::db::stages::CurrentStage;
+    use crate::endpoints::errors::ModelError;
+    use crate::endpoints::messages::Answer;
+
+    #[test]
+    fn test_generate_kmers() {
+        let challenge = Challenge::Kmers { k: 5, length: 40 };
+        let challenge_string = challenge.generate(7);
+        let solution = match challenge.solve(&challenge_string) {
+            Answer::Counts(counts) => counts,
+            other => panic!("expected counts, got {:?}", other),
+        };
+        assert_eq!(challenge_string.len(), 40);
+        assert!(solution.keys().all(|kmer| kmer.len() == 5));
+        assert_eq!(solution.values().sum::<u64>(), 36);
+    }
+
+    #[test]
+    fn test_verify() {
+        let sequence = |s: &str| Answer::Sequence(s.to_string());
+
+        let kmers = Challenge::Kmers { k: 3, length: 4 };
+        let counts = Answer::Counts([("ACG".to_string(), 1), ("CGT".to_string(), 1)].into());
+        assert!(kmers.verify("ACGT", &counts));
+        assert!(!kmers.verify("ACGA", &counts));
+        // Right answer, wrong shape
+        assert!(!kmers.verify("ACGT", &sequence("ACGT")));
+
+        let reverse = Challenge::ReverseComplement { length: 4 };
+        assert!(reverse.verify("AACG", &sequence("CGTT")));
+        assert!(!reverse.verify("AACG", &sequence("GCAA")));
+
+        let gc = Challenge::GcContent {
+            window: 3,
+            length: 4,
+        };
+        assert!(gc.verify("GCAT", &Answer::Fractions(vec![2.0 / 3.0, 0.0])));
+        assert!(gc.verify("GCAT", &Answer::Fractions(vec![0.6666667, 0.0])));
+        assert!(!gc.verify("GCAT", &Answer::Fractions(vec![0.67, 0.0])));
+        assert!(!gc.verify("GCAT", &Answer::Fractions(vec![2.0 / 3.0])));
+
+        // Both ACG and CGT repeat, and either will do
+        let repeat = Challenge::LongestRepeat { length: 11 };
+        assert!(repeat.verify("ACGTTCGTACG", &sequence("ACG")));
+        assert!(repeat.verify("ACGTTCGTACG", &sequence("CGT")));
+        assert!(!repeat.verify("ACGTTCGTACG", &sequence("GTT")));
+        assert!(!repeat.verify("ACGTTCGTACG", &sequence("CG")));
+    }
+
+    #[test]
+    fn test_stages() {
+        let stage: Stage = serde_json::from_value(json!({
+            "kind": "kmers",
+            "k": 4,
+            "length": 50,
+            "hints": ["Every substring of 4 characters, overlapping"],
+        }))
+        .unwrap();
+        assert_eq!(stage.challenge, Challenge::Kmers { k: 4, length: 50 });
+        assert_eq!(stage.hints.len(), 1);
+        // Stages set up before there were hints
+        let stage: Stage =
+            serde_json::from_value(json!({"kind": "reverse_complement", "length": 10})).unwrap();
+        assert!(stage.hints.is_empty());
+    }
+
+    #[test]
+    fn test_seeds_are_reproducible() {
+        let challenge = Challenge::Kmers { k: 3, length: 24 };
+        assert_eq!(challenge.generate(42), challenge.generate(42));
+        assert_ne!(challenge.generate(42), challenge.generate(43));
+        // Pinned, so a change to the generator that would change everyone's
+        // challenge (and so their solution) gets noticed
+        assert_eq!(challenge.generate(42), "CCGGTTTACAACGGAGAGGAGGTT");
+        assert_eq!(challenge.generate(-1), "GCGAGCGAGGACCTATTTCATGTG");
+    }
+
+    #[test]
+    fn test_stored_challenges() {
+        let stored = |version, challenge| StoredChallenge { version, challenge };
+        let challenge = Challenge::Kmers { k: 4, length: 50 };
+        assert_eq!(
+            Challenge::from_stored(&challenge.to_stored()).unwrap(),
+            challenge
+        );
+        // What the migration backfilled stages from before seeding with
+        let backfilled = stored(1, json!({"kind": "kmers", "k": 3, "length": 100}));
+        assert_eq!(
+            Challenge::from_stored(&backfilled).unwrap(),
+            Challenge::default()
+        );
+        assert!(matches!(
+            Challenge::from_stored(&stored(2, json!({}))),
+            Err(StoredChallengeError::UnknownVersion(2))
+        ));
+        assert!(matches!(
+            Challenge::from_stored(&stored(1, json!({"kind": "kmers", "k": 3}))),
+            Err(StoredChallengeError::Malformed(1, _))
+        ));
+    }
+
+    // A stage that doesn't read back is the database's fault, not a reason to
+    // take the request down with it
+    #[test]
+    fn test_unreadable_stages_are_errors() {
+        let current = CurrentStage {
+            stage: 1,
+            cycle: "default".into(),
+            challenge_string: "ACGT".into(),
+            seed: None,
+            challenge: StoredChallenge {
+                version: 1,
+                challenge: json!({"kind": "kmers"}),
+            },
+            solved_at: None,
+        };
+        assert!(matches!(
+            read_challenge(&current),
+            Err(ModelError::SqlError)
+        ));
+        assert!(matches!(
+            grade(&current, &Answer::Sequence("ACGT".into())),
+            Err(ModelError::SqlError)
+        ));
+    }
+}
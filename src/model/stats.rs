@@ -0,0 +1,112 @@
+use chrono::NaiveDate;
+use serde::Serialize;
+use sqlx::PgPool;
+use utoipa::ToSchema;
+
+use crate::db;
+use crate::endpoints::errors::ModelError;
+
+// How a cycle is going, for the recruiting team
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct CycleStats {
+    pub cycle: String,
+    // Only days that had any
+    pub registrations_per_day: Vec<DailyRegistrations>,
+    pub funnel: Funnel,
+    // Of everyone who registered, how many have solved it - between 0 and 1
+    pub solve_rate: f64,
+    pub time_to_completion: Percentiles,
+    // Fewest attempts first
+    pub attempts: Vec<AttemptCount>,
+}
+
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct DailyRegistrations {
+    pub day: NaiveDate,
+    pub registrations: i64,
+}
+
+// Everyone who got at least as far as each step
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct Funnel {
+    pub registered: i64,
+    pub fetched_challenge: i64,
+    pub submitted: i64,
+    pub solved: i64,
+}
+
+// In seconds, from registering to solving the last stage. Missing until
+// someone's solved it
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct Percentiles {
+    pub median: Option<f64>,
+    pub p75: Option<f64>,
+    pub p90: Option<f64>,
+    pub p95: Option<f64>,
+}
+
+// How many applicants took this many submissions, counting up to solving the
+// last stage, and how many of them got there
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct AttemptCount {
+    pub attempts: i64,
+    pub applicants: i64,
+    pub solved: i64,
+}
+
+// The active cycle's stats, unless another is asked for
+pub async fn summarize(pool: &PgPool, cycle: Option<&str>) -> Result<CycleStats, ModelError> {
+    let cycle = match db::leaderboard::get_cycle_name_db(pool, cycle).await {
+        Ok(Some(cycle)) => cycle,
+        Ok(None) => return Err(ModelError::CycleNotFound),
+        Err(_) => return Err(ModelError::SqlError),
+    };
+
+    let summarized = async {
+        let registrations_per_day = db::stats::get_registrations_db(pool, &cycle).await?;
+        let funnel = db::stats::get_funnel_db(pool, &cycle).await?;
+        let time_to_completion = db::stats::get_completion_times_db(pool, &cycle).await?;
+        let attempts = db::stats::get_attempts_db(pool, &cycle).await?;
+        Ok::<_, sqlx::Error>((registrations_per_day, funnel, time_to_completion, attempts))
+    };
+    let (registrations_per_day, funnel, time_to_completion, attempts) = match summarized.await {
+        Ok(summarized) => summarized,
+        Err(e) => {
+            error!("Failed to summarize cycle {}: {:?}", cycle, e);
+            return Err(ModelError::SqlError);
+        }
+    };
+
+    Ok(CycleStats {
+        solve_rate: solve_rate(&funnel),
+        cycle,
+        registrations_per_day,
+        funnel,
+        time_to_completion,
+        attempts,
+    })
+}
+
+fn solve_rate(funnel: &Funnel) -> f64 {
+    if funnel.registered == 0 {
+        return 0.0;
+    }
+    funnel.solved as f64 / funnel.registered as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{solve_rate, Funnel};
+
+    #[test]
+    fn test_solve_rate() {
+        let funnel = |registered, solved| Funnel {
+            registered,
+            fetched_challenge: registered,
+            submitted: registered,
+            solved,
+        };
+        assert_eq!(solve_rate(&funnel(0, 0)), 0.0);
+        assert_eq!(solve_rate(&funnel(4, 1)), 0.25);
+    }
+}
@@ -0,0 +1,172 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use serde_json::json;
+use sqlx::PgPool;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::db;
+use crate::endpoints::errors::ModelError;
+use crate::jobs::{self, Job};
+use crate::model::audit::{self, Action, Actor, RequestContext};
+use crate::model::events::Event;
+
+// A lost token gets emailed at most this often, so nobody can flood an
+// applicant's inbox by asking over and over
+const RESEND_MINUTES: i64 = 10;
+
+// A token that was replaced, and who replaced it
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct TokenRotation {
+    pub revoked_token: Uuid,
+    // applicant or admin
+    pub rotated_by: String,
+    pub rotated_at: DateTime<Utc>,
+}
+
+// When a token handed out now should stop working
+pub fn expiry(lifetime: Option<Duration>) -> Option<DateTime<Utc>> {
+    lifetime.map(|lifetime| Utc::now() + lifetime)
+}
+
+// Every route that takes a token goes through here first. Returns the NUID the
+// token belongs to
+pub async fn authenticate(pool: &PgPool, token: Uuid) -> Result<String, ModelError> {
+    match db::tokens::get_token_db(pool, token).await {
+        Ok(Some((_, Some(expires_at)))) if expires_at <= Utc::now() => {
+            Err(ModelError::TokenExpired)
+        }
+        Ok(Some((nuid, _))) => Ok(nuid),
+        Ok(None) => match db::tokens::is_revoked_db(pool, token).await {
+            Ok(true) => Err(ModelError::TokenRevoked),
+            Ok(false) => Err(ModelError::NoUserFound),
+            Err(_) => Err(ModelError::SqlError),
+        },
+        Err(_) => Err(ModelError::SqlError),
+    }
+}
+
+// For an applicant whose token has leaked - they prove they hold it, and get a
+// new one back that replaces it
+pub async fn rotate_token(
+    pool: &PgPool,
+    token: Uuid,
+    lifetime: Option<Duration>,
+    ctx: &RequestContext,
+) -> Result<Uuid, ModelError> {
+    let nuid = authenticate(pool, token).await?;
+    rotate(pool, &nuid, Some(token), lifetime, Actor::Applicant, ctx).await
+}
+
+// Hands an applicant a new token, whatever state their old one is in
+pub async fn rotate_token_for(
+    pool: &PgPool,
+    nuid: &str,
+    lifetime: Option<Duration>,
+    ctx: &RequestContext,
+) -> Result<Uuid, ModelError> {
+    rotate(pool, nuid, None, lifetime, Actor::Admin, ctx).await
+}
+
+// Replaces `token` if it's given, and whatever their token is if not. Two
+// rotations of the same token can't both win - the second finds it revoked
+async fn rotate(
+    pool: &PgPool,
+    nuid: &str,
+    token: Option<Uuid>,
+    lifetime: Option<Duration>,
+    rotated_by: Actor,
+    ctx: &RequestContext,
+) -> Result<Uuid, ModelError> {
+    let new_token = Uuid::new_v4();
+
+    let mut tx = pool.begin().await.map_err(|_| ModelError::SqlError)?;
+    let rotated = async {
+        let old_token = db::tokens::rotate_token_db(
+            &mut tx,
+            nuid,
+            token,
+            new_token,
+            expiry(lifetime),
+            rotated_by.as_str(),
+        )
+        .await?;
+        if let Some(old_token) = old_token {
+            let details = json!({ "revoked_token": old_token });
+            audit::record(
+                &mut tx,
+                ctx,
+                Action::TokenRotated,
+                rotated_by,
+                Some(nuid),
+                details,
+            )
+            .await?;
+        }
+        Ok::<_, sqlx::Error>(old_token)
+    };
+    match rotated.await {
+        Ok(Some(_)) => {}
+        Ok(None) if token.is_some() => return Err(ModelError::TokenRevoked),
+        Ok(None) => return Err(ModelError::NoUserFound),
+        Err(e) => {
+            error!("Failed to rotate the token for {}: {:?}", nuid, e);
+            return Err(ModelError::SqlError);
+        }
+    }
+
+    match tx.commit().await {
+        Ok(()) => {
+            info!("Rotated the token for {} ({})", nuid, rotated_by.as_str());
+            Ok(new_token)
+        }
+        Err(_) => Err(ModelError::SqlError),
+    }
+}
+
+// Emails an applicant their current token, at the address they registered
+// with. It's never handed back directly - anyone can ask, and all they need is
+// a NUID. Whether the NUID is registered isn't given away either
+pub async fn send_token(pool: &PgPool, nuid: &str, ctx: &RequestContext) -> Result<(), ModelError> {
+    match db::transactions::get_contact_db(pool, nuid).await {
+        Ok(Some(contact)) if contact.email.is_some() => {}
+        Ok(_) => return Ok(()),
+        Err(_) => return Err(ModelError::SqlError),
+    }
+
+    let since = Utc::now() - Duration::minutes(RESEND_MINUTES);
+    let mut tx = pool.begin().await.map_err(|_| ModelError::SqlError)?;
+    let queued = async {
+        let action = Action::TokenRequested;
+        if db::audit::has_event_since_db(&mut tx, nuid, action.as_str(), since).await? {
+            return Ok(());
+        }
+        let job = Job::SendEmail {
+            nuid: nuid.into(),
+            event: Event::TokenRequested,
+        };
+        jobs::enqueue(&mut tx, &job).await?;
+        audit::record(
+            &mut tx,
+            ctx,
+            action,
+            Actor::Applicant,
+            Some(nuid),
+            json!({}),
+        )
+        .await
+    };
+    if let Err(e) = queued.await {
+        error!("Failed to queue the token email for {}: {:?}", nuid, e);
+        return Err(ModelError::SqlError);
+    }
+
+    tx.commit().await.map_err(|_| ModelError::SqlError)
+}
+
+pub async fn get_rotations(pool: &PgPool, nuid: &str) -> Result<Vec<TokenRotation>, ModelError> {
+    match db::tokens::get_rotations_db(pool, nuid).await {
+        Ok(rotations) => Ok(rotations),
+        Err(_) => Err(ModelError::SqlError),
+    }
+}
@@ -0,0 +1,593 @@
+// Runs the programs applicants upload, each in a throwaway process. It gets its
+// own user, PID, network, IPC, UTS and mount namespaces, so it has no
+// privileges, no network and no one else's processes to see or signal, and the
+// places secrets live are covered up with empty filesystems. It's PID 1 of its
+// namespace, so when it's killed everything it started goes with it. On top of
+// that it runs as nobody under rlimits, and a seccomp filter turns away the
+// syscalls that could get it back out
+mod seccomp;
+
+use std::ffi::{CStr, CString, OsStr};
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::chown;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use seccompiler::BpfProgram;
+use tempfile::TempDir;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::process::Command;
+use tokio::sync::Semaphore;
+
+use crate::config::SandboxSettings;
+use crate::endpoints::messages::Language;
+
+const NOBODY: u32 = 65534;
+// Counts every process nobody has, across every program running at once
+const MAX_PROCESSES: u64 = 64;
+const MAX_OPEN_FILES: u64 = 64;
+const MAX_FILE_SIZE: u64 = 64 << 20;
+// Enough of the compiler's complaints to find the problem
+const MAX_STDERR: usize = 16 << 10;
+// Compilers need more room than what they compile
+const COMPILE_TIMEOUT: Duration = Duration::from_secs(20);
+const COMPILE_MEMORY_MB: u64 = 1024;
+const PATH: &str = "/usr/local/bin:/usr/bin:/bin";
+// Covered up, on top of the server's working directory - that's where its
+// config and .env are. /proc still shows the processes outside its namespace
+const HIDDEN: &[&str] = &["/var/tmp", "/dev/shm", "/root", "/home", "/proc"];
+// Where the program finds its own directory. Nothing else in /tmp, like other
+// programs' directories, can be seen from in there
+const SCRATCH: &CStr = c"/tmp";
+
+#[derive(thiserror::Error, Debug)]
+pub enum SandboxError {
+    #[error("couldn't run the program: {0}")]
+    Io(#[from] io::Error),
+    #[error("couldn't build the seccomp filter: {0}")]
+    Seccomp(#[from] seccompiler::Error),
+    #[error("programs can only be run when the server runs as root")]
+    NotRoot,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Exited(i32),
+    // By a signal - usually for going over its memory or CPU time
+    Killed,
+    TimedOut,
+    OutputTooLong,
+}
+
+#[derive(Debug)]
+pub struct Run {
+    pub status: Status,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    pub runtime: Duration,
+}
+
+impl Run {
+    pub fn succeeded(&self) -> bool {
+        self.status == Status::Exited(0)
+    }
+}
+
+pub enum Compiled {
+    Ready(Program),
+    // With what the compiler had to say
+    Failed(String),
+}
+
+#[derive(Clone, Copy)]
+struct Limits {
+    timeout: Duration,
+    memory_mb: u64,
+    output: usize,
+}
+
+struct Toolchain {
+    file: &'static str,
+    compile: Option<&'static [&'static str]>,
+    run: &'static [&'static str],
+}
+
+fn toolchain(language: Language) -> Toolchain {
+    match language {
+        Language::Python => Toolchain {
+            file: "main.py",
+            compile: None,
+            run: &["python3", "-I", "main.py"],
+        },
+        Language::C => Toolchain {
+            file: "main.c",
+            compile: Some(&["cc", "-O2", "-o", "main", "main.c", "-lm"]),
+            run: &["./main"],
+        },
+        Language::Cpp => Toolchain {
+            file: "main.cpp",
+            compile: Some(&["c++", "-O2", "-o", "main", "main.cpp"]),
+            run: &["./main"],
+        },
+    }
+}
+
+#[derive(Clone)]
+pub struct Sandbox {
+    settings: SandboxSettings,
+    // One per program compiling or running at once
+    slots: Arc<Semaphore>,
+}
+
+// Written out, compiled if it needed to be, and ready to run
+pub struct Program {
+    sandbox: Sandbox,
+    dir: TempDir,
+    run: &'static [&'static str],
+}
+
+impl Sandbox {
+    // Without root there's no running programs as nobody, or limiting how many
+    // processes they start
+    pub fn new(settings: SandboxSettings) -> Result<Sandbox, SandboxError> {
+        // Safe - it can't fail
+        if unsafe { libc::geteuid() } != 0 {
+            return Err(SandboxError::NotRoot);
+        }
+        Ok(Sandbox {
+            settings,
+            slots: Arc::new(Semaphore::new(settings.concurrency.max(1))),
+        })
+    }
+
+    pub async fn compile(
+        &self,
+        language: Language,
+        source: &str,
+    ) -> Result<Compiled, SandboxError> {
+        let toolchain = toolchain(language);
+        let dir = tempfile::Builder::new().prefix("program-").tempdir()?;
+        tokio::fs::write(dir.path().join(toolchain.file), source).await?;
+        chown(dir.path(), Some(NOBODY), Some(NOBODY))?;
+
+        if let Some(compile) = toolchain.compile {
+            let limits = Limits {
+                timeout: COMPILE_TIMEOUT,
+                memory_mb: COMPILE_MEMORY_MB,
+                output: MAX_STDERR,
+            };
+            let run = self.execute(dir.path(), compile, b"", limits).await?;
+            if !run.succeeded() {
+                let output = match run.status {
+                    Status::TimedOut => format!(
+                        "Compiling took longer than {} seconds",
+                        COMPILE_TIMEOUT.as_secs()
+                    ),
+                    _ => String::from_utf8_lossy(&run.stderr).into_owned(),
+                };
+                return Ok(Compiled::Failed(output));
+            }
+        }
+
+        Ok(Compiled::Ready(Program {
+            sandbox: self.clone(),
+            dir,
+            run: toolchain.run,
+        }))
+    }
+
+    async fn execute(
+        &self,
+        dir: &Path,
+        argv: &[&str],
+        input: &[u8],
+        limits: Limits,
+    ) -> Result<Run, SandboxError> {
+        let _slot = self.slots.acquire().await.expect("never closed");
+
+        // Everything the child needs is made up front - it can't allocate
+        // between fork and exec
+        let filter = seccomp::filter()?;
+        let hidden = hidden()?;
+        let dir = CString::new(dir.as_os_str().as_bytes()).expect("paths have no NULs");
+
+        let mut command = Command::new(argv[0]);
+        command
+            .args(&argv[1..])
+            .env_clear()
+            .env("PATH", PATH)
+            .env("HOME", OsStr::from_bytes(SCRATCH.to_bytes()))
+            .env("TMPDIR", OsStr::from_bytes(SCRATCH.to_bytes()))
+            .env("LANG", "C.UTF-8")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        // Safe since confine only makes raw syscalls
+        unsafe {
+            command.pre_exec(move || confine(&filter, &dir, &hidden, limits));
+        }
+
+        let started = Instant::now();
+        let mut child = command.spawn()?;
+        let pid = child.id().expect("it hasn't been waited on") as i32;
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let stderr = child.stderr.take().expect("stderr is piped");
+
+        let finish = async {
+            let write = async move {
+                // It's free to stop reading before the end
+                let _ = stdin.write_all(input).await;
+            };
+            let (_, stdout, stderr) = tokio::join!(
+                write,
+                read_capped(stdout, limits.output, Some(pid)),
+                read_capped(stderr, MAX_STDERR, None),
+            );
+            (stdout, stderr, child.wait().await)
+        };
+        let finished = tokio::time::timeout(limits.timeout, finish).await;
+        let runtime = started.elapsed();
+        // Anything it left behind. Killing its PID 1 takes down the rest of the
+        // namespace too
+        kill_group(pid);
+
+        let (stdout, stderr, status) = match finished {
+            Ok(finished) => finished,
+            Err(_) => {
+                return Ok(Run {
+                    status: Status::TimedOut,
+                    stdout: vec![],
+                    stderr: vec![],
+                    runtime,
+                })
+            }
+        };
+        let (stdout, too_long) = stdout?;
+        let (stderr, _) = stderr?;
+        let status = match (too_long, status?.code()) {
+            (true, _) => Status::OutputTooLong,
+            (false, Some(code)) => Status::Exited(code),
+            (false, None) => Status::Killed,
+        };
+
+        Ok(Run {
+            status,
+            stdout,
+            stderr,
+            runtime,
+        })
+    }
+
+    fn run_limits(&self) -> Limits {
+        Limits {
+            timeout: Duration::from_millis(self.settings.timeout_ms),
+            memory_mb: self.settings.memory_mb,
+            output: (self.settings.output_kb as usize) << 10,
+        }
+    }
+}
+
+impl Program {
+    pub async fn run(&self, input: &str) -> Result<Run, SandboxError> {
+        self.sandbox
+            .execute(
+                self.dir.path(),
+                self.run,
+                input.as_bytes(),
+                self.sandbox.run_limits(),
+            )
+            .await
+    }
+}
+
+fn hidden() -> io::Result<Vec<CString>> {
+    let mut paths: Vec<PathBuf> = HIDDEN.iter().map(PathBuf::from).collect();
+    let cwd = std::env::current_dir()?;
+    if cwd != Path::new("/") {
+        paths.push(cwd);
+    }
+    Ok(paths
+        .into_iter()
+        .filter(|path| path.is_dir())
+        .map(|path| CString::new(path.as_os_str().as_bytes()).expect("paths have no NULs"))
+        .collect())
+}
+
+// Reads up to `cap` bytes. Past that, it kills the program if it has a pid to
+// kill, or throws the rest away
+async fn read_capped(
+    mut reader: impl AsyncRead + Unpin,
+    cap: usize,
+    kill: Option<i32>,
+) -> io::Result<(Vec<u8>, bool)> {
+    let mut kept = vec![];
+    let mut buf = [0; 8192];
+    let mut over = false;
+    loop {
+        let read = reader.read(&mut buf).await?;
+        if read == 0 {
+            return Ok((kept, over));
+        }
+        let room = cap.saturating_sub(kept.len());
+        kept.extend_from_slice(&buf[..read.min(room)]);
+        if read > room {
+            over = true;
+            if let Some(pid) = kill {
+                kill_group(pid);
+                return Ok((kept, over));
+            }
+        }
+    }
+}
+
+fn kill_group(pid: i32) {
+    // Safe - the worst it can do is miss
+    unsafe {
+        libc::kill(-pid, libc::SIGKILL);
+    }
+}
+
+#[cfg(target_env = "gnu")]
+type Resource = libc::__rlimit_resource_t;
+#[cfg(not(target_env = "gnu"))]
+type Resource = libc::c_int;
+
+// Runs in the child between fork and exec, where only raw syscalls are safe -
+// nothing in here can allocate
+fn confine(
+    filter: &BpfProgram,
+    dir: &CString,
+    hidden: &[CString],
+    limits: Limits,
+) -> io::Result<()> {
+    unsafe {
+        // Its own process group, so the server can kill the whole run at once.
+        // The seccomp filter keeps anything from leaving it
+        check(libc::setpgid(0, 0))?;
+        check(libc::setgroups(0, std::ptr::null()))?;
+        check(libc::setgid(NOBODY))?;
+        check(libc::setuid(NOBODY))?;
+
+        // No one's mapped into the new user namespace, so whoever it runs as
+        // has no privileges outside of it. The new network namespace only has
+        // a loopback, and that's down
+        check(libc::unshare(
+            libc::CLONE_NEWUSER
+                | libc::CLONE_NEWNS
+                | libc::CLONE_NEWPID
+                | libc::CLONE_NEWNET
+                | libc::CLONE_NEWIPC
+                | libc::CLONE_NEWUTS,
+        ))?;
+        // Only what's forked from here on goes in the new PID namespace, so the
+        // program runs in a child, as its PID 1. This process stays behind for
+        // the server to wait on
+        let pid = libc::fork();
+        check(pid)?;
+        if pid > 0 {
+            supervise(pid);
+        }
+        // In case this process is killed on its own
+        check(libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL))?;
+
+        for path in hidden {
+            let covered = check(libc::mount(
+                c"none".as_ptr(),
+                path.as_ptr(),
+                c"tmpfs".as_ptr(),
+                libc::MS_RDONLY | libc::MS_NOSUID | libc::MS_NODEV | libc::MS_NOEXEC,
+                std::ptr::null(),
+            ));
+            match covered {
+                // It can't get there anyway, or it's under something already
+                // covered up
+                Err(e) if matches!(e.raw_os_error(), Some(libc::EACCES | libc::ENOENT)) => {}
+                covered => covered?,
+            }
+        }
+        check(libc::mount(
+            dir.as_ptr(),
+            SCRATCH.as_ptr(),
+            std::ptr::null(),
+            libc::MS_BIND | libc::MS_REC,
+            std::ptr::null(),
+        ))?;
+        check(libc::chdir(SCRATCH.as_ptr()))?;
+
+        limit(libc::RLIMIT_CPU, limits.timeout.as_secs() + 1)?;
+        limit(libc::RLIMIT_AS, limits.memory_mb << 20)?;
+        limit(libc::RLIMIT_FSIZE, MAX_FILE_SIZE)?;
+        limit(libc::RLIMIT_NOFILE, MAX_OPEN_FILES)?;
+        limit(libc::RLIMIT_CORE, 0)?;
+        limit(libc::RLIMIT_NPROC, MAX_PROCESSES)?;
+    }
+
+    seccompiler::apply_filter(filter).map_err(|_| io::Error::last_os_error())
+}
+
+// Waits out the program and exits the way it did, so the server sees how it
+// finished. Never returns, so it never gets as far as exec
+unsafe fn supervise(pid: libc::pid_t) -> ! {
+    // Holding on to its pipes would keep them open after the program's gone,
+    // and the server finds out exec happened when they close
+    libc::syscall(libc::SYS_close_range, 0, libc::c_uint::MAX, 0);
+    let mut status = 0;
+    while libc::waitpid(pid, &mut status, 0) == -1 {
+        if io::Error::last_os_error().raw_os_error() != Some(libc::EINTR) {
+            libc::_exit(127);
+        }
+    }
+    if libc::WIFSIGNALED(status) {
+        libc::kill(libc::getpid(), libc::SIGKILL);
+    }
+    libc::_exit(libc::WEXITSTATUS(status))
+}
+
+unsafe fn limit(resource: Resource, max: u64) -> io::Result<()> {
+    let rlimit = libc::rlimit {
+        rlim_cur: max,
+        rlim_max: max,
+    };
+    check(libc::setrlimit(resource, &rlimit))
+}
+
+fn check(result: libc::c_int) -> io::Result<()> {
+    if result == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Compiled, Program, Sandbox, SandboxError, Status};
+    use crate::config::SandboxSettings;
+    use crate::endpoints::messages::Language;
+
+    // These need root and the toolchains the languages run with, so they're
+    // skipped anywhere without them
+    fn sandbox(settings: SandboxSettings) -> Option<Sandbox> {
+        let sandbox = match Sandbox::new(settings) {
+            Ok(sandbox) => sandbox,
+            Err(SandboxError::NotRoot) => {
+                eprintln!("skipping: the sandbox only runs as root");
+                return None;
+            }
+            Err(e) => panic!("couldn't set up the sandbox: {}", e),
+        };
+        for tool in ["python3", "cc"] {
+            if std::process::Command::new(tool)
+                .arg("--version")
+                .output()
+                .is_err()
+            {
+                eprintln!("skipping: {} isn't installed", tool);
+                return None;
+            }
+        }
+        Some(sandbox)
+    }
+
+    fn limited() -> Option<Sandbox> {
+        sandbox(SandboxSettings {
+            timeout_ms: 1000,
+            output_kb: 1,
+            ..Default::default()
+        })
+    }
+
+    async fn compile(sandbox: &Sandbox, language: Language, source: &str) -> Program {
+        match sandbox.compile(language, source).await.unwrap() {
+            Compiled::Ready(program) => program,
+            Compiled::Failed(output) => panic!("didn't compile: {}", output),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_runs_programs() {
+        let Some(sandbox) = limited() else { return };
+        let python = compile(&sandbox, Language::Python, "print(input()[::-1])").await;
+        let run = python.run("ACGT\n").await.unwrap();
+        assert_eq!(run.status, Status::Exited(0));
+        assert_eq!(run.stdout, b"TGCA\n");
+
+        let c = compile(
+            &sandbox,
+            Language::C,
+            "#include <stdio.h>\nint main() { char s[16]; scanf(\"%15s\", s); printf(\"%s!\", s); }",
+        )
+        .await;
+        assert_eq!(c.run("ACGT").await.unwrap().stdout, b"ACGT!");
+    }
+
+    #[tokio::test]
+    async fn test_compile_errors() {
+        let Some(sandbox) = sandbox(SandboxSettings::default()) else {
+            return;
+        };
+        match sandbox.compile(Language::C, "int main() {").await.unwrap() {
+            Compiled::Failed(output) => assert!(output.contains("error")),
+            Compiled::Ready(_) => panic!("shouldn't have compiled"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_limits() {
+        let Some(sandbox) = limited() else { return };
+        let spin = compile(&sandbox, Language::Python, "while True: pass").await;
+        assert_eq!(spin.run("").await.unwrap().status, Status::TimedOut);
+
+        let chatty = compile(&sandbox, Language::Python, "while True: print('A' * 100)").await;
+        assert_eq!(chatty.run("").await.unwrap().status, Status::OutputTooLong);
+
+        let hungry = compile(&sandbox, Language::Python, "x = bytearray(1 << 30)").await;
+        assert_eq!(hungry.run("").await.unwrap().status, Status::Exited(1));
+    }
+
+    #[tokio::test]
+    async fn test_confinement() {
+        let Some(sandbox) = limited() else { return };
+        let network = compile(
+            &sandbox,
+            Language::Python,
+            "import socket\ntry:\n  socket.create_connection(('1.1.1.1', 53), timeout=1)\nexcept OSError:\n  print('blocked')",
+        )
+        .await;
+        assert_eq!(network.run("").await.unwrap().stdout, b"blocked\n");
+
+        // The server's working directory, where its config lives
+        let snoop = compile(
+            &sandbox,
+            Language::Python,
+            &format!(
+                "import os\nprint(os.path.exists({:?}))",
+                std::env::current_dir().unwrap().join("Cargo.toml")
+            ),
+        )
+        .await;
+        assert_eq!(snoop.run("").await.unwrap().stdout, b"False\n");
+
+        // Other programs' directories
+        let other = compile(&sandbox, Language::Python, "").await;
+        let look = compile(
+            &sandbox,
+            Language::Python,
+            &format!("import os\nprint(os.path.exists({:?}))", other.dir.path()),
+        )
+        .await;
+        assert_eq!(look.run("").await.unwrap().stdout, b"False\n");
+
+        // Everyone else's programs, and the server
+        let signal = compile(
+            &sandbox,
+            Language::Python,
+            "import os\nprint(os.getpid())\ntry:\n  os.kill(-1, 9)\nexcept OSError:\n  print('refused')",
+        )
+        .await;
+        assert_eq!(signal.run("").await.unwrap().stdout, b"1\nrefused\n");
+    }
+
+    #[tokio::test]
+    async fn test_nothing_outlives_a_run() {
+        let Some(sandbox) = limited() else { return };
+        // A child that tries to get away, and leaves a file behind if it's
+        // still around after the run's over
+        let escape = compile(
+            &sandbox,
+            Language::Python,
+            "import os, time\nif os.fork() == 0:\n  try:\n    os.setsid()\n  except OSError:\n    pass\n  time.sleep(1)\n  open('escaped', 'w').close()",
+        )
+        .await;
+        assert_eq!(escape.run("").await.unwrap().status, Status::Exited(0));
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        assert!(!escape.dir.path().join("escaped").exists());
+    }
+}
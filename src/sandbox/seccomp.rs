@@ -0,0 +1,104 @@
+use std::collections::BTreeMap;
+
+use seccompiler::{
+    BpfProgram, SeccompAction, SeccompCmpArgLen, SeccompCmpOp, SeccompCondition, SeccompFilter,
+    SeccompRule,
+};
+
+// Syscalls a solution has no business making - anything that could get it out
+// of its namespaces, onto the network, or poking at other processes
+const DENIED: &[libc::c_long] = &[
+    libc::SYS_ptrace,
+    libc::SYS_process_vm_readv,
+    libc::SYS_process_vm_writev,
+    libc::SYS_mount,
+    libc::SYS_umount2,
+    libc::SYS_pivot_root,
+    libc::SYS_chroot,
+    libc::SYS_unshare,
+    libc::SYS_setns,
+    libc::SYS_socket,
+    libc::SYS_bpf,
+    libc::SYS_perf_event_open,
+    libc::SYS_userfaultfd,
+    libc::SYS_io_uring_setup,
+    libc::SYS_keyctl,
+    libc::SYS_add_key,
+    libc::SYS_request_key,
+    libc::SYS_open_by_handle_at,
+    libc::SYS_name_to_handle_at,
+    libc::SYS_init_module,
+    libc::SYS_finit_module,
+    libc::SYS_delete_module,
+    libc::SYS_kexec_load,
+    libc::SYS_reboot,
+    libc::SYS_swapon,
+    libc::SYS_swapoff,
+    libc::SYS_acct,
+    libc::SYS_syslog,
+    libc::SYS_settimeofday,
+    libc::SYS_clock_settime,
+    libc::SYS_adjtimex,
+    // Either would take it out of the process group the server kills when the
+    // run's over
+    libc::SYS_setsid,
+    libc::SYS_setpgid,
+    // Its arguments are behind a pointer, so there's no checking its flags like
+    // clone's below. ENOSYS makes libc fall back to clone
+    libc::SYS_clone3,
+];
+
+const NAMESPACES: &[libc::c_int] = &[
+    libc::CLONE_NEWUSER,
+    libc::CLONE_NEWNS,
+    libc::CLONE_NEWNET,
+    libc::CLONE_NEWPID,
+    libc::CLONE_NEWIPC,
+    libc::CLONE_NEWUTS,
+    libc::CLONE_NEWCGROUP,
+];
+
+// Denied syscalls fail with ENOSYS, as if the kernel didn't have them - the
+// program carries on and can cope, instead of dying somewhere confusing
+pub fn filter() -> Result<BpfProgram, seccompiler::Error> {
+    let mut rules: BTreeMap<i64, Vec<SeccompRule>> =
+        DENIED.iter().map(|&syscall| (syscall, vec![])).collect();
+
+    // Threads and processes are fine, new namespaces aren't
+    let new_namespace = NAMESPACES
+        .iter()
+        .map(|&flag| {
+            let flag = flag as u64;
+            let condition = SeccompCondition::new(
+                0,
+                SeccompCmpArgLen::Qword,
+                SeccompCmpOp::MaskedEq(flag),
+                flag,
+            )?;
+            SeccompRule::new(vec![condition])
+        })
+        .collect::<Result<_, _>>()?;
+    rules.insert(libc::SYS_clone, new_namespace);
+
+    // Its own processes are fine, but not whole groups or every process it can
+    // see - a pid of 0 or less
+    let pid = |op, value| {
+        SeccompCondition::new(0, SeccompCmpArgLen::Dword, op, value)
+            .and_then(|condition| SeccompRule::new(vec![condition]))
+    };
+    rules.insert(
+        libc::SYS_kill,
+        vec![
+            pid(SeccompCmpOp::Eq, 0)?,
+            pid(SeccompCmpOp::MaskedEq(1 << 31), 1 << 31)?,
+        ],
+    );
+
+    let filter = SeccompFilter::new(
+        rules,
+        SeccompAction::Allow,
+        SeccompAction::Errno(libc::ENOSYS as u32),
+        std::env::consts::ARCH.try_into()?,
+    )?;
+    Ok(filter.try_into()?)
+}
@@ -0,0 +1,129 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use super::Delivery;
+
+pub const SIGNATURE_HEADER: &str = "X-Generate-Signature";
+pub const EVENT_HEADER: &str = "X-Generate-Event";
+pub const DELIVERY_HEADER: &str = "X-Generate-Delivery";
+
+// Sends one delivery, returning the response status or why it didn't work
+pub async fn deliver(
+    http: &reqwest::Client,
+    secret: &str,
+    delivery: &Delivery,
+) -> Result<u16, (Option<u16>, String)> {
+    let body = serde_json::to_vec(&delivery.payload).map_err(|e| (None, e.to_string()))?;
+
+    let res = http
+        .post(&delivery.url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(EVENT_HEADER, &delivery.event)
+        .header(DELIVERY_HEADER, delivery.delivery_id)
+        .header(SIGNATURE_HEADER, sign(secret, &body))
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| (None, e.to_string()))?;
+
+    let status = res.status();
+    if status.is_success() {
+        Ok(status.as_u16())
+    } else {
+        Err((
+            Some(status.as_u16()),
+            format!("Receiver responded with {}", status),
+        ))
+    }
+}
+
+// Receivers recompute this over the raw body with their copy of the secret
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any size");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use chrono::Utc;
+    use serde_json::json;
+    use tokio::sync::mpsc;
+    use warp::http::HeaderMap;
+    use warp::hyper::body::Bytes;
+    use warp::Filter;
+
+    use super::{deliver, sign, DELIVERY_HEADER, EVENT_HEADER, SIGNATURE_HEADER};
+    use crate::webhooks::Delivery;
+
+    fn delivery(url: String) -> Delivery {
+        Delivery {
+            delivery_id: 7,
+            url,
+            event: String::from("applicant.solved"),
+            payload: json!({"event": "applicant.solved", "data": {"nuid": "001453760"}}),
+            status: String::from("pending"),
+            attempts: 0,
+            created_at: Utc::now(),
+            last_attempt_at: None,
+            last_response_status: None,
+            last_error: None,
+        }
+    }
+
+    // A receiver that hands back everything it's sent, and answers with `status`
+    async fn stand_in(status: u16) -> (SocketAddr, mpsc::UnboundedReceiver<(HeaderMap, Bytes)>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let hook = warp::post()
+            .and(warp::header::headers_cloned())
+            .and(warp::body::bytes())
+            .map(move |headers, body| {
+                sender.send((headers, body)).unwrap();
+                warp::reply::with_status("", warp::http::StatusCode::from_u16(status).unwrap())
+            });
+
+        let (addr, server) = warp::serve(hook).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        (addr, receiver)
+    }
+
+    #[test]
+    fn test_sign() {
+        // Known HMAC-SHA256 test vector
+        assert_eq!(
+            sign("key", b"The quick brown fox jumps over the lazy dog"),
+            "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_deliveries_are_signed() {
+        let (addr, mut received) = stand_in(200).await;
+        let delivery = delivery(format!("http://{}/hook", addr));
+
+        let status = deliver(&reqwest::Client::new(), "shh", &delivery).await;
+        let (headers, body) = received.recv().await.unwrap();
+
+        assert_eq!(status, Ok(200));
+        assert_eq!(headers[SIGNATURE_HEADER], sign("shh", &body));
+        assert_eq!(headers[EVENT_HEADER], "applicant.solved");
+        assert_eq!(headers[DELIVERY_HEADER], "7");
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap(),
+            delivery.payload
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_deliveries() {
+        let (addr, _received) = stand_in(503).await;
+        let delivery = delivery(format!("http://{}/hook", addr));
+
+        let status = deliver(&reqwest::Client::new(), "shh", &delivery).await;
+
+        assert!(matches!(status, Err((Some(503), _))));
+    }
+}
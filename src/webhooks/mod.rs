@@ -0,0 +1,146 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::json;
+use sqlx::{PgPool, Postgres, Transaction};
+use utoipa::ToSchema;
+
+use crate::config::WebhookSettings;
+use crate::db;
+use crate::endpoints::errors::ModelError;
+use crate::jobs::{self, Job, JobError};
+use crate::model::events::Event;
+
+pub mod delivery;
+
+pub use delivery::deliver;
+
+// A row of the webhook_deliveries table - the log of every webhook we've sent
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct Delivery {
+    pub delivery_id: i64,
+    pub url: String,
+    pub event: String,
+    #[schema(value_type = Object)]
+    pub payload: serde_json::Value,
+    // pending, delivered or failed (gave up retrying)
+    pub status: String,
+    pub attempts: i32,
+    pub created_at: DateTime<Utc>,
+    pub last_attempt_at: Option<DateTime<Utc>>,
+    pub last_response_status: Option<i32>,
+    pub last_error: Option<String>,
+}
+
+// The configured webhooks - cheap to clone
+#[derive(Clone, Debug, Default)]
+pub struct Webhooks {
+    hooks: Arc<Vec<WebhookSettings>>,
+}
+
+impl Webhooks {
+    pub fn new(hooks: Vec<WebhookSettings>) -> Webhooks {
+        Webhooks {
+            hooks: Arc::new(hooks),
+        }
+    }
+
+    // Logs a delivery and queues a job to send it for every webhook subscribed
+    // to this event
+    pub async fn fan_out(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        event: Event,
+        data: serde_json::Value,
+    ) -> Result<(), JobError> {
+        let payload = json!({
+            "event": event.as_str(),
+            "occurred_at": Utc::now(),
+            "data": data,
+        });
+
+        for hook in self.hooks.iter().filter(|hook| hook.subscribes_to(event)) {
+            let delivery_id =
+                db::webhooks::create_delivery_db(tx, &hook.url, event.as_str(), &payload).await?;
+            jobs::enqueue(tx, &Job::DeliverWebhook { delivery_id }).await?;
+        }
+
+        Ok(())
+    }
+
+    fn secret_for(&self, url: &str) -> Option<&str> {
+        self.hooks
+            .iter()
+            .find(|hook| hook.url == url)
+            .map(|hook| hook.secret.as_str())
+    }
+}
+
+impl WebhookSettings {
+    // No filter means every event
+    fn subscribes_to(&self, event: Event) -> bool {
+        self.events.is_empty() || self.events.iter().any(|e| e == event.as_str())
+    }
+}
+
+// Sends a logged delivery and records how it went. Failing here hands the retry
+// back to the job runner
+pub async fn deliver_queued(
+    pool: &PgPool,
+    http: &reqwest::Client,
+    webhooks: &Webhooks,
+    delivery_id: i64,
+    last_attempt: bool,
+) -> Result<(), JobError> {
+    let delivery = db::webhooks::get_delivery_db(pool, delivery_id).await?;
+
+    let secret = match webhooks.secret_for(&delivery.url) {
+        Some(secret) => secret,
+        None => {
+            // The webhook was taken out of the config after this was queued
+            let msg = "No webhook is configured for this url";
+            db::webhooks::record_attempt_db(pool, delivery_id, "failed", None, Some(msg.into()))
+                .await?;
+            return Err(JobError::permanent(msg));
+        }
+    };
+
+    match deliver(http, secret, &delivery).await {
+        Ok(status) => {
+            info!("Delivered webhook {} to {}", delivery_id, delivery.url);
+            db::webhooks::record_attempt_db(
+                pool,
+                delivery_id,
+                "delivered",
+                Some(status as i32),
+                None,
+            )
+            .await?;
+            Ok(())
+        }
+        Err((status, e)) => {
+            let state = if last_attempt { "failed" } else { "pending" };
+            db::webhooks::record_attempt_db(
+                pool,
+                delivery_id,
+                state,
+                status.map(|s| s as i32),
+                Some(e.clone()),
+            )
+            .await?;
+            Err(JobError::retry(e))
+        }
+    }
+}
+
+pub async fn get_deliveries(
+    pool: &PgPool,
+    status: Option<String>,
+    limit: i64,
+) -> Result<Vec<Delivery>, ModelError> {
+    match db::webhooks::get_deliveries_db(pool, status, limit).await {
+        Ok(deliveries) => Ok(deliveries),
+        Err(_) => Err(ModelError::SqlError),
+    }
+}